@@ -39,20 +39,40 @@ fn impl_is_dao(ast: &syn::MacroInput) -> quote::Tokens {
         },
         syn::Body::Enum(_) => panic!("#[derive(NumFields)] can only be used with structs"),
     };
+    // the Option fields are None when the column is NULL,
+    // ie: the aggregates of the results that are not from a table
     let from_fields:Vec<quote::Tokens> =
-            fields.iter().map(|&(field,_ty)| {
-                        quote!{
-                            #field: {
-                                    let v = dao.get(stringify!(#field)).unwrap();
-                                    FromValue::from_type(v.to_owned())
-                                },
+            fields.iter().map(|&(field,ty)| {
+                        if is_option(ty) {
+                            quote!{
+                                #field: dao.get(stringify!(#field))
+                                    .map(|v| FromValue::from_type(v.to_owned())),
+                            }
+                        } else {
+                            quote!{
+                                #field: {
+                                        let v = match dao.get(stringify!(#field)) {
+                                            Some(v) => v,
+                                            None => panic!("{} has no value for {}", stringify!(#name), stringify!(#field)),
+                                        };
+                                        FromValue::from_type(v.to_owned())
+                                    },
+                            }
                         }
                     }).collect::<Vec<_>>();
 
     let to_dao:Vec<quote::Tokens> =
-            fields.iter().map(|&(field,_ty)| {
-                        quote!{
-                            dao.insert(stringify!(#field).to_string(), self.#field.to_db_type());
+            fields.iter().map(|&(field,ty)| {
+                        if is_option(ty) {
+                            quote!{
+                                if let Some(ref v) = self.#field {
+                                    dao.insert(stringify!(#field).to_string(), v.to_db_type());
+                                }
+                            }
+                        } else {
+                            quote!{
+                                dao.insert(stringify!(#field).to_string(), self.#field.to_db_type());
+                            }
                         }
                     }).collect::<Vec<_>>();
     quote! {
@@ -73,6 +93,18 @@ fn impl_is_dao(ast: &syn::MacroInput) -> quote::Tokens {
     }
 }
 
+fn is_option(ty: &syn::Ty) -> bool {
+    match *ty {
+        syn::Ty::Path(None, ref path) => {
+            match path.segments.last() {
+                Some(segment) => segment.ident == "Option",
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
 #[proc_macro_derive(IsTable)]
 pub fn to_table_name(input: TokenStream) -> TokenStream {
     // Construct a string representation of the type definition
@@ -299,6 +299,8 @@ pub struct DaoResult {
     pub page: Option<usize>,
     /// page size
     pub page_size: Option<usize>,
+    /// the position of the next page in keyset pagination
+    pub cursor: Option<String>,
 }
 
 
@@ -339,6 +341,17 @@ impl DaoResult {
         }
         Some(casted.remove(0))
     }
+
+    /// cast the dao to a struct that is not a table,
+    /// ie: the rows of a report with the aggregates of the groups,
+    /// the fields are matched with the names of the columns as returned
+    pub fn cast_as<T: IsDao>(&self) -> Vec<T> {
+        self.dao.iter().map(|dao| T::from_dao(dao)).collect()
+    }
+
+    pub fn cast_one_as<T: IsDao>(&self) -> Option<T> {
+        self.dao.first().map(|dao| T::from_dao(dao))
+    }
 }
 
 /// TODO: optimization, used enum types for the key values
@@ -507,68 +520,76 @@ pub trait FromValue {
     fn from_type(ty: Value) -> Self;
 }
 
-impl FromValue for bool {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::Bool(x) => x,
-            _ => panic!("error!"),
-        }
+/// the integer of any type, sqlite keeps all the integers as `Value::I64`
+fn integer(ty: &Value) -> Option<i64> {
+    match *ty {
+        Value::I64(x) => Some(x),
+        Value::I32(x) => Some(x as i64),
+        Value::I16(x) => Some(x as i64),
+        Value::I8(x) => Some(x as i64),
+        Value::U64(x) if x <= i64::max_value() as u64 => Some(x as i64),
+        Value::U32(x) => Some(x as i64),
+        Value::U16(x) => Some(x as i64),
+        Value::U8(x) => Some(x as i64),
+        _ => None,
     }
 }
 
-impl FromValue for i8 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::I8(x) => x,
-            _ => panic!("error!"),
-        }
-    }
-}
-impl FromValue for i16 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::I16(x) => x,
-            _ => panic!("error!"),
-        }
-    }
-}
-impl FromValue for i32 {
+/// the booleans are integers in sqlite
+impl FromValue for bool {
     fn from_type(ty: Value) -> Self {
         match ty {
-            Value::I32(x) => x,
-            _ => panic!("error!"),
+            Value::Bool(x) => x,
+            ty => {
+                match integer(&ty) {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => panic!("error! {:?}", ty),
+                }
+            }
         }
     }
 }
+
+/// the integers of the other types are converted when the value fits
+macro_rules! impl_from_value_for_integer{
+	($t:ty, $variant:ident) => (
+		impl FromValue for $t {
+			fn from_type(ty: Value) -> Self {
+				match ty {
+					Value::$variant(x) => x,
+					ty => {
+						match integer(&ty) {
+							Some(x) if x >= <$t>::min_value() as i64 && x <= <$t>::max_value() as i64 => x as $t,
+							_ => panic!("error! {:?}", ty),
+						}
+					}
+				}
+			}
+		}
+	);
+}
+
+impl_from_value_for_integer!(i8, I8);
+impl_from_value_for_integer!(i16, I16);
+impl_from_value_for_integer!(i32, I32);
+impl_from_value_for_integer!(u8, U8);
+impl_from_value_for_integer!(u16, U16);
+impl_from_value_for_integer!(u32, U32);
+
+/// the narrower integers are widened, since the aggregates such as COUNT and SUM
+/// are not of the same integer type in every database
 impl FromValue for i64 {
     fn from_type(ty: Value) -> Self {
         match ty {
             Value::I64(x) => x,
-            _ => panic!("error!"),
-        }
-    }
-}
-impl FromValue for u8 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U8(x) => x,
-            _ => panic!("error!"),
-        }
-    }
-}
-impl FromValue for u16 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U16(x) => x,
-            _ => panic!("error!"),
-        }
-    }
-}
-impl FromValue for u32 {
-    fn from_type(ty: Value) -> Self {
-        match ty {
-            Value::U32(x) => x,
-            _ => panic!("error!"),
+            Value::I32(x) => x as i64,
+            Value::I16(x) => x as i64,
+            Value::I8(x) => x as i64,
+            Value::U32(x) => x as i64,
+            Value::U16(x) => x as i64,
+            Value::U8(x) => x as i64,
+            _ => panic!("error! {:?}", ty),
         }
     }
 }
@@ -576,33 +597,64 @@ impl FromValue for u64 {
     fn from_type(ty: Value) -> Self {
         match ty {
             Value::U64(x) => x,
-            _ => panic!("error!"),
+            ty => {
+                match integer(&ty) {
+                    Some(x) if x >= 0 => x as u64,
+                    _ => panic!("error! {:?}", ty),
+                }
+            }
         }
     }
 }
 
+/// the REAL of sqlite is a `Value::F64`
 impl FromValue for f32 {
     fn from_type(ty: Value) -> Self {
         match ty {
             Value::F32(x) => x,
-            _ => panic!("error!"),
+            Value::F64(x) => x as f32,
+            ty => {
+                match integer(&ty) {
+                    Some(x) => x as f32,
+                    None => panic!("error! {:?}", ty),
+                }
+            }
         }
     }
 }
+/// the integers are converted too, ie: AVG of an integer column in sqlite
+/// returns an integer when the average has no fraction
 impl FromValue for f64 {
     fn from_type(ty: Value) -> Self {
         match ty {
             Value::F64(x) => x,
+            Value::F32(x) => x as f64,
+            Value::I64(x) => x as f64,
+            Value::I32(x) => x as f64,
+            Value::I16(x) => x as f64,
+            Value::I8(x) => x as f64,
+            Value::U64(x) => x as f64,
+            Value::U32(x) => x as f64,
+            Value::U16(x) => x as f64,
+            Value::U8(x) => x as f64,
             _ => panic!("error! {:?}", ty),
         }
     }
 }
 
+/// the numbers are written as text, a text column of sqlite can have the values of any storage class
 impl FromValue for String {
     fn from_type(ty: Value) -> Self {
         match ty {
             Value::String(x) => x,
-            _ => panic!("error! {:?}", ty),
+            Value::F64(x) => x.to_string(),
+            Value::F32(x) => x.to_string(),
+            ty => {
+                match integer(&ty) {
+                    Some(x) => x.to_string(),
+                    None => panic!("error! {:?}", ty),
+                }
+            }
         }
     }
 }
@@ -1,11 +1,12 @@
-use query::Query;
 use table::Table;
 use dao::{Dao, DaoResult, Value};
 use writer::SqlFrag;
-use query::{Connector, Equality, Operand, Field};
-use query::{Direction, Modifier, NullsWhere, JoinType};
-use query::{Filter, Condition};
+use query::{Operand, Field};
+use query::Join;
+use query::Keyset;
+use query::Filter;
 use query::Range;
+use query::COUNT;
 use std::error::Error;
 use std::fmt;
 use r2d2;
@@ -17,12 +18,15 @@ use regex::Error as RegexError;
 #[cfg(feature = "sqlite")]
 use rusqlite::Error as SqliteError;
 use platform::PlatformError;
+use platform::statement_cache::CacheStats;
 use dao::Type;
-use query::source::{SourceField, QuerySource};
-use query::{Select,Insert,Update,Delete};
-use query::query::Data;
-use query::ColumnName;
+use query::{Query,Select,Insert,Update,Delete};
+use query::Rewriter;
+use query::TableName;
+use dialect::Dialect;
 
+/// the savepoint of the statements that are applied all or none inside the transaction of the caller
+const ATOMIC_SAVEPOINT: &'static str = "rustorm_atomic";
 
 /// SqlOption, contains the info about the features and quirks of underlying database
 #[derive(PartialEq)]
@@ -33,7 +37,7 @@ pub enum SqlOption {
     UsesQuestionMark,
     /// postgresql supports returning clause on insert and update
     SupportsReturningClause,
-    /// support CTE (common table expression ie. WITH) (postgresql, sqlite)
+    /// support CTE (common table expression ie. WITH) (postgresql, sqlite, mysql >= 8.0)
     SupportsCTE,
     /// supports inheritance (postgresql)
     SupportsInheritance,
@@ -42,6 +46,78 @@ pub enum SqlOption {
     /// wheter the returned rows in a query included Meta columns for easy extraction of records
     /// (postgres returns this), sqlite does not return meta columns, so you have to extract it by index yourself.
     ReturnMetaColumns,
+    /// supports case insensitive ILIKE (postgresql), emulated with LOWER(..) LIKE LOWER(..) on others
+    SupportsILike,
+    /// supports IS DISTINCT FROM (postgresql)
+    SupportsIsDistinctFrom,
+    /// supports the null safe equal operator `<=>` (mysql)
+    SupportsNullSafeEqual,
+    /// the null safe comparison with `IS` and `IS NOT` (sqlite)
+    SupportsIsOperator,
+    /// supports SIMILAR TO (postgresql)
+    SupportsSimilarTo,
+    /// uses the posix regex operator `~` (postgresql)
+    SupportsPosixRegex,
+    /// supports the REGEXP operator (mysql), sqlite has the operator but no regexp function by default
+    SupportsRegexp,
+    /// supports ARRAY[..] values (postgresql)
+    SupportsArray,
+    /// supports quantified comparison ANY/ALL with subqueries (postgresql, mysql)
+    SupportsAnyAll,
+    /// supports INSERT .. ON CONFLICT (postgresql, sqlite 3.24+)
+    SupportsOnConflict,
+    /// supports INSERT .. ON DUPLICATE KEY UPDATE (mysql)
+    SupportsOnDuplicateKey,
+    /// supports UPDATE .. FROM and DELETE .. USING (postgresql)
+    SupportsUpdateFrom,
+    /// supports UPDATE t JOIN .. and DELETE t FROM t JOIN .. (mysql)
+    SupportsMultiTableUpdate,
+    /// the tables have the implicit rowid column (sqlite)
+    HasRowid,
+    /// supports locking the selected rows with FOR UPDATE/FOR SHARE, NOWAIT and SKIP LOCKED
+    /// (postgresql >= 9.5, mysql >= 8.0)
+    SupportsRowLocking,
+    /// supports comparing row values `(a, b) > (?, ?)` (postgresql)
+    SupportsRowValues,
+    /// supports SELECT DISTINCT ON (..) (postgresql), emulated with ROW_NUMBER() on others
+    SupportsDistinctOn,
+    /// supports the window functions ie: `ROW_NUMBER() OVER (..)`
+    /// (postgresql, sqlserver, sqlite >= 3.25, mysql >= 8.0)
+    SupportsWindowFunctions,
+    /// supports LATERAL joins (postgresql, mysql >= 8.0.14)
+    SupportsLateral,
+    /// supports NULLS FIRST/LAST in the ORDER BY (postgresql, sqlite >= 3.30),
+    /// emulated with `CASE WHEN x IS NULL ..` on others
+    SupportsNullsOrdering,
+    /// quotes the identifiers with backticks (mysql), the others use double quotes
+    UsesBacktickQuote,
+    /// the backslash is an escape character in string literals (mysql)
+    UsesBackslashEscape,
+    /// has no boolean type, true and false are written as 1 and 0 (sqlite)
+    UsesIntegerBoolean,
+    /// bytes are written as `'\x..'::bytea` (postgresql), the others use `X'..'`
+    UsesByteaHex,
+    /// timestamps are written as `TIMESTAMP WITH TIME ZONE '..'` (postgresql),
+    /// the others use the text in UTC
+    SupportsTimestampTz,
+    /// uses the named parameters `@p1`, `@p2` (sql server)
+    UsesAtParam,
+    /// quotes the identifiers with brackets `[name]` (sql server)
+    UsesBracketQuote,
+    /// bytes are written as `0x..` (sql server)
+    UsesHexLiteral,
+    /// limits the rows with TOP (n) and OFFSET n ROWS FETCH NEXT n ROWS ONLY instead of LIMIT and OFFSET (sql server)
+    UsesOffsetFetch,
+    /// returns the inserted, updated or deleted rows with OUTPUT INSERTED.* / DELETED.* (sql server)
+    SupportsOutputClause,
+    /// recursive queries are declared with a plain WITH (sql server)
+    OmitsRecursiveKeyword,
+    /// full text search with to_tsvector and plainto_tsquery (postgresql)
+    SupportsTsVector,
+    /// full text search with MATCH on the fts5 virtual tables (sqlite)
+    SupportsFts5,
+    /// full text search with MATCH .. AGAINST on the FULLTEXT indexes (mysql)
+    SupportsMatchAgainst,
 }
 
 /// specifies if the sql will be build in debug mode for debugging purposed
@@ -54,6 +130,18 @@ pub enum BuildMode {
     Standard,
 }
 
+/// specifies when the table names, column names and aliases are quoted
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum IdentifierQuoting {
+    /// quote all the identifiers
+    Always,
+    /// quote only the identifiers that are reserved words, mixed-case or have special characters
+    WhenNeeded,
+}
+
 #[derive(Debug)]
 pub enum DbError {
     Error(String),
@@ -144,24 +232,103 @@ impl From<SqliteError> for DbError {
 /// This is the database interface which will should be implemented to you the specifics of each database platform
 /// At least all methods on this trait should be implemented for target deployment database
 /// A lower level API for manipulating objects in the database
-///
-/// TODO: acquire only a connection until a query is about to be executed.
-/// generating query don't really need database connection just yet.
+/// The sql is generated by the Dialect of the database, which doesn't need the connection
 
 pub trait Database {
+    /// the sql generation of this database, all the queries are built with it
+    fn dialect(&self) -> &Dialect;
+
     /// return the version of the database
     /// lower version of database has fewer supported features
     fn version(&self) -> Result<String, DbError>;
 
     /// begin database transaction
-    fn begin(&self);
+    fn begin(&self) -> Result<(), DbError>;
 
     /// commit database transaction
-    fn commit(&self);
+    fn commit(&self) -> Result<(), DbError>;
 
     /// rollback data changes executed prior to calling the begin method
-    fn rollback(&self);
+    fn rollback(&self) -> Result<(), DbError>;
+
+    /// a transaction is begun and not yet committed or rolled back
+    fn in_transaction(&self) -> bool;
+
+    /// the statements of insert_all, update_all and delete_with_return are applied all or none,
+    /// in their own transaction, or in a savepoint when the caller already began a transaction
+    /// so the transaction of the caller is not committed early.
+    /// Returns whether a savepoint is used
+    fn begin_atomic(&self) -> Result<bool, DbError> {
+        if self.in_transaction() {
+            try!(self.execute_sql(&format!("SAVEPOINT {}", ATOMIC_SAVEPOINT), &[]));
+            Ok(true)
+        } else {
+            try!(self.begin());
+            Ok(false)
+        }
+    }
+
+    fn commit_atomic(&self, is_savepoint: bool) -> Result<(), DbError> {
+        if is_savepoint {
+            try!(self.execute_sql(&format!("RELEASE SAVEPOINT {}", ATOMIC_SAVEPOINT), &[]));
+            Ok(())
+        } else {
+            self.commit()
+        }
+    }
+
+    /// only the statements since the savepoint are undone, the transaction of the caller goes on
+    fn rollback_atomic(&self, is_savepoint: bool) -> Result<(), DbError> {
+        if is_savepoint {
+            try!(self.execute_sql(&format!("ROLLBACK TO SAVEPOINT {}", ATOMIC_SAVEPOINT), &[]));
+            Ok(())
+        } else {
+            self.rollback()
+        }
+    }
+
+
+    /// the rewriters applied to the queries before the sql is built
+    fn rewriters(&self) -> &[Box<Rewriter>] {
+        &[]
+    }
+
+    /// apply the registered rewriters in the order they were added
+    fn rewrite(&self, query: Query) -> Result<Query, DbError> {
+        let mut query = query;
+        for rewriter in self.rewriters() {
+            query = try!(rewriter.rewrite(query));
+        }
+        Ok(query)
+    }
+
+    fn rewrite_select(&self, query: &Select) -> Result<Select, DbError> {
+        match try!(self.rewrite(Query::Select(query.to_owned()))) {
+            Query::Select(select) => Ok(select),
+            _ => Err(DbError::new("The rewritten select is no longer a select")),
+        }
+    }
+
+    fn rewrite_insert(&self, query: &Insert) -> Result<Insert, DbError> {
+        match try!(self.rewrite(Query::Insert(query.to_owned()))) {
+            Query::Insert(insert) => Ok(insert),
+            _ => Err(DbError::new("The rewritten insert is no longer an insert")),
+        }
+    }
+
+    fn rewrite_update(&self, query: &Update) -> Result<Update, DbError> {
+        match try!(self.rewrite(Query::Update(query.to_owned()))) {
+            Query::Update(update) => Ok(update),
+            _ => Err(DbError::new("The rewritten update is no longer an update")),
+        }
+    }
 
+    fn rewrite_delete(&self, query: &Delete) -> Result<Delete, DbError> {
+        match try!(self.rewrite(Query::Delete(query.to_owned()))) {
+            Query::Delete(delete) => Ok(delete),
+            _ => Err(DbError::new("The rewritten delete is no longer a delete")),
+        }
+    }
 
     /// select
     /// returns an array to the qualified records
@@ -173,7 +340,8 @@ pub trait Database {
     /// insert an object, returns the inserted Dao value
     /// including the value generated via the defaults
     fn insert(&self, query: &Insert) -> Result<Dao, DbError> {
-        let sql_frag = self.build_insert(query, &BuildMode::Standard);
+        let query = &try!(self.rewrite_insert(query));
+        let sql_frag = try!(self.dialect().build_insert(query, &BuildMode::Standard).checked());
         match self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params) {
             Ok(Some(result)) => Ok(result),
             Ok(None) => Err(DbError::new("No result from insert")),
@@ -181,10 +349,53 @@ pub trait Database {
         }
     }
 
+    /// insert multiple rows, split into several statements
+    /// to stay under the maximum number of parameters of the database,
+    /// the statements are executed in a transaction, or in a savepoint of the transaction already begun,
+    /// so either all or none of the rows are inserted.
+    /// returns the inserted Dao values when the RETURNING clause is supported,
+    /// otherwise an empty list
+    fn insert_all(&self, query: &Insert) -> Result<Vec<Dao>, DbError> {
+        let query = &try!(self.rewrite_insert(query));
+        let returns = !query.return_columns.is_empty() &&
+                      self.dialect().sql_options().contains(&SqlOption::SupportsReturningClause);
+        let batches = query.batches(self.max_parameters());
+        let insert_batches = || -> Result<Vec<Dao>, DbError> {
+            let mut inserted = vec![];
+            for batch in &batches {
+                let sql_frag = try!(self.dialect().build_insert(batch, &BuildMode::Standard).checked());
+                if returns {
+                    let mut daos = try!(self.execute_sql_with_return(&sql_frag.sql, &sql_frag.params));
+                    inserted.append(&mut daos);
+                } else {
+                    try!(self.execute_sql(&sql_frag.sql, &sql_frag.params));
+                }
+            }
+            Ok(inserted)
+        };
+        if batches.len() == 1 {
+            return insert_batches();
+        }
+        let is_savepoint = try!(self.begin_atomic());
+        let result = insert_batches();
+        match result {
+            Ok(daos) => {
+                try!(self.commit_atomic(is_savepoint));
+                Ok(daos)
+            }
+            Err(e) => {
+                // the error of the statement is more useful than the error of the rollback
+                let _ = self.rollback_atomic(is_savepoint);
+                Err(e)
+            }
+        }
+    }
+
     /// update
     /// returns the updated Dao
     fn update(&self, query: &Update) -> Result<Dao,DbError>{
-        let sql_frag = self.build_update(query, &BuildMode::Standard);
+        let query = &try!(self.rewrite_update(query));
+        let sql_frag = try!(self.dialect().build_update(query, &BuildMode::Standard).checked());
         match self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params) {
             Ok(Some(result)) => Ok(result),
             Ok(None) => Err(DbError::new("No result from insert")),
@@ -193,12 +404,118 @@ pub trait Database {
     }
 
 
+    /// update the records, returns all the updated Dao
+    /// emulated by selecting the matching records then updating them inside a transaction
+    /// when the RETURNING clause is not supported
+    fn update_all(&self, query: &Update) -> Result<Vec<Dao>, DbError> {
+        let query = &try!(self.rewrite_update(query));
+        if self.dialect().sql_options().contains(&SqlOption::SupportsReturningClause) {
+            let sql_frag = try!(self.dialect().build_update(query, &BuildMode::Standard).checked());
+            return self.execute_sql_with_return(&sql_frag.sql, &sql_frag.params);
+        }
+        // only the values can be applied to the selected records
+        for value in &query.values {
+            match *value {
+                Operand::Value(_) => (),
+                _ => return Err(DbError::new("Unable to return the updated records of computed values")),
+            }
+        }
+        let is_savepoint = try!(self.begin_atomic());
+        let result = self.select_affected(&query.table, &query.joins, &query.filters)
+            .and_then(|mut daos| {
+                let sql_frag = try!(self.dialect().build_update(query, &BuildMode::Standard).checked());
+                try!(self.execute_sql(&sql_frag.sql, &sql_frag.params));
+                for dao in &mut daos {
+                    for (column, value) in query.columns.iter().zip(query.values.iter()) {
+                        if let Operand::Value(ref value) = *value {
+                            dao.insert(column.column.to_owned(), value.to_owned());
+                        }
+                    }
+                }
+                Ok(daos)
+            });
+        match result {
+            Ok(daos) => {
+                try!(self.commit_atomic(is_savepoint));
+                Ok(daos)
+            }
+            Err(e) => {
+                // the error of the statement is more useful than the error of the rollback
+                let _ = self.rollback_atomic(is_savepoint);
+                Err(e)
+            }
+        }
+    }
+
+    /// delete the records, returns the deleted Dao
+    /// emulated by selecting the matching records then deleting them inside a transaction
+    /// when the RETURNING clause is not supported
+    fn delete_with_return(&self, query: &Delete) -> Result<Vec<Dao>, DbError> {
+        let query = &try!(self.rewrite_delete(query));
+        if self.dialect().sql_options().contains(&SqlOption::SupportsReturningClause) {
+            let sql_frag = try!(self.dialect().build_delete(query, &BuildMode::Standard).checked());
+            return self.execute_sql_with_return(&sql_frag.sql, &sql_frag.params);
+        }
+        let is_savepoint = try!(self.begin_atomic());
+        let result = self.select_affected(&query.from_table, &query.joins, &query.filters)
+            .and_then(|daos| {
+                let sql_frag = try!(self.dialect().build_delete(query, &BuildMode::Standard).checked());
+                try!(self.execute_sql(&sql_frag.sql, &sql_frag.params));
+                Ok(daos)
+            });
+        match result {
+            Ok(daos) => {
+                try!(self.commit_atomic(is_savepoint));
+                Ok(daos)
+            }
+            Err(e) => {
+                // the error of the statement is more useful than the error of the rollback
+                let _ = self.rollback_atomic(is_savepoint);
+                Err(e)
+            }
+        }
+    }
+
+    /// select the records of the table that will be affected by an update or delete,
+    /// the rows are locked until the end of the transaction so they can't change in between
+    fn select_affected(&self, table: &TableName, joins: &[Join], filters: &[Filter]) -> Result<Vec<Dao>, DbError> {
+        let mut select = Select::new();
+        select.enable_query_stat = false;
+        if self.dialect().sql_options().contains(&SqlOption::SupportsRowLocking) {
+            select.for_update();
+        }
+        if joins.is_empty() {
+            select.column("*");
+        } else {
+            select.column(&format!("{}.*", table.complete_name()));
+        }
+        select.from(table);
+        select.joins = joins.to_vec();
+        select.filters = filters.to_vec();
+        let sql_frag = try!(self.dialect().build_select(&select, &BuildMode::Standard).checked());
+        self.execute_sql_with_return(&sql_frag.sql, &sql_frag.params)
+    }
+
     /// execute query with return dao,
-    /// use the enumerated column for data extraction when db doesn't support returning the records column names
+    /// use the enumerated column for data extraction when db doesn't support returning the records column names.
+    /// the query is rewritten by the registered rewriters
     fn execute_with_return(&self, query: &Select) -> Result<DaoResult, DbError> {
-        let sql_frag = &self.build_select(query, &BuildMode::Standard);
+        let query = &try!(self.rewrite_select(query));
+        let sql_frag = &try!(self.dialect().build_select(query, &BuildMode::Standard).checked());
         let result = try!(self.execute_sql_with_return(&sql_frag.sql, &sql_frag.params));
-        if query.enable_query_stat{
+        if let Some(ref keyset) = query.keyset {
+            // the total count is not computed, it is as slow as the offset this is avoiding
+            let cursor = self.next_cursor(query, keyset, &result);
+            let dao_result = DaoResult {
+                dao: result,
+                renamed_columns: query.get_renamed_columns(),
+                total: None,
+                page: None,
+                page_size: query.range.limit,
+                cursor: cursor,
+            };
+            Ok(dao_result)
+        }else if query.enable_query_stat{
             let (page, page_size, total) = try!(self.get_query_stats(query));
             let dao_result = DaoResult {
                 dao: result,
@@ -206,6 +523,7 @@ pub trait Database {
                 total: total,
                 page: page,
                 page_size: page_size,
+                cursor: None,
             };
             Ok(dao_result)
         }else{
@@ -215,11 +533,33 @@ pub trait Database {
                 total: None,
                 page: None,
                 page_size: None,
+                cursor: None,
             };
             Ok(dao_result)
         }
     }
 
+    /// the cursor of the next page, from the keyset values of the last row,
+    /// there is no next page when this page is not full
+    fn next_cursor(&self, query: &Select, keyset: &Keyset, page: &[Dao]) -> Option<String> {
+        match query.range.limit {
+            Some(limit) if page.len() == limit => (),
+            _ => return None,
+        }
+        let last = match page.last() {
+            Some(last) => last,
+            None => return None,
+        };
+        let mut values = vec![];
+        for c in &keyset.columns {
+            match last.get(&c.column) {
+                Some(value) => values.push(value.to_owned()),
+                None => return None,
+            }
+        }
+        Some(Keyset::encode_cursor(&values))
+    }
+
     /// get the query stats page, page_size and the total records
     fn get_query_stats(&self,
                        query: &Select)
@@ -234,14 +574,26 @@ pub trait Database {
             None
         };
 
-        let mut count_query = query.to_owned();
-        count_query.enumerated_fields = vec![];//remove the enumerated fields
-        count_query.column("COUNT(*) AS COUNT");
-        count_query.order_by = vec![];
-        count_query.range = Range::new();//remove the range
-        let debug_sql = &self.build_select(&count_query, &BuildMode::Debug);
+        // the select is counted as a subquery, so the DISTINCT and GROUP BY are kept as they are
+        let mut counted = query.to_owned();
+        counted.order_by = vec![];
+        counted.range = Range::new();//remove the range
+        counted.lock = None;//aggregates can not be locked
+        counted.enable_query_stat = false;
+        if counted.enumerated_fields.is_empty() {
+            counted.column("*");
+        }
+        let mut count_query = Select::new();
+        count_query.enumerated_fields.push(Field {
+            operand: COUNT(&"*"),
+            name: Some("count".to_owned()),
+        });
+        count_query.from_query(counted, "t");
+        let debug_sql = &self.dialect().build_select(&count_query, &BuildMode::Debug);
         println!("STAT QUERY: {}", debug_sql);
-        let count_result = try!(self.execute_with_one_return(&count_query));
+        // the query is already rewritten
+        let sql_frag = try!(self.dialect().build_select(&count_query, &BuildMode::Standard).checked());
+        let count_result = try!(self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params));
         println!("range: {:#?}", query.range);
         println!("count result {:#?}", count_result);
         let total = if let Some(count_result) = count_result {
@@ -259,16 +611,18 @@ pub trait Database {
         Ok((page, query.range.limit, total))
     }
 
-    /// execute query with 1 return dao
+    /// execute query with 1 return dao, the query is rewritten by the registered rewriters
     fn execute_with_one_return(&self, query: &Select) -> Result<Option<Dao>, DbError> {
-        let sql_frag = &self.build_select(query, &BuildMode::Standard);
+        let query = &try!(self.rewrite_select(query));
+        let sql_frag = &try!(self.dialect().build_select(query, &BuildMode::Standard).checked());
         self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params)
     }
 
     /// delete records
     /// returns the number of deleted records
     fn delete(&self, query: &Delete) -> Result<usize, DbError> {
-        let sql_frag = &self.build_delete(query, &BuildMode::Standard);
+        let query = &try!(self.rewrite_delete(query));
+        let sql_frag = &try!(self.dialect().build_delete(query, &BuildMode::Standard).checked());
         self.execute_sql(&sql_frag.sql, &sql_frag.params)
     }
 
@@ -290,460 +644,21 @@ pub trait Database {
     /// everything else, no required return other than error or affected number of records
     fn execute_sql(&self, sql: &str, param: &[Value]) -> Result<usize, DbError>;
 
-    /// build a query, return the sql string and the parameters.
-    /// use by select to build the select query
-    /// build all types of query
-    /// TODO: need to supply the number of parameters where to start the numbering of the number parameters
-    fn build_query(&self, query: &Query, build_mode: &BuildMode) -> SqlFrag {
-        match *query {
-            Query::Select(ref select) => self.build_select(select, build_mode),
-            Query::Insert(ref insert) => self.build_insert(insert, build_mode),
-            Query::Update(ref update) => self.build_update(update, build_mode),
-            Query::Delete(ref delete) => self.build_delete(delete, build_mode),
-        }
-    }
-
-    /// build operand, i.e: columns, query, function, values
-    fn build_operand(&self, w: &mut SqlFrag, use_complete_name: bool, operand: &Operand) {
-        match *operand {
-            Operand::ColumnName(ref column_name) => {
-                if use_complete_name {
-                    w.append(&column_name.column);
-                } else {
-                    w.append(&column_name.complete_name());
-                }
-            }
-            Operand::QuerySource(ref query_source) => {
-                self.build_query_source(w, query_source);
-            }
-            Operand::Value(ref value) => {
-                w.parameter(value.clone());
-            }
-            Operand::Vec(ref operands) => {
-                let mut do_comma = false;
-                if !operands.is_empty() {
-                    w.append("(");
-                    for op in operands {
-                        if do_comma {
-                            w.commasp();
-                        } else {
-                            do_comma = true;
-                        }
-                        self.build_operand(w, use_complete_name, op);
-                    }
-                    w.append(")");
-                }
-            }
-            Operand::None => (), //dont do anything
-        }
-    }
-
-    fn build_condition(&self, w: &mut SqlFrag, use_complete_name: bool, cond: &Condition) {
-        self.build_operand(w, use_complete_name, &cond.left);
-        w.append(" ");
-        match cond.equality {
-            Equality::EQ => {
-                w.append("= ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::NEQ => {
-                w.append("!= ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::LT => {
-                w.append("< ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::LTE => {
-                w.append("<= ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::GT => {
-                w.append("> ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::GTE => {
-                w.append(">= ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::IN => {
-                w.append("IN ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::NOT_IN => {
-                w.append("NOT IN ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::LIKE => {
-                w.append("LIKE ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::ILIKE => {
-                w.append("ILIKE ");
-                self.build_operand(w, use_complete_name, &cond.right);
-            }
-            Equality::IS_NOT_NULL => {
-                w.append("IS NOT NULL");
-            }
-
-            Equality::IS_NULL => {
-                w.append("IS NULL");
-            }
-        }
-    }
-
-    fn build_field(&self, w: &mut SqlFrag, use_complete_name: bool, field: &Field) {
-        self.build_operand(w, use_complete_name, &field.operand);
-        match field.name {
-            Some(ref name) => {
-                w.append(" AS ");
-                w.append(name);
-            }
-            None => (),
-        }
-    }
-
-    fn build_query_source(&self,
-                          w: &mut SqlFrag,
-                          query_source: &QuerySource) {
-        match *query_source {
-            QuerySource::TableName(ref table_name) => {
-                if self.sql_options().contains(&SqlOption::UsesSchema) {
-                    w.append(&table_name.complete_name());
-                } else {
-                    w.append(&table_name.name);
-                }
-            }
-            QuerySource::Function(ref function) => {
-                w.sp();
-                w.append(&function.function);
-                w.append("(");
-                let mut do_comma = false;
-                for param in &function.params {
-                    if do_comma {
-                        w.commasp();
-                    } else {
-                        do_comma = true;
-                    }
-                    self.build_operand(w, false, param);
-                }
-                w.append(")");
-            }
-            QuerySource::Query(ref _q) => {
-                let sql_frag = &self.build_select(&_q, &w.build_mode);
-                w.append(&sql_frag.sql);
-            }
-        }
-    }
-
-    fn build_source_field(&self,
-                          w: &mut SqlFrag,
-                          source_field: &SourceField) {
-        self.build_query_source(w, &source_field.source);
-        match source_field.rename {
-            Some(ref rename) => {
-                w.append(" AS ");
-                w.append(rename);
-            }
-            None => (),
-        }
-    }
-
-    fn build_filter(&self, w: &mut SqlFrag, use_complete_name: bool, filter: &Filter) {
-        if !filter.sub_filters.is_empty() {
-            w.append("( ");
-        }
-        self.build_condition(w, use_complete_name, &filter.condition);
-        w.sp();
-        for filt in &filter.sub_filters {
-            match filt.connector {
-                Connector::And => {
-                    w.append("AND ");
-                }
-                Connector::Or => {
-                    w.append("OR ");
-                }
-            }
-            self.build_filter(w, use_complete_name, filt);// build sub filters as well
-        }
-        if !filter.sub_filters.is_empty() {
-            w.append(" )");
-        }
-    }
-
-    /// build the filter clause or the where clause of the query
-    /// TODO: add the sub filters
-    fn build_filters(&self, w: &mut SqlFrag, use_complete_name: bool, filters: &[Filter]) {
-        let mut do_and = false;
-        for filter in filters {
-            if do_and {
-                w.left_river(" AND ");
-            } else {
-                do_and = true;
-            }
-            self.build_filter(w, use_complete_name, filter);
-        }
-    }
-
-    /// build the enumerated, distinct, *, columns
-    fn build_enumerated_fields(&self,
-                               w: &mut SqlFrag,
-                               use_complete_name: bool, 
-                               enumerated_fields: &[Field]) {
-        let mut do_comma = false;
-        let mut cnt = 0;
-        for field in enumerated_fields {
-            if do_comma {
-                w.commasp();
-            } else {
-                do_comma = true;
-            }
-            cnt += 1;
-            if cnt % 4 == 0 {
-                // break at every 4 columns to encourage sql tuning/revising
-                w.left_river("");
-            }
-            self.build_field(w, use_complete_name, field);
-        }
-    }
-
-    /// build the select statment from the query object
-    fn build_select(&self, query: &Select, build_mode: &BuildMode) -> SqlFrag {
-        let use_complete_name = query.joins.is_empty();
-        let mut w = SqlFrag::new(self.sql_options(), build_mode);
-        w.left_river("SELECT");
-        self.build_enumerated_fields(&mut w, use_complete_name, &query.enumerated_fields); //TODO: add support for column_sql, fields, functions
-        w.left_river("FROM");
-
-        assert!(!query.from.is_empty(),
-                "There should be table, query, function to select from");
-        let mut do_comma = false;
-        for field in &query.from {
-            if do_comma {
-                w.commasp();
-            } else {
-                do_comma = true;
-            }
-            self.build_source_field(&mut w, field);
-        }
-        if !query.joins.is_empty() {
-            for join in &query.joins {
-                match join.modifier {
-                    Some(ref modifier) => {
-                        match *modifier {
-                            Modifier::LEFT => w.right_river("LEFT "),
-                            Modifier::RIGHT => w.right_river("RIGHT "),
-                            Modifier::FULL => w.right_river("FULL "),
-                        };
-                    }
-                    None => (),
-                }
-                match join.join_type {
-                    Some(ref join_type) => {
-                        match *join_type {
-                            JoinType::CROSS => w.right_river("CROSS "),
-                            JoinType::INNER => w.right_river("INNER "),
-                            JoinType::OUTER => w.right_river("OUTER "),
-                            JoinType::NATURAL => w.right_river("NATURAL "),
-                        };
-                    }
-                    None => (),
-                }
-                w.append("JOIN ");
-                w.append(&join.table_name.complete_name());
-                w.right_river("ON ");
-                self.build_filter(&mut w, use_complete_name, &join.on);
-            }
-        }
-
-        if !query.filters.is_empty() {
-            w.left_river("WHERE ");
-            self.build_filters(&mut w, use_complete_name, &query.filters);
-        }
-
-        if !query.group_by.is_empty() {
-            w.left_river("GROUP BY ");
-            let mut do_comma = false;
-            for operand in &query.group_by {
-                if do_comma {
-                    w.comma();
-                } else {
-                    do_comma = true;
-                }
-                self.build_operand(&mut w, use_complete_name, operand);
-                w.append(" ");
-            }
-        }
-
-        if !query.having.is_empty() {
-            w.left_river("HAVING ");
-            let mut do_comma = false;
-            for hav in &query.having {
-                if do_comma {
-                    w.commasp();
-                } else {
-                    do_comma = true;
-                }
-                self.build_filter(&mut w, use_complete_name, hav);
-            }
-        }
-
-        if !query.order_by.is_empty() {
-            w.left_river("ORDER BY ");
-            let mut do_comma = false;
-            for order in &query.order_by {
-                if do_comma {
-                    w.commasp();
-                } else {
-                    do_comma = true;
-                }
-                self.build_operand(&mut w, use_complete_name, &order.operand);
-                match &order.direction {
-                    &Some(ref direction) => {
-                        match direction {
-                            &Direction::ASC => w.append(" ASC"),
-                            &Direction::DESC => w.append(" DESC"),
-                        }
-                    }
-                    &None => w.append(""),
-                };
-                match &order.nulls_where {
-                    &Some(ref nulls_where) => {
-                        match nulls_where {
-                            &NullsWhere::FIRST => w.append(" NULLS FIRST"),
-                            &NullsWhere::LAST => w.append(" NULLS LAST"),
-                        }
-                    }
-                    &None => w.append(""),
-                };
-            }
-        }
-        match query.range.limit {
-            Some(limit) => {
-                w.left_river("LIMIT ");
-                w.append(&format!("{}", limit));
-            }
-            None => (),
-        }
-        match query.range.offset {
-            Some(offset) => {
-                w.left_river("OFFSET ");
-                w.append(&format!("{}", offset));
-            }
-            None => (),
-        }
-        w
-    }
-
-    /// TODO: when the number of values is greater than the number of columns
-    /// wrap it into another set and make sure the values are in multiples of the the n columns
-    /// http://www.postgresql.org/docs/9.0/static/dml-insert.html
-    fn build_insert(&self, query: &Insert, build_mode: &BuildMode) -> SqlFrag {
-        let use_complete_name = false;
-        let mut w = SqlFrag::new(self.sql_options(), build_mode);
-        w.left_river("INSERT");
-        w.append("INTO ");
-        if self.sql_options().contains(&SqlOption::UsesSchema) {
-            w.append(&query.into.complete_name());
-        } else {
-            w.append(&query.into.name);
-        }
-        w.append("( ");
-        self.build_column_names(&mut w, use_complete_name, &query.columns);
-        w.append(" ) ");
-        match query.data{
-            Data::Values(ref values) => {
-                w.left_river("VALUES");
-                w.append("(");
-                let mut do_comma = false;
-                for vo in values {
-                    if do_comma {
-                        w.commasp();
-                    } else {
-                        do_comma = true;
-                    }
-                    self.build_operand(&mut w, use_complete_name, vo);
-                }
-                w.append(") ");
-            },
-            Data::Query(ref data_query) => {
-               let sql_frag = self.build_select(data_query, build_mode);
-               w.append(&sql_frag.sql);
-            }
-        }
-        if !query.return_columns.is_empty() {
-            if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
-                w.left_river("RETURNING");
-                self.build_column_names(&mut w, use_complete_name, &query.return_columns);
-            }
-        }
-        w.ln();
-        w
-    }
-
-    fn build_column_names(&self, w: &mut SqlFrag, use_complete_name: bool, column_names: &Vec<ColumnName>){
-        let mut do_comma = false;
-        for c in column_names {
-            if do_comma{w.commasp();}else{do_comma = true;}
-            if use_complete_name{
-                w.append(&c.complete_name());
-            }else{
-                w.append(&c.column);
-            }
-        }
+    /// the hits and misses of the prepared statement cache of this connection
+    fn statement_cache_stats(&self) -> Option<CacheStats> {
+        None
     }
 
-
-    fn build_update(&self, query: &Update,  build_mode: &BuildMode) -> SqlFrag {
-        let use_complete_name = false;
-        let mut w = SqlFrag::new(self.sql_options(), build_mode);
-        w.left_river("UPDATE ");
-        w.append(&query.table.complete_name());
-        let mut do_comma = false;
-        if !query.columns.is_empty() {
-            w.left_river("SET ");
-        }
-        let mut column_index = 0;
-        assert_eq!(query.columns.len(), query.values.len());
-        for ec in &query.columns {
-            if do_comma {
-                w.commasp();
-            } else {
-                do_comma = true;
-            }
-            w.append(&ec.column);
-            w.append(" = ");
-            let value = &query.values[column_index];
-            self.build_operand(&mut w, use_complete_name, value);
-            column_index += 1;
-        }
-
-        if !query.filters.is_empty() {
-            w.left_river("WHERE ");
-            self.build_filters(&mut w, use_complete_name, &query.filters);
-        }
-        if !query.return_columns.is_empty() {
-            if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
-                w.left_river("RETURNING ");
-                self.build_column_names(&mut w, use_complete_name, &query.return_columns) 
-            }
-        }
-        w
+    /// discard the cached prepared statements of all the connections of the pool,
+    /// called after the DDL operations since the statements may refer to the old schema
+    fn invalidate_statement_cache(&self) {
     }
 
-    fn build_delete(&self, query: &Delete, build_mode: &BuildMode) -> SqlFrag {
-        let use_complete_name = false;
-        let mut w = SqlFrag::new(self.sql_options(), build_mode);
-        w.left_river("DELETE FROM ");
-        w.append(&query.from_table.complete_name());
-        if !query.filters.is_empty() {
-            w.left_river("WHERE ");
-            self.build_filters(&mut w, use_complete_name, &query.filters);
-        }
-        w
+    /// the maximum number of parameters in a single statement,
+    /// defaults to the SQLITE_MAX_VARIABLE_NUMBER of sqlite
+    fn max_parameters(&self) -> usize {
+        999
     }
-
-    fn sql_options(&self) -> Vec<SqlOption>;
 }
 
 
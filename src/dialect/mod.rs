@@ -0,0 +1,1378 @@
+use query::Query;
+use writer::SqlFrag;
+use query::{Connector, Equality, Operand, Field};
+use query::{Direction, Modifier, NullsWhere, JoinType, Join};
+use query::{Lock, LockMode, LockWait};
+use query::Keyset;
+use query::{Order, HasEquality};
+use query::{Filter, Condition};
+use query::Range;
+use query::DeclaredQuery;
+use query::source::{SourceField, QuerySource};
+use query::{Select,Insert,Update,Delete};
+use query::query::Data;
+use query::{OnConflict, ConflictAction};
+use query::query::EXCLUDED;
+use query::{ColumnName, TableName};
+use query::Function;
+use dao::Value;
+pub use database::{SqlOption, BuildMode, IdentifierQuoting};
+
+pub mod postgres;
+pub mod sqlite;
+pub mod mysql;
+pub mod sqlserver;
+
+pub use self::postgres::PostgresDialect;
+pub use self::sqlite::SqliteDialect;
+pub use self::mysql::MysqlDialect;
+pub use self::sqlserver::SqlServerDialect;
+
+/// The sql generation of a database platform, with the features and quirks of the database
+/// expressed in its SqlOptions.
+/// This doesn't need a connection, so queries can be built in tests and build scripts,
+/// each of the database platforms builds its sql with its dialect
+pub trait Dialect {
+    fn sql_options(&self) -> Vec<SqlOption>;
+
+    /// whether all the identifiers are quoted or only those that needs to be
+    fn identifier_quoting(&self) -> IdentifierQuoting {
+        IdentifierQuoting::WhenNeeded
+    }
+
+    /// a sql fragment with the options and identifier quoting of this database
+    fn sql_frag(&self, build_mode: &BuildMode) -> SqlFrag {
+        let mut w = SqlFrag::new(self.sql_options(), build_mode);
+        w.identifier_quoting = self.identifier_quoting();
+        w
+    }
+
+    /// build a query, return the sql string and the parameters.
+    /// use by select to build the select query
+    /// build all types of query
+    /// subqueries are written in the same sql fragment, so the parameters are numbered continuously
+    fn build_query(&self, query: &Query, build_mode: &BuildMode) -> SqlFrag {
+        match *query {
+            Query::Select(ref select) => self.build_select(select, build_mode),
+            Query::Insert(ref insert) => self.build_insert(insert, build_mode),
+            Query::Update(ref update) => self.build_update(update, build_mode),
+            Query::Delete(ref delete) => self.build_delete(delete, build_mode),
+        }
+    }
+
+    /// build operand, i.e: columns, query, function, values
+    fn build_operand(&self, w: &mut SqlFrag, use_complete_name: bool, operand: &Operand) {
+        match *operand {
+            Operand::ColumnName(ref column_name) => {
+                if use_complete_name {
+                    w.identifier(&column_name.column);
+                } else if column_name.table == Some(EXCLUDED.to_owned()) {
+                    // the pseudo table of the conflicting row is a keyword, it can not be quoted
+                    w.append(EXCLUDED);
+                    w.append(".");
+                    w.identifier(&column_name.column);
+                } else {
+                    w.qualified_identifier(&column_name.complete_name());
+                }
+            }
+            Operand::QuerySource(ref query_source) => {
+                self.build_query_source(w, query_source);
+            }
+            Operand::Value(ref value) => {
+                w.parameter(value.clone());
+            }
+            Operand::Vec(ref operands) => {
+                let mut do_comma = false;
+                if !operands.is_empty() {
+                    w.append("(");
+                    for op in operands {
+                        if do_comma {
+                            w.commasp();
+                        } else {
+                            do_comma = true;
+                        }
+                        self.build_operand(w, use_complete_name, op);
+                    }
+                    w.append(")");
+                }
+            }
+            Operand::Raw(ref sql, ref params) => {
+                w.raw(sql, params);
+            }
+            Operand::Null => {
+                w.append("NULL");
+            }
+            Operand::None => (), //dont do anything
+        }
+    }
+
+    fn build_condition(&self, w: &mut SqlFrag, use_complete_name: bool, cond: &Condition) {
+        let sql_options = self.sql_options();
+        match cond.equality {
+            // the subquery is the left operand, there is no right operand
+            Equality::EXISTS => {
+                w.append("EXISTS ");
+                self.build_operand(w, use_complete_name, &cond.left);
+            }
+            Equality::NOT_EXISTS => {
+                w.append("NOT EXISTS ");
+                self.build_operand(w, use_complete_name, &cond.left);
+            }
+            Equality::ILIKE |
+            Equality::NOT_ILIKE if !sql_options.contains(&SqlOption::SupportsILike) => {
+                w.append("LOWER(");
+                self.build_operand(w, use_complete_name, &cond.left);
+                match cond.equality {
+                    Equality::NOT_ILIKE => w.append(") NOT LIKE LOWER("),
+                    _ => w.append(") LIKE LOWER("),
+                };
+                self.build_operand(w, use_complete_name, &cond.right);
+                w.append(")");
+            }
+            Equality::IS_DISTINCT_FROM if sql_options.contains(&SqlOption::SupportsNullSafeEqual) => {
+                w.append("NOT (");
+                self.build_operand(w, use_complete_name, &cond.left);
+                w.append(" <=> ");
+                self.build_operand(w, use_complete_name, &cond.right);
+                w.append(")");
+            }
+            // the values are not distinct when their intersection is not empty, NULLs included (sql server)
+            Equality::IS_DISTINCT_FROM |
+            Equality::IS_NOT_DISTINCT_FROM if !sql_options.contains(&SqlOption::SupportsIsDistinctFrom) &&
+                                              !sql_options.contains(&SqlOption::SupportsNullSafeEqual) &&
+                                              !sql_options.contains(&SqlOption::SupportsIsOperator) => {
+                match cond.equality {
+                    Equality::IS_DISTINCT_FROM => w.append("NOT EXISTS (SELECT "),
+                    _ => w.append("EXISTS (SELECT "),
+                };
+                self.build_operand(w, use_complete_name, &cond.left);
+                w.append(" INTERSECT SELECT ");
+                self.build_operand(w, use_complete_name, &cond.right);
+                w.append(")");
+            }
+            Equality::ANY(ref equality) => {
+                self.build_quantified_condition(w, use_complete_name, cond, equality, true);
+            }
+            Equality::ALL(ref equality) => {
+                self.build_quantified_condition(w, use_complete_name, cond, equality, false);
+            }
+            Equality::MATCH => {
+                self.build_text_match(w, use_complete_name, &cond.left, &cond.right);
+            }
+            _ => {
+                self.build_operand(w, use_complete_name, &cond.left);
+                w.append(" ");
+                self.build_infix_condition(w, use_complete_name, cond, &sql_options);
+            }
+        }
+    }
+
+    /// build the equality and the right operand of the condition
+    fn build_infix_condition(&self,
+                             w: &mut SqlFrag,
+                             use_complete_name: bool,
+                             cond: &Condition,
+                             sql_options: &[SqlOption]) {
+        match cond.equality {
+            Equality::EQ => {
+                w.append("= ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::NEQ => {
+                w.append("!= ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::LT => {
+                w.append("< ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::LTE => {
+                w.append("<= ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::GT => {
+                w.append("> ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::GTE => {
+                w.append(">= ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::IN => {
+                w.append("IN ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::NOT_IN => {
+                w.append("NOT IN ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::LIKE => {
+                w.append("LIKE ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::ILIKE => {
+                w.append("ILIKE ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::NOT_LIKE => {
+                w.append("NOT LIKE ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::NOT_ILIKE => {
+                w.append("NOT ILIKE ");
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::IS_NOT_NULL => {
+                w.append("IS NOT NULL");
+            }
+
+            Equality::IS_NULL => {
+                w.append("IS NULL");
+            }
+            Equality::BETWEEN | Equality::NOT_BETWEEN => {
+                match cond.equality {
+                    Equality::NOT_BETWEEN => w.append("NOT BETWEEN "),
+                    _ => w.append("BETWEEN "),
+                };
+                match cond.right {
+                    Operand::Vec(ref bounds) if bounds.len() == 2 => {
+                        self.build_operand(w, use_complete_name, &bounds[0]);
+                        w.append(" AND ");
+                        self.build_operand(w, use_complete_name, &bounds[1]);
+                    }
+                    _ => w.set_error("BETWEEN expects exactly 2 operands, the low and the high bound"),
+                }
+            }
+            Equality::IS_DISTINCT_FROM => {
+                if sql_options.contains(&SqlOption::SupportsIsDistinctFrom) {
+                    w.append("IS DISTINCT FROM ");
+                } else {
+                    w.append("IS NOT ");
+                }
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::IS_NOT_DISTINCT_FROM => {
+                if sql_options.contains(&SqlOption::SupportsIsDistinctFrom) {
+                    w.append("IS NOT DISTINCT FROM ");
+                } else if sql_options.contains(&SqlOption::SupportsNullSafeEqual) {
+                    w.append("<=> ");
+                } else {
+                    w.append("IS ");
+                }
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::SIMILAR_TO | Equality::NOT_SIMILAR_TO => {
+                if !sql_options.contains(&SqlOption::SupportsSimilarTo) {
+                    return w.set_error("SIMILAR TO is not supported in this database, use REGEX instead");
+                }
+                match cond.equality {
+                    Equality::NOT_SIMILAR_TO => w.append("NOT SIMILAR TO "),
+                    _ => w.append("SIMILAR TO "),
+                };
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::REGEX => {
+                if sql_options.contains(&SqlOption::SupportsPosixRegex) {
+                    w.append("~ ");
+                } else if sql_options.contains(&SqlOption::SupportsRegexp) {
+                    w.append("REGEXP ");
+                } else {
+                    return w.set_error("REGEX is not supported in this database");
+                }
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::NOT_REGEX => {
+                if sql_options.contains(&SqlOption::SupportsPosixRegex) {
+                    w.append("!~ ");
+                } else if sql_options.contains(&SqlOption::SupportsRegexp) {
+                    w.append("NOT REGEXP ");
+                } else {
+                    return w.set_error("REGEX is not supported in this database");
+                }
+                self.build_operand(w, use_complete_name, &cond.right);
+            }
+            Equality::EXISTS |
+            Equality::NOT_EXISTS |
+            Equality::ANY(_) |
+            Equality::ALL(_) |
+            Equality::MATCH => unreachable!(),
+        }
+    }
+
+    /// build the full text search of the text in the column,
+    /// the searched is the text search configuration and the text
+    fn build_text_match(&self, w: &mut SqlFrag, use_complete_name: bool, column: &Operand, searched: &Operand) {
+        let sql_options = self.sql_options();
+        let (config, text) = match *searched {
+            Operand::Vec(ref parts) if parts.len() == 2 => {
+                match parts[0] {
+                    Operand::Value(Value::String(ref config)) => (config, &parts[1]),
+                    _ => return w.set_error("MATCH expects the text search configuration to be a string"),
+                }
+            }
+            _ => return w.set_error("MATCH expects the text search configuration and the text searched"),
+        };
+        if sql_options.contains(&SqlOption::SupportsTsVector) {
+            let config = ts_config(config);
+            w.append(&format!("to_tsvector({}, ", config));
+            self.build_operand(w, use_complete_name, column);
+            w.append(&format!(") @@ plainto_tsquery({}, ", config));
+            self.build_operand(w, use_complete_name, text);
+            w.append(")");
+        } else if sql_options.contains(&SqlOption::SupportsFts5) {
+            self.build_operand(w, use_complete_name, column);
+            w.append(" MATCH ");
+            match *text {
+                Operand::Value(Value::String(ref text)) => {
+                    let words = Operand::Value(Value::String(fts5_words(text)));
+                    self.build_operand(w, use_complete_name, &words);
+                }
+                _ => self.build_operand(w, use_complete_name, text),
+            }
+        } else if sql_options.contains(&SqlOption::SupportsMatchAgainst) {
+            w.append("MATCH (");
+            self.build_operand(w, use_complete_name, column);
+            w.append(") AGAINST (");
+            self.build_operand(w, use_complete_name, text);
+            w.append(" IN NATURAL LANGUAGE MODE)");
+        } else {
+            w.set_error("full text search is not supported in this database");
+        }
+    }
+
+    /// build `left op ANY (right)` or `left op ALL (right)`
+    /// a list of values is expanded into OR'ed/AND'ed comparisons when the database has no arrays,
+    /// a subquery with `= ANY` and `!= ALL` is rewritten into IN and NOT IN when ANY/ALL is not supported
+    fn build_quantified_condition(&self,
+                                  w: &mut SqlFrag,
+                                  use_complete_name: bool,
+                                  cond: &Condition,
+                                  equality: &Equality,
+                                  is_any: bool) {
+        let sql_options = self.sql_options();
+        let operator = match equality.operator() {
+            Some(operator) => operator,
+            None => {
+                return w.set_error(&format!("ANY/ALL can only be used with comparison operators, found {:?}",
+                                            equality))
+            }
+        };
+        let quantifier = if is_any { "ANY" } else { "ALL" };
+        match cond.right {
+            Operand::Vec(ref operands) => {
+                if sql_options.contains(&SqlOption::SupportsArray) {
+                    self.build_operand(w, use_complete_name, &cond.left);
+                    w.append(&format!(" {} {} (ARRAY[", operator, quantifier));
+                    let mut do_comma = false;
+                    for op in operands {
+                        if do_comma {
+                            w.commasp();
+                        } else {
+                            do_comma = true;
+                        }
+                        self.build_operand(w, use_complete_name, op);
+                    }
+                    w.append("])");
+                } else if operands.is_empty() {
+                    // ANY of nothing is false, ALL of nothing is true
+                    if is_any {
+                        w.append("1 = 0");
+                    } else {
+                        w.append("1 = 1");
+                    }
+                } else {
+                    w.append("( ");
+                    let mut do_connector = false;
+                    for op in operands {
+                        if do_connector {
+                            if is_any {
+                                w.append(" OR ");
+                            } else {
+                                w.append(" AND ");
+                            }
+                        } else {
+                            do_connector = true;
+                        }
+                        let expanded = Condition {
+                            left: cond.left.clone(),
+                            equality: equality.clone(),
+                            right: op.clone(),
+                        };
+                        self.build_condition(w, use_complete_name, &expanded);
+                    }
+                    w.append(" )");
+                }
+            }
+            _ => {
+                // subqueries are already enclosed in parenthesis
+                let is_subquery = match cond.right {
+                    Operand::QuerySource(QuerySource::Query(_)) => true,
+                    _ => false,
+                };
+                self.build_operand(w, use_complete_name, &cond.left);
+                if sql_options.contains(&SqlOption::SupportsAnyAll) {
+                    w.append(&format!(" {} {} ", operator, quantifier));
+                } else {
+                    match (equality, is_any) {
+                        (&Equality::EQ, true) => w.append(" IN "),
+                        (&Equality::NEQ, false) => w.append(" NOT IN "),
+                        _ => {
+                            return w.set_error(&format!("{} {} is not supported in this database, \
+                                                         only = ANY and != ALL can be emulated",
+                                                        operator,
+                                                        quantifier))
+                        }
+                    };
+                }
+                if !is_subquery {
+                    w.append("(");
+                }
+                self.build_operand(w, use_complete_name, &cond.right);
+                if !is_subquery {
+                    w.append(")");
+                }
+            }
+        }
+    }
+
+    fn build_field(&self, w: &mut SqlFrag, use_complete_name: bool, field: &Field) {
+        self.build_operand(w, use_complete_name, &field.operand);
+        match field.name {
+            Some(ref name) => {
+                w.append(" AS ");
+                w.identifier(name);
+            }
+            None => (),
+        }
+    }
+
+    fn build_query_source(&self,
+                          w: &mut SqlFrag,
+                          query_source: &QuerySource) {
+        match *query_source {
+            QuerySource::TableName(ref table_name) => {
+                if self.sql_options().contains(&SqlOption::UsesSchema) {
+                    w.qualified_identifier(&table_name.complete_name());
+                } else {
+                    w.identifier(&table_name.name);
+                }
+            }
+            QuerySource::Function(ref function) => {
+                self.build_function(w, function);
+            }
+            QuerySource::Query(ref q) => {
+                w.append("(");
+                self.write_select(w, q, true);
+                w.append(")");
+            }
+            QuerySource::Raw(ref sql, ref params) => {
+                w.raw(sql, params);
+            }
+        }
+    }
+
+    /// build the function call,
+    /// platforms override this to map the function into its equivalent in the database
+    fn build_function(&self, w: &mut SqlFrag, function: &Function) {
+        match &*function.function {
+            "CAST" => {
+                let (operand, data_type) = match function.cast_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                self.build_cast(w, operand, data_type);
+            }
+            _ => self.build_function_call(w, &function.function, &function.params),
+        }
+    }
+
+    /// build `CAST(operand AS data_type)`, the data type is not a parameter
+    fn build_cast(&self, w: &mut SqlFrag, operand: &Operand, data_type: &str) {
+        w.append("CAST(");
+        self.build_operand(w, false, operand);
+        w.append(&format!(" AS {})", data_type));
+    }
+
+    /// build `name(param1, param2..)`
+    fn build_function_call(&self, w: &mut SqlFrag, name: &str, params: &[Operand]) {
+        w.append(name);
+        w.append("(");
+        let mut do_comma = false;
+        for param in params {
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            self.build_operand(w, false, param);
+        }
+        w.append(")");
+    }
+
+    fn build_source_field(&self,
+                          w: &mut SqlFrag,
+                          source_field: &SourceField) {
+        self.build_query_source(w, &source_field.source);
+        match source_field.rename {
+            Some(ref rename) => {
+                w.append(" AS ");
+                w.identifier(rename);
+            }
+            None => (),
+        }
+    }
+
+    fn build_filter(&self, w: &mut SqlFrag, use_complete_name: bool, filter: &Filter) {
+        if !filter.sub_filters.is_empty() {
+            w.append("( ");
+        }
+        self.build_condition(w, use_complete_name, &filter.condition);
+        w.sp();
+        for filt in &filter.sub_filters {
+            match filt.connector {
+                Connector::And => {
+                    w.append("AND ");
+                }
+                Connector::Or => {
+                    w.append("OR ");
+                }
+            }
+            self.build_filter(w, use_complete_name, filt);// build sub filters as well
+        }
+        if !filter.sub_filters.is_empty() {
+            w.append(" )");
+        }
+    }
+
+    /// build the filter clause or the where clause of the query
+    /// TODO: add the sub filters
+    fn build_filters(&self, w: &mut SqlFrag, use_complete_name: bool, filters: &[Filter]) {
+        let mut do_and = false;
+        for filter in filters {
+            if do_and {
+                w.left_river(" AND ");
+            } else {
+                do_and = true;
+            }
+            self.build_filter(w, use_complete_name, filter);
+        }
+    }
+
+    /// build the enumerated, distinct, *, columns
+    fn build_enumerated_fields(&self,
+                               w: &mut SqlFrag,
+                               use_complete_name: bool, 
+                               enumerated_fields: &[Field]) {
+        let mut do_comma = false;
+        let mut cnt = 0;
+        for field in enumerated_fields {
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            cnt += 1;
+            if cnt % 4 == 0 {
+                // break at every 4 columns to encourage sql tuning/revising
+                w.left_river("");
+            }
+            self.build_field(w, use_complete_name, field);
+        }
+    }
+
+    /// build the select statment from the query object
+    fn build_select(&self, query: &Select, build_mode: &BuildMode) -> SqlFrag {
+        let mut w = self.sql_frag(build_mode);
+        self.write_select(&mut w, query, false);
+        w
+    }
+
+    /// the JOIN .. ON .. clauses
+    fn build_joins(&self, w: &mut SqlFrag, use_complete_name: bool, joins: &[Join]) {
+        for join in joins {
+            match join.modifier {
+                Some(ref modifier) => {
+                    match *modifier {
+                        Modifier::LEFT => w.right_river("LEFT "),
+                        Modifier::RIGHT => w.right_river("RIGHT "),
+                        Modifier::FULL => w.right_river("FULL "),
+                    };
+                }
+                None => (),
+            }
+            match join.join_type {
+                Some(ref join_type) => {
+                    let join_type = match *join_type {
+                        JoinType::CROSS => "CROSS ",
+                        JoinType::INNER => "INNER ",
+                        JoinType::OUTER => "OUTER ",
+                        JoinType::NATURAL => "NATURAL ",
+                    };
+                    // in the same line as the modifier, ie: LEFT OUTER JOIN
+                    if join.modifier.is_some() {
+                        w.append(join_type);
+                    } else {
+                        w.right_river(join_type);
+                    }
+                }
+                None => (),
+            }
+            w.append("JOIN ");
+            if join.lateral {
+                if !self.sql_options().contains(&SqlOption::SupportsLateral) {
+                    return w.set_error("LATERAL join is not supported in this database");
+                }
+                w.append("LATERAL ");
+            }
+            self.build_source_field(w, &join.source);
+            if !join.using.is_empty() {
+                w.right_river("USING ");
+                w.append("(");
+                w.identifiers(&join.using);
+                w.append(")");
+            } else if let Some(ref on) = join.on {
+                w.right_river("ON ");
+                self.build_filter(w, use_complete_name, on);
+            }
+        }
+    }
+
+    /// the joined tables as a comma separated list, used in UPDATE .. FROM and DELETE .. USING
+    /// where the join conditions are moved into the WHERE clause
+    fn build_join_tables(&self, w: &mut SqlFrag, joins: &[Join]) {
+        let mut do_comma = false;
+        for join in joins {
+            if join.modifier.is_some() {
+                return w.set_error("only inner joins are supported in update and delete");
+            }
+            if !join.using.is_empty() {
+                return w.set_error("USING is not supported in update and delete, use ON instead");
+            }
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            self.build_source_field(w, &join.source);
+        }
+    }
+
+    /// the join conditions followed by the filters
+    fn join_filters(&self, joins: &[Join], filters: &[Filter]) -> Vec<Filter> {
+        let mut join_filters: Vec<Filter> = joins.iter().filter_map(|j| j.on.clone()).collect();
+        join_filters.extend_from_slice(filters);
+        join_filters
+    }
+
+    /// the emulation of the joined update and delete in databases that only supports 1 table,
+    /// `rowid IN (SELECT table.rowid FROM table, joined WHERE join_conditions AND filters)` in sqlite,
+    /// `EXISTS (SELECT 1 FROM joined WHERE join_conditions AND filters)` in the others.
+    /// The filters are written only here, so their parameters are not repeated
+    fn build_joined_filter(&self, w: &mut SqlFrag, table: &TableName, joins: &[Join], filters: &[Filter]) {
+        let has_rowid = self.sql_options().contains(&SqlOption::HasRowid);
+        if has_rowid {
+            w.append("rowid IN (");
+            w.left_river("SELECT");
+            w.qualified_identifier(&table.complete_name());
+            w.append(".rowid");
+            w.left_river("FROM");
+            w.qualified_identifier(&table.complete_name());
+            w.commasp();
+        } else {
+            w.append("EXISTS (");
+            w.left_river("SELECT");
+            w.append("1");
+            w.left_river("FROM");
+        }
+        self.build_join_tables(w, joins);
+        w.left_river("WHERE ");
+        self.build_filters(w, false, &self.join_filters(joins, filters));
+        w.append(")");
+    }
+
+    /// the value from the joined tables in an update of databases that only supports 1 table,
+    /// `(SELECT value FROM joined WHERE join_conditions)` correlated with the updated row.
+    /// The joined row is matched with the join conditions only, when there are several
+    /// matching rows any of them is used, the same as UPDATE .. FROM
+    fn build_joined_value(&self, w: &mut SqlFrag, value: &Operand, joins: &[Join]) {
+        w.append("(");
+        w.left_river("SELECT");
+        self.build_operand(w, false, value);
+        w.left_river("FROM");
+        self.build_join_tables(w, joins);
+        w.left_river("WHERE ");
+        self.build_filters(w, false, &self.join_filters(joins, &[]));
+        w.append(")");
+    }
+
+    /// DISTINCT ON emulated with the ROW_NUMBER() window function,
+    /// the first row of each partition is selected from the numbered rows
+    /// ie: `SELECT .. FROM (SELECT .., ROW_NUMBER() OVER (PARTITION BY .. ORDER BY ..) AS distinct_on_row ..)
+    /// AS distinct_on WHERE distinct_on_row = 1`.
+    /// The ORDER BY can only have columns, and those have to be selected as well,
+    /// a selected `*` or no selected columns will include the distinct_on_row column.
+    /// The window functions needs sqlite >= 3.25 or mysql >= 8.0
+    fn emulate_distinct_on(&self, query: &Select) -> Result<Select, String> {
+        if !self.sql_options().contains(&SqlOption::SupportsWindowFunctions) {
+            return Err("DISTINCT ON is emulated with ROW_NUMBER(), which is not supported in this database version".to_owned());
+        }
+        let mut window = self.sql_frag(&BuildMode::Standard);
+        window.append("ROW_NUMBER() OVER (PARTITION BY ");
+        window.identifiers(&query.distinct_on_columns);
+        let mut outer_order = vec![];
+        for (i, order) in query.order_by.iter().enumerate() {
+            let column = match order.operand {
+                Operand::ColumnName(ref column) => column,
+                _ => return Err("Only columns are supported in the ORDER BY of an emulated DISTINCT ON".to_owned()),
+            };
+            window.append(if i == 0 { " ORDER BY " } else { ", " });
+            self.build_order(&mut window, false, order);
+            outer_order.push(Order {
+                operand: Operand::ColumnName(ColumnName::from(&*column.column)),
+                direction: order.direction.to_owned(),
+                nulls_where: order.nulls_where.to_owned(),
+            });
+        }
+        window.append(")");
+
+        let mut inner = query.to_owned();
+        inner.distinct_on_columns = vec![];
+        inner.order_by = vec![];
+        inner.range = Range::new();
+        inner.lock = None;
+        let mut outer = Select::new();
+        if query.enumerated_fields.is_empty() {
+            inner.column("*");
+            outer.column("*");
+        }
+        inner.enumerated_fields.push(Field {
+            operand: Operand::Raw(window.sql, vec![]),
+            name: Some("distinct_on_row".to_owned()),
+        });
+
+        for field in &query.enumerated_fields {
+            let name = match (&field.name, &field.operand) {
+                (&Some(ref name), _) => name.to_owned(),
+                (&None, &Operand::ColumnName(ref column)) => column.column.to_owned(),
+                _ => return Err("Name the selected expressions with AS when emulating DISTINCT ON".to_owned()),
+            };
+            outer.column(&name);
+        }
+        outer.from_query(inner, "distinct_on");
+        outer.add_filter(&"distinct_on_row".EQ(&Operand::Raw("1".to_owned(), vec![])));
+        outer.order_by = outer_order;
+        outer.range = query.range.to_owned();
+        outer.lock = query.lock.to_owned();
+        outer.enable_query_stat = query.enable_query_stat;
+        Ok(outer)
+    }
+
+    /// `x DESC NULLS FIRST`, the NULLS FIRST/LAST is emulated with a `CASE WHEN x IS NULL ..` sort key
+    /// on the databases without it, the `x IS NULL` boolean is not an expression in sql server
+    fn build_order(&self, w: &mut SqlFrag, use_complete_name: bool, order: &Order) {
+        let supports_nulls = self.sql_options().contains(&SqlOption::SupportsNullsOrdering);
+        if !supports_nulls {
+            if let Some(ref nulls_where) = order.nulls_where {
+                w.append("CASE WHEN ");
+                self.build_operand(w, use_complete_name, &order.operand);
+                match *nulls_where {
+                    NullsWhere::FIRST => w.append(" IS NULL THEN 0 ELSE 1 END, "),
+                    NullsWhere::LAST => w.append(" IS NULL THEN 1 ELSE 0 END, "),
+                };
+            }
+        }
+        self.build_operand(w, use_complete_name, &order.operand);
+        match order.direction {
+            Some(Direction::ASC) => {
+                w.append(" ASC");
+            }
+            Some(Direction::DESC) => {
+                w.append(" DESC");
+            }
+            None => (),
+        }
+        if supports_nulls {
+            match order.nulls_where {
+                Some(NullsWhere::FIRST) => {
+                    w.append(" NULLS FIRST");
+                }
+                Some(NullsWhere::LAST) => {
+                    w.append(" NULLS LAST");
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// `WITH [RECURSIVE] name (fields) AS (query [UNION ALL recursive_query]), ..`
+    fn build_declared_queries(&self, w: &mut SqlFrag, declared_queries: &[DeclaredQuery]) {
+        if !self.sql_options().contains(&SqlOption::SupportsCTE) {
+            return w.set_error("WITH queries are not supported in this database");
+        }
+        w.left_river("WITH ");
+        if declared_queries.iter().any(|d| d.is_recursive) &&
+           !self.sql_options().contains(&SqlOption::OmitsRecursiveKeyword) {
+            w.append("RECURSIVE ");
+        }
+        let mut do_comma = false;
+        for declared in declared_queries {
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            w.identifier(&declared.name);
+            if !declared.fields.is_empty() {
+                w.append(" (");
+                w.identifiers(&declared.fields);
+                w.append(")");
+            }
+            w.append(" AS (");
+            self.write_select(w, &declared.query, true);
+            if let Some(ref recursive_query) = declared.recursive_query {
+                w.left_river("UNION ALL");
+                self.write_select(w, recursive_query, true);
+            }
+            w.append(")");
+        }
+    }
+
+    /// write the select statement into the sql fragment,
+    /// subqueries are written into the same fragment so the parameters are numbered continuously.
+    /// The columns in subqueries are always written with their table name so that correlated subqueries
+    /// can refer to the columns of the outer query
+    fn write_select(&self, w: &mut SqlFrag, query: &Select, is_subquery: bool) {
+        if !query.distinct_on_columns.is_empty() &&
+           !self.sql_options().contains(&SqlOption::SupportsDistinctOn) {
+            return match self.emulate_distinct_on(query) {
+                Ok(emulated) => self.write_select(w, &emulated, is_subquery),
+                Err(e) => w.set_error(&e),
+            };
+        }
+        let use_complete_name = query.joins.is_empty() && !is_subquery;
+        if !query.declared_query.is_empty() {
+            self.build_declared_queries(w, &query.declared_query);
+        }
+        w.left_river("SELECT");
+        if !query.distinct_on_columns.is_empty() {
+            w.append("DISTINCT ON (");
+            w.identifiers(&query.distinct_on_columns);
+            w.append(") ");
+        } else if query.distinct {
+            w.append("DISTINCT ");
+        }
+        let offset_fetch = self.sql_options().contains(&SqlOption::UsesOffsetFetch);
+        if offset_fetch && query.range.offset.is_none() {
+            if let Some(limit) = query.range.limit {
+                w.append(&format!("TOP ({}) ", limit));
+            }
+        }
+        self.build_enumerated_fields(w, use_complete_name, &query.enumerated_fields); //TODO: add support for column_sql, fields, functions
+        w.left_river("FROM");
+
+        if query.from.is_empty() {
+            return w.set_error("There should be table, query, function to select from");
+        }
+        let mut do_comma = false;
+        for field in &query.from {
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            self.build_source_field(w, field);
+        }
+        self.build_joins(w, use_complete_name, &query.joins);
+
+        let seek_keyset = match query.keyset {
+            Some(ref keyset) if !keyset.after.is_empty() => Some(keyset),
+            _ => None,
+        };
+        if !query.filters.is_empty() || seek_keyset.is_some() {
+            w.left_river("WHERE ");
+            self.build_filters(w, use_complete_name, &query.filters);
+            if let Some(keyset) = seek_keyset {
+                if !query.filters.is_empty() {
+                    w.left_river(" AND ");
+                }
+                self.build_keyset(w, use_complete_name, keyset);
+            }
+        }
+
+        if !query.group_by.is_empty() {
+            w.left_river("GROUP BY ");
+            let mut do_comma = false;
+            for operand in &query.group_by {
+                if do_comma {
+                    w.comma();
+                } else {
+                    do_comma = true;
+                }
+                self.build_operand(w, use_complete_name, operand);
+                w.append(" ");
+            }
+        }
+
+        if !query.having.is_empty() {
+            w.left_river("HAVING ");
+            let mut do_comma = false;
+            for hav in &query.having {
+                if do_comma {
+                    w.commasp();
+                } else {
+                    do_comma = true;
+                }
+                self.build_filter(w, use_complete_name, hav);
+            }
+        }
+
+        if !query.order_by.is_empty() {
+            w.left_river("ORDER BY ");
+            let mut do_comma = false;
+            for order in &query.order_by {
+                if do_comma {
+                    w.commasp();
+                } else {
+                    do_comma = true;
+                }
+                self.build_order(w, use_complete_name, order);
+            }
+        }
+        if offset_fetch {
+            if let Some(offset) = query.range.offset {
+                self.build_offset_fetch(w, query.order_by.is_empty(), offset, query.range.limit);
+            }
+        } else {
+            match query.range.limit {
+                Some(limit) => {
+                    w.left_river("LIMIT ");
+                    w.append(&format!("{}", limit));
+                }
+                None => (),
+            }
+            match query.range.offset {
+                Some(offset) => {
+                    w.left_river("OFFSET ");
+                    w.append(&format!("{}", offset));
+                }
+                None => (),
+            }
+        }
+        if let Some(ref lock) = query.lock {
+            self.build_lock(w, lock);
+        }
+    }
+
+    /// `OFFSET n ROWS FETCH NEXT m ROWS ONLY` in sqlserver, which requires an ORDER BY,
+    /// a limit without an offset is written as `SELECT TOP (m)` instead
+    fn build_offset_fetch(&self, w: &mut SqlFrag, unordered: bool, offset: usize, limit: Option<usize>) {
+        if unordered {
+            w.left_river("ORDER BY ");
+            w.append("(SELECT NULL)");
+        }
+        w.left_river("OFFSET ");
+        w.append(&format!("{} ROWS", offset));
+        if let Some(limit) = limit {
+            w.left_river("FETCH ");
+            w.append(&format!("NEXT {} ROWS ONLY", limit));
+        }
+    }
+
+    /// the records after the last row of the previous page,
+    /// `(a, b) > (?, ?)` when row values are supported,
+    /// otherwise expanded into `( a > ? OR ( a = ? AND b > ? ) )`
+    fn build_keyset(&self, w: &mut SqlFrag, use_complete_name: bool, keyset: &Keyset) {
+        if keyset.columns.len() != keyset.after.len() {
+            return w.set_error("The values should match the keyset columns");
+        }
+        let operator = match keyset.direction {
+            Direction::ASC => " > ",
+            Direction::DESC => " < ",
+        };
+        let columns: Vec<Operand> = keyset.columns
+            .iter()
+            .map(|c| Operand::ColumnName(c.to_owned()))
+            .collect();
+        if self.sql_options().contains(&SqlOption::SupportsRowValues) {
+            let values = keyset.after.iter().map(|v| Operand::Value(v.to_owned())).collect();
+            self.build_operand(w, use_complete_name, &Operand::Vec(columns));
+            w.append(operator);
+            self.build_operand(w, use_complete_name, &Operand::Vec(values));
+        } else {
+            w.append("( ");
+            for (i, column) in columns.iter().enumerate() {
+                if i > 0 {
+                    w.append("OR ( ");
+                    for j in 0..i {
+                        self.build_operand(w, use_complete_name, &columns[j]);
+                        w.append(" = ");
+                        w.parameter(keyset.after[j].to_owned());
+                        w.append(" AND ");
+                    }
+                }
+                self.build_operand(w, use_complete_name, column);
+                w.append(operator);
+                w.parameter(keyset.after[i].to_owned());
+                if i > 0 {
+                    w.append(" ) ");
+                } else {
+                    w.append(" ");
+                }
+            }
+            w.append(")");
+        }
+    }
+
+    /// FOR UPDATE | FOR SHARE [OF tables] [NOWAIT | SKIP LOCKED]
+    fn build_lock(&self, w: &mut SqlFrag, lock: &Lock) {
+        if !self.sql_options().contains(&SqlOption::SupportsRowLocking) {
+            return w.set_error("Row locking FOR UPDATE/FOR SHARE is not supported in this database, use a transaction instead");
+        }
+        w.left_river("FOR ");
+        match lock.mode {
+            LockMode::UPDATE => w.append("UPDATE"),
+            LockMode::SHARE => w.append("SHARE"),
+        };
+        if !lock.of_tables.is_empty() {
+            w.append(" OF ");
+            let mut do_comma = false;
+            for table in &lock.of_tables {
+                if do_comma {
+                    w.commasp();
+                } else {
+                    do_comma = true;
+                }
+                w.qualified_identifier(&table.complete_name());
+            }
+        }
+        match lock.wait {
+            Some(LockWait::NOWAIT) => {
+                w.append(" NOWAIT");
+            }
+            Some(LockWait::SKIP_LOCKED) => {
+                w.append(" SKIP LOCKED");
+            }
+            None => (),
+        }
+    }
+
+    /// TODO: when the number of values is greater than the number of columns
+    /// wrap it into another set and make sure the values are in multiples of the the n columns
+    /// http://www.postgresql.org/docs/9.0/static/dml-insert.html
+    fn build_insert(&self, query: &Insert, build_mode: &BuildMode) -> SqlFrag {
+        let use_complete_name = false;
+        let mut w = self.sql_frag(build_mode);
+        w.left_river("INSERT");
+        w.append("INTO ");
+        if self.sql_options().contains(&SqlOption::UsesSchema) {
+            w.qualified_identifier(&query.into.complete_name());
+        } else {
+            w.identifier(&query.into.name);
+        }
+        w.append("( ");
+        self.build_column_names(&mut w, use_complete_name, &query.columns);
+        w.append(" ) ");
+        let output = self.sql_options().contains(&SqlOption::SupportsOutputClause);
+        if output && !query.return_columns.is_empty() {
+            self.build_output(&mut w, "INSERTED", &query.return_columns);
+        }
+        match query.data{
+            Data::Values(ref values) => {
+                w.left_river("VALUES");
+                w.append("(");
+                let mut do_comma = false;
+                for vo in values {
+                    if do_comma {
+                        w.commasp();
+                    } else {
+                        do_comma = true;
+                    }
+                    self.build_operand(&mut w, use_complete_name, vo);
+                }
+                w.append(") ");
+            },
+            Data::Rows(ref rows) => {
+                w.left_river("VALUES");
+                let mut do_row_comma = false;
+                for row in rows {
+                    if do_row_comma {
+                        w.commasp();
+                    } else {
+                        do_row_comma = true;
+                    }
+                    w.append("(");
+                    let mut do_comma = false;
+                    for vo in row {
+                        if do_comma {
+                            w.commasp();
+                        } else {
+                            do_comma = true;
+                        }
+                        self.build_operand(&mut w, use_complete_name, vo);
+                    }
+                    w.append(")");
+                }
+                w.append(" ");
+            },
+            Data::Query(ref data_query) => {
+               self.write_select(&mut w, data_query, false);
+            }
+        }
+        if let Some(ref on_conflict) = query.on_conflict {
+            self.build_on_conflict(&mut w, on_conflict);
+        }
+        if !query.return_columns.is_empty() {
+            if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
+                w.left_river("RETURNING");
+                self.build_column_names(&mut w, use_complete_name, &query.return_columns);
+            }
+        }
+        w.ln();
+        w
+    }
+
+    /// ON CONFLICT (..) DO NOTHING | DO UPDATE SET c = EXCLUDED.c in postgresql and sqlite,
+    /// ON DUPLICATE KEY UPDATE c = VALUES(c) in mysql
+    fn build_on_conflict(&self, w: &mut SqlFrag, on_conflict: &OnConflict) {
+        let sql_options = self.sql_options();
+        if sql_options.contains(&SqlOption::SupportsOnConflict) {
+            w.left_river("ON");
+            w.append("CONFLICT ");
+            if !on_conflict.columns.is_empty() {
+                w.append("( ");
+                self.build_column_names(w, false, &on_conflict.columns);
+                w.append(" ) ");
+            }
+            match on_conflict.action {
+                ConflictAction::DoNothing => {
+                    w.append("DO NOTHING ");
+                }
+                ConflictAction::DoUpdate { ref columns, ref values } => {
+                    if on_conflict.columns.is_empty() {
+                        return w.set_error("ON CONFLICT DO UPDATE requires the conflicting columns");
+                    }
+                    w.append("DO UPDATE ");
+                    w.left_river("SET");
+                    let mut do_comma = false;
+                    for (column, value) in columns.iter().zip(values.iter()) {
+                        if do_comma {
+                            w.commasp();
+                        } else {
+                            do_comma = true;
+                        }
+                        w.identifier(&column.column);
+                        w.append(" = ");
+                        self.build_operand(w, false, value);
+                    }
+                }
+            }
+        } else if sql_options.contains(&SqlOption::SupportsOnDuplicateKey) {
+            w.left_river("ON");
+            w.append("DUPLICATE KEY UPDATE ");
+            match on_conflict.action {
+                // setting a column to itself leaves the existing row unchanged,
+                // but unlike DO NOTHING it is still an update, the row is locked
+                // and is counted as an affected row with CLIENT_FOUND_ROWS
+                ConflictAction::DoNothing => {
+                    let column = match on_conflict.columns.first() {
+                        Some(column) => column,
+                        None => return w.set_error("ON DUPLICATE KEY UPDATE requires at least 1 column"),
+                    };
+                    w.identifier(&column.column);
+                    w.append(" = ");
+                    w.identifier(&column.column);
+                    w.append(" ");
+                }
+                ConflictAction::DoUpdate { ref columns, ref values } => {
+                    let mut do_comma = false;
+                    for (column, value) in columns.iter().zip(values.iter()) {
+                        if do_comma {
+                            w.commasp();
+                        } else {
+                            do_comma = true;
+                        }
+                        w.identifier(&column.column);
+                        w.append(" = ");
+                        match *value {
+                            Operand::ColumnName(ref excluded)
+                                if excluded.table == Some(EXCLUDED.to_owned()) => {
+                                w.append("VALUES(");
+                                w.identifier(&excluded.column);
+                                w.append(")");
+                            }
+                            _ => self.build_operand(w, true, value),
+                        }
+                    }
+                }
+            }
+        } else {
+            w.set_error("upsert is not supported in this database");
+        }
+    }
+
+    fn build_column_names(&self, w: &mut SqlFrag, use_complete_name: bool, column_names: &Vec<ColumnName>){
+        let mut do_comma = false;
+        for c in column_names {
+            if do_comma{w.commasp();}else{do_comma = true;}
+            if use_complete_name{
+                w.qualified_identifier(&c.complete_name());
+            }else{
+                w.identifier(&c.column);
+            }
+        }
+    }
+
+    /// `OUTPUT INSERTED.a, INSERTED.b` in sqlserver, in place of the RETURNING clause,
+    /// the `pseudo_table` is `INSERTED` for insert and update, `DELETED` for delete
+    fn build_output(&self, w: &mut SqlFrag, pseudo_table: &str, column_names: &Vec<ColumnName>) {
+        w.left_river("OUTPUT ");
+        let mut do_comma = false;
+        for c in column_names {
+            if do_comma{w.commasp();}else{do_comma = true;}
+            w.append(pseudo_table);
+            w.append(".");
+            w.identifier(&c.column);
+        }
+    }
+
+
+    /// when there are joins, this is rendered as UPDATE .. FROM in postgresql,
+    /// UPDATE .. JOIN in mysql, the others select the joined values with correlated subqueries
+    fn build_update(&self, query: &Update,  build_mode: &BuildMode) -> SqlFrag {
+        let use_complete_name = false;
+        let sql_options = self.sql_options();
+        let has_joins = !query.joins.is_empty();
+        let update_from = has_joins && sql_options.contains(&SqlOption::SupportsUpdateFrom);
+        let multi_table = has_joins && sql_options.contains(&SqlOption::SupportsMultiTableUpdate);
+        let rewrite_joins = has_joins && !update_from && !multi_table;
+        let mut w = self.sql_frag(build_mode);
+        w.left_river("UPDATE ");
+        w.qualified_identifier(&query.table.complete_name());
+        if multi_table {
+            self.build_joins(&mut w, use_complete_name, &query.joins);
+        }
+        let mut do_comma = false;
+        if !query.columns.is_empty() {
+            w.left_river("SET ");
+        }
+        let mut column_index = 0;
+        assert_eq!(query.columns.len(), query.values.len());
+        for ec in &query.columns {
+            if do_comma {
+                w.commasp();
+            } else {
+                do_comma = true;
+            }
+            if multi_table {
+                w.qualified_identifier(&query.table.complete_name());
+                w.append(".");
+            }
+            w.identifier(&ec.column);
+            w.append(" = ");
+            let value = &query.values[column_index];
+            match *value {
+                // only the values that may refer to the joined tables are selected from them
+                Operand::Value(_) => self.build_operand(&mut w, use_complete_name, value),
+                _ if rewrite_joins => self.build_joined_value(&mut w, value, &query.joins),
+                _ => self.build_operand(&mut w, use_complete_name, value),
+            }
+            column_index += 1;
+        }
+        if !query.return_columns.is_empty() &&
+           sql_options.contains(&SqlOption::SupportsOutputClause) {
+            self.build_output(&mut w, "INSERTED", &query.return_columns);
+        }
+
+        if update_from {
+            w.left_river("FROM ");
+            self.build_join_tables(&mut w, &query.joins);
+            w.left_river("WHERE ");
+            self.build_filters(&mut w, use_complete_name, &self.join_filters(&query.joins, &query.filters));
+        } else if rewrite_joins {
+            w.left_river("WHERE ");
+            self.build_joined_filter(&mut w, &query.table, &query.joins, &query.filters);
+        } else if !query.filters.is_empty() {
+            w.left_river("WHERE ");
+            self.build_filters(&mut w, use_complete_name, &query.filters);
+        }
+        if !query.return_columns.is_empty() {
+            if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
+                w.left_river("RETURNING ");
+                self.build_column_names(&mut w, use_complete_name, &query.return_columns) 
+            }
+        }
+        w
+    }
+
+    /// when there are joins, this is rendered as DELETE .. USING in postgresql,
+    /// DELETE t FROM t JOIN .. in mysql and rewritten into a subquery in the others
+    fn build_delete(&self, query: &Delete, build_mode: &BuildMode) -> SqlFrag {
+        let use_complete_name = false;
+        let sql_options = self.sql_options();
+        let has_joins = !query.joins.is_empty();
+        let delete_using = has_joins && sql_options.contains(&SqlOption::SupportsUpdateFrom);
+        let multi_table = has_joins && sql_options.contains(&SqlOption::SupportsMultiTableUpdate);
+        let mut w = self.sql_frag(build_mode);
+        if multi_table {
+            w.left_river("DELETE ");
+            w.qualified_identifier(&query.from_table.complete_name());
+            w.left_river("FROM ");
+            w.qualified_identifier(&query.from_table.complete_name());
+            self.build_joins(&mut w, use_complete_name, &query.joins);
+        } else {
+            w.left_river("DELETE FROM ");
+            w.qualified_identifier(&query.from_table.complete_name());
+        }
+        if !query.return_columns.is_empty() &&
+           sql_options.contains(&SqlOption::SupportsOutputClause) {
+            self.build_output(&mut w, "DELETED", &query.return_columns);
+        }
+        if delete_using {
+            w.left_river("USING ");
+            self.build_join_tables(&mut w, &query.joins);
+            w.left_river("WHERE ");
+            self.build_filters(&mut w, use_complete_name, &self.join_filters(&query.joins, &query.filters));
+        } else if has_joins && !multi_table {
+            w.left_river("WHERE ");
+            self.build_joined_filter(&mut w, &query.from_table, &query.joins, &query.filters);
+        } else if !query.filters.is_empty() {
+            w.left_river("WHERE ");
+            self.build_filters(&mut w, use_complete_name, &query.filters);
+        }
+        if !query.return_columns.is_empty() {
+            if self.sql_options().contains(&SqlOption::SupportsReturningClause) {
+                w.left_river("RETURNING ");
+                self.build_column_names(&mut w, use_complete_name, &query.return_columns)
+            }
+        }
+        w
+    }
+}
+
+/// the text search configuration of postgresql as a literal, ie: `'english'`,
+/// not a parameter so the expression indexes on `to_tsvector('english', column)` are used
+pub fn ts_config(config: &str) -> String {
+    format!("'{}'", config.replace("'", "''"))
+}
+
+/// the words of the text as the fts5 query where all the words should match, as plainto_tsquery,
+/// each word is quoted so the punctuations are not read as the fts5 query syntax
+pub fn fts5_words(text: &str) -> String {
+    let words: Vec<String> = text.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace("\"", "\"\"")))
+        .collect();
+    words.join(" ")
+}
+
+/// the major, minor and patch number of the version reported by the database,
+/// ie: `3.35.5` of sqlite or `8.0.32-0ubuntu0.22.04.2` of mysql, the patch is 0 when there is none
+pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut numbers = version.split(|c: char| !c.is_digit(10));
+    let major = numbers.next().and_then(|n| n.parse().ok());
+    let minor = numbers.next().and_then(|n| n.parse().ok());
+    let patch = numbers.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+    match (major, minor) {
+        (Some(major), Some(minor)) => Some((major, minor, patch)),
+        _ => None,
+    }
+}
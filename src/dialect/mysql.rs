@@ -0,0 +1,132 @@
+use dialect::{Dialect, SqlOption, IdentifierQuoting};
+use dialect::parse_version;
+use writer::{SqlFrag, literal};
+use query::{Operand, Function};
+use dao::Value;
+
+/// the sql generation of mysql, without a connection
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct MysqlDialect {
+    pub identifier_quoting: IdentifierQuoting,
+    /// the major, minor and patch version of the server,
+    /// the features of the later versions are not used when it is not known
+    pub version: Option<(u32, u32, u32)>,
+}
+
+impl MysqlDialect {
+    pub fn new() -> Self {
+        MysqlDialect {
+            identifier_quoting: IdentifierQuoting::WhenNeeded,
+            version: None,
+        }
+    }
+
+    /// the dialect of the server version, as returned by `SELECT version()`
+    pub fn with_version(version: &str) -> Self {
+        MysqlDialect {
+            identifier_quoting: IdentifierQuoting::WhenNeeded,
+            version: parse_version(version),
+        }
+    }
+
+    fn is_at_least(&self, major: u32, minor: u32, patch: u32) -> bool {
+        match self.version {
+            Some(version) => version >= (major, minor, patch),
+            None => false,
+        }
+    }
+}
+
+impl Dialect for MysqlDialect {
+    /// return this list of options, supported features in the database
+    fn sql_options(&self) -> Vec<SqlOption> {
+        let mut options = vec![
+            SqlOption::UsesQuestionMark,//mysql uses question mark instead of the numbered params
+            SqlOption::SupportsNullSafeEqual,
+            SqlOption::SupportsRegexp,
+            SqlOption::SupportsAnyAll,
+            SqlOption::SupportsOnDuplicateKey,
+            SqlOption::SupportsMultiTableUpdate,
+            SqlOption::UsesBacktickQuote,
+            SqlOption::UsesBackslashEscape,
+            SqlOption::SupportsMatchAgainst, // mysql >= 5.6 for innodb
+        ];
+        if self.is_at_least(8, 0, 0) {
+            options.push(SqlOption::SupportsWindowFunctions);
+            options.push(SqlOption::SupportsCTE);
+            // FOR SHARE, NOWAIT and SKIP LOCKED
+            options.push(SqlOption::SupportsRowLocking);
+        }
+        if self.is_at_least(8, 0, 14) {
+            options.push(SqlOption::SupportsLateral);
+        }
+        options
+    }
+
+    fn identifier_quoting(&self) -> IdentifierQuoting {
+        self.identifier_quoting
+    }
+
+    /// map the functions into their mysql equivalent
+    fn build_function(&self, w: &mut SqlFrag, function: &Function) {
+        match &*function.function {
+            // LENGTH in mysql counts the bytes
+            "LENGTH" => self.build_function_call(w, "CHAR_LENGTH", &function.params),
+            "CAST" => {
+                let (operand, data_type) = match function.cast_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                // mysql can not cast to TEXT, the length is needed so the text is not truncated
+                // to the width of the casted value
+                match data_type {
+                    "TEXT" => self.build_cast(w, operand, "CHAR(4096)"),
+                    _ => self.build_cast(w, operand, data_type),
+                }
+            }
+            "STRING_AGG" => {
+                // the separator can not be a parameter in mysql,
+                // it is quoted as the literals are, escaping the backslashes too
+                let separator = match function.string_agg_separator() {
+                    Ok(separator) => literal(&Value::String(separator.to_owned()), &self.sql_options()),
+                    Err(e) => return w.set_error(&e),
+                };
+                w.append("GROUP_CONCAT(");
+                self.build_operand(w, false, &function.params[0]);
+                w.append(&format!(" SEPARATOR {})", separator));
+            }
+            "DATE_TRUNC" => {
+                let (unit, operand) = match function.date_trunc_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                let format = match unit {
+                    "year" => "%Y-01-01 00:00:00",
+                    "month" => "%Y-%m-01 00:00:00",
+                    "day" => "%Y-%m-%d 00:00:00",
+                    "hour" => "%Y-%m-%d %H:00:00",
+                    "minute" => "%Y-%m-%d %H:%i:00",
+                    "second" => "%Y-%m-%d %H:%i:%s",
+                    unit => return w.set_error(&format!("DATE_TRUNC with unit {} is not supported in mysql", unit)),
+                };
+                let params = vec![operand.clone(), Operand::Value(Value::String(format.to_owned()))];
+                self.build_function_call(w, "DATE_FORMAT", &params);
+            }
+            "TS_RANK" => {
+                // the relevance of the natural language search
+                let (column, _, text) = match function.text_search_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                w.append("MATCH (");
+                self.build_operand(w, false, column);
+                w.append(") AGAINST (");
+                self.build_operand(w, false, text);
+                w.append(" IN NATURAL LANGUAGE MODE)");
+            }
+            "TS_HEADLINE" => w.set_error("TS_HEADLINE is not supported in mysql"),
+            _ => self.build_function_call(w, &function.function, &function.params),
+        }
+    }
+}
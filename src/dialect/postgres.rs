@@ -0,0 +1,96 @@
+use dialect::{Dialect, SqlOption, IdentifierQuoting, ts_config};
+use writer::SqlFrag;
+use query::Function;
+
+/// the sql generation of postgresql, without a connection
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct PostgresDialect {
+    pub identifier_quoting: IdentifierQuoting,
+}
+
+impl PostgresDialect {
+    pub fn new() -> Self {
+        PostgresDialect { identifier_quoting: IdentifierQuoting::WhenNeeded }
+    }
+}
+
+impl Dialect for PostgresDialect {
+    /// return this list of options, supported features in the database
+    /// TODO: make this features version specific
+    /// http://www.postgresql.org/about/featurematrix/
+    /// writer CTE  >= 9.1
+    /// Inheritance  >= 9.0
+    /// JSON >= 9.2
+    /// JSONB >= 9.4
+    /// Returning >= 8.2
+    fn sql_options(&self) -> Vec<SqlOption> {
+        vec![
+            SqlOption::UsesNumberedParam,  // uses numbered parameters
+            SqlOption::SupportsReturningClause, // supports returning clause, feature
+            SqlOption::SupportsCTE,
+            SqlOption::SupportsInheritance,
+            SqlOption::UsesSchema,
+            SqlOption::ReturnMetaColumns,// whether to use the column names returned in a statement
+            SqlOption::SupportsILike,
+            SqlOption::SupportsIsDistinctFrom,
+            SqlOption::SupportsSimilarTo,
+            SqlOption::SupportsPosixRegex,
+            SqlOption::SupportsArray,
+            SqlOption::SupportsAnyAll,
+            SqlOption::SupportsOnConflict,
+            SqlOption::SupportsUpdateFrom,
+            SqlOption::SupportsRowLocking,
+            SqlOption::SupportsRowValues,
+            SqlOption::SupportsDistinctOn,
+            SqlOption::SupportsWindowFunctions,
+            SqlOption::SupportsLateral,
+            SqlOption::SupportsNullsOrdering,
+            SqlOption::UsesByteaHex,
+            SqlOption::SupportsTimestampTz,
+            SqlOption::SupportsTsVector,
+        ]
+    }
+
+    fn identifier_quoting(&self) -> IdentifierQuoting {
+        self.identifier_quoting
+    }
+
+    /// the full text search functions are done on the tsvector of the column
+    fn build_function(&self, w: &mut SqlFrag, function: &Function) {
+        match &*function.function {
+            "TS_RANK" => {
+                let (column, config, text) = match function.text_search_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                let config = ts_config(config);
+                w.append(&format!("ts_rank(to_tsvector({}, ", config));
+                self.build_operand(w, false, column);
+                w.append(&format!("), plainto_tsquery({}, ", config));
+                self.build_operand(w, false, text);
+                w.append("))");
+            }
+            "TS_HEADLINE" => {
+                let (column, config, text) = match function.text_search_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                let config = ts_config(config);
+                w.append(&format!("ts_headline({}, ", config));
+                self.build_operand(w, false, column);
+                w.append(&format!(", plainto_tsquery({}, ", config));
+                self.build_operand(w, false, text);
+                w.append("))");
+            }
+            "CAST" => {
+                let (operand, data_type) = match function.cast_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                self.build_cast(w, operand, data_type);
+            }
+            _ => self.build_function_call(w, &function.function, &function.params),
+        }
+    }
+}
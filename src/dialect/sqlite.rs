@@ -0,0 +1,161 @@
+use dialect::{Dialect, SqlOption, IdentifierQuoting};
+use dialect::parse_version;
+use writer::SqlFrag;
+use query::{Operand, Function, ColumnName};
+use dao::Value;
+
+/// the sql generation of sqlite, without a connection
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct SqliteDialect {
+    pub identifier_quoting: IdentifierQuoting,
+    /// the major, minor and patch version of the linked sqlite,
+    /// the features of the later versions are not used when it is not known
+    pub version: Option<(u32, u32, u32)>,
+}
+
+impl SqliteDialect {
+    pub fn new() -> Self {
+        SqliteDialect {
+            identifier_quoting: IdentifierQuoting::WhenNeeded,
+            version: None,
+        }
+    }
+
+    /// the dialect of the sqlite version, as returned by `SELECT sqlite_version()`
+    pub fn with_version(version: &str) -> Self {
+        SqliteDialect {
+            identifier_quoting: IdentifierQuoting::WhenNeeded,
+            version: parse_version(version),
+        }
+    }
+
+    fn is_at_least(&self, major: u32, minor: u32, patch: u32) -> bool {
+        match self.version {
+            Some(version) => version >= (major, minor, patch),
+            None => false,
+        }
+    }
+}
+
+impl Dialect for SqliteDialect {
+    /// return this list of options, supported features in the database
+    fn sql_options(&self) -> Vec<SqlOption> {
+        let mut options = vec![
+            SqlOption::UsesNumberedParam,  // uses numbered parameters
+            SqlOption::SupportsCTE,
+            SqlOption::UsesIntegerBoolean,
+            SqlOption::HasRowid,
+            SqlOption::SupportsIsOperator,
+            SqlOption::SupportsFts5,
+        ];
+        if self.is_at_least(3, 24, 0) {
+            options.push(SqlOption::SupportsOnConflict);
+        }
+        if self.is_at_least(3, 25, 0) {
+            options.push(SqlOption::SupportsWindowFunctions);
+        }
+        if self.is_at_least(3, 30, 0) {
+            options.push(SqlOption::SupportsNullsOrdering);
+        }
+        if self.is_at_least(3, 35, 0) {
+            options.push(SqlOption::SupportsReturningClause);
+        }
+        options
+    }
+
+    fn identifier_quoting(&self) -> IdentifierQuoting {
+        self.identifier_quoting
+    }
+
+    /// sqlite has no NOW, date_trunc and string_agg,
+    /// use their equivalent instead
+    fn build_function(&self, w: &mut SqlFrag, function: &Function) {
+        match &*function.function {
+            "NOW" => {
+                w.append("CURRENT_TIMESTAMP");
+            }
+            "SUBSTRING" => self.build_function_call(w, "SUBSTR", &function.params),
+            "STRING_AGG" => self.build_function_call(w, "GROUP_CONCAT", &function.params),
+            "CONCAT" => {
+                w.append("(");
+                let mut do_concat = false;
+                for param in &function.params {
+                    if do_concat {
+                        w.append(" || ");
+                    } else {
+                        do_concat = true;
+                    }
+                    self.build_operand(w, false, param);
+                }
+                w.append(")");
+            }
+            "CAST" => {
+                let (operand, data_type) = match function.cast_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                self.build_cast(w, operand, data_type);
+            }
+            "DATE_TRUNC" => {
+                let (unit, operand) = match function.date_trunc_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                let format = match unit {
+                    "year" => "%Y-01-01 00:00:00",
+                    "month" => "%Y-%m-01 00:00:00",
+                    "day" => "%Y-%m-%d 00:00:00",
+                    "hour" => "%Y-%m-%d %H:00:00",
+                    "minute" => "%Y-%m-%d %H:%M:00",
+                    "second" => "%Y-%m-%d %H:%M:%S",
+                    unit => return w.set_error(&format!("DATE_TRUNC with unit {} is not supported in sqlite", unit)),
+                };
+                let params = vec![Operand::Value(Value::String(format.to_owned())), operand.clone()];
+                self.build_function_call(w, "STRFTIME", &params);
+            }
+            "TS_RANK" => {
+                // bm25 is lower for the better matches
+                let (column, _, _) = match function.text_search_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                match fts5_table(column) {
+                    Some(table) => {
+                        w.append("-bm25(");
+                        w.identifier(table);
+                        w.append(")");
+                    }
+                    None => w.set_error(&fts5_table_error(&function.function)),
+                }
+            }
+            "TS_HEADLINE" => {
+                let (column, _, _) = match function.text_search_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                match fts5_table(column) {
+                    Some(table) => {
+                        w.append("snippet(");
+                        w.identifier(table);
+                        w.append(", -1, '<b>', '</b>', ' ... ', 35)");
+                    }
+                    None => w.set_error(&fts5_table_error(&function.function)),
+                }
+            }
+            _ => self.build_function_call(w, &function.function, &function.params),
+        }
+    }
+}
+
+/// the fts5 table of the column, the auxiliary functions of fts5 are called with the table
+fn fts5_table(column: &Operand) -> Option<&str> {
+    match *column {
+        Operand::ColumnName(ColumnName { table: Some(ref table), .. }) => Some(&table[..]),
+        _ => None,
+    }
+}
+
+fn fts5_table_error(function: &str) -> String {
+    format!("{} expects the column qualified with the fts5 table in sqlite", function)
+}
@@ -0,0 +1,77 @@
+use dialect::{Dialect, SqlOption, IdentifierQuoting};
+use writer::SqlFrag;
+use query::Function;
+use query::function::DATE_TRUNC_UNITS;
+
+/// the sql generation of sql server (T-SQL), without a connection
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct SqlServerDialect {
+    pub identifier_quoting: IdentifierQuoting,
+}
+
+impl SqlServerDialect {
+    pub fn new() -> Self {
+        SqlServerDialect { identifier_quoting: IdentifierQuoting::WhenNeeded }
+    }
+}
+
+impl Dialect for SqlServerDialect {
+    /// return this list of options, supported features in the database
+    fn sql_options(&self) -> Vec<SqlOption> {
+        vec![
+            SqlOption::UsesAtParam,
+            SqlOption::UsesBracketQuote,
+            SqlOption::UsesOffsetFetch,
+            SqlOption::SupportsOutputClause,
+            SqlOption::SupportsCTE,
+            SqlOption::OmitsRecursiveKeyword,
+            SqlOption::UsesSchema,
+            SqlOption::UsesIntegerBoolean,
+            SqlOption::UsesHexLiteral,
+            SqlOption::SupportsAnyAll,
+            SqlOption::SupportsWindowFunctions,
+        ]
+    }
+
+    fn identifier_quoting(&self) -> IdentifierQuoting {
+        self.identifier_quoting
+    }
+
+    /// map the functions into their T-SQL equivalent
+    fn build_function(&self, w: &mut SqlFrag, function: &Function) {
+        match &*function.function {
+            "NOW" => {
+                w.append("GETDATE()");
+            }
+            "LENGTH" => self.build_function_call(w, "LEN", &function.params),
+            "CAST" => {
+                let (operand, data_type) = match function.cast_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                match data_type {
+                    "TEXT" => self.build_cast(w, operand, "NVARCHAR(MAX)"),
+                    _ => self.build_cast(w, operand, data_type),
+                }
+            }
+            "DATE_TRUNC" => {
+                // sql server >= 2022, the unit is a keyword and not a string
+                let (unit, operand) = match function.date_trunc_parts() {
+                    Ok(parts) => parts,
+                    Err(e) => return w.set_error(&e),
+                };
+                if !DATE_TRUNC_UNITS.contains(&unit) {
+                    return w.set_error(&format!("DATE_TRUNC with unit {} is not supported in sqlserver", unit));
+                }
+                w.append(&format!("DATETRUNC({}, ", unit));
+                self.build_operand(w, false, operand);
+                w.append(")");
+            }
+            "TS_RANK" | "TS_HEADLINE" => {
+                w.set_error(&format!("{} is not supported in sqlserver", function.function))
+            }
+            _ => self.build_function_call(w, &function.function, &function.params),
+        }
+    }
+}
@@ -3,10 +3,40 @@ use query::IsTable;
 use dao::IsDao;
 use query::Select;
 use query::Filter;
+use query::Equality;
 use query::Delete;
 use query::Insert;
 use query::Update;
+use dao::Dao;
 use dao::Value;
+use dao::ToValue;
+use query::{Operand, TableName};
+use query::operand::ToOperand;
+use query::Traversal;
+use query::tree::{self, TREE_DEPTH, TREE_PATH};
+use table::Table;
+
+/// a node of the tree together with its position from the starting node
+#[derive(Debug)]
+pub struct TreeNode<T> {
+    pub node: T,
+    /// the starting node is at depth 0
+    pub depth: usize,
+    /// the keys of the nodes from the starting node, separated with `/`
+    pub path: String,
+}
+
+/// the values of the object in the order of the columns of the table,
+/// the columns with no value, ie: the Option fields that are None, are inserted as NULL
+fn column_values(table: &TableName, dao: &Dao) -> Vec<Operand> {
+    table.columns
+        .iter()
+        .map(|c| match dao.get(&c.column) {
+            Some(value) => Operand::Value(value.to_owned()),
+            None => Operand::Null,
+        })
+        .collect()
+}
 
 /// A higher level API for manipulating objects in the database
 /// This serves as a helper function for the query api
@@ -74,32 +104,148 @@ impl <'a>EntityManager<'a> {
             q.column(&c.column);
         }
         q.return_all();
+        for value in &column_values(&table, &dao) {
+            q.value(value);
+        }
+        q.insert(self.db)
+    }
+
+    /// insert all the objects in batches of multiple rows
+    /// returns the inserted records when the database supports the RETURNING clause
+    pub fn insert_all<T,D>(&self, ts: &[T]) -> Result<Vec<D>, DbError>
+        where T: IsTable + IsDao, D: IsDao
+    {
+        let table = T::table_name();
+        let mut q = Insert::into(&table);
         for c in &table.columns {
-            let value:Option<&Value> = dao.get(&c.column);
-            match value {
-                Some(value) => {
-                    q.value(value);
-                }
-                None => (),
+            q.column(&c.column);
+        }
+        q.return_all();
+        if ts.is_empty() {
+            return Ok(vec![]);
+        }
+        for t in ts {
+            let values = column_values(&table, &t.to_dao());
+            q.row(values.iter().map(|v| v as &ToOperand).collect());
+        }
+        q.insert_all(self.db)
+    }
+
+    /// insert the object, or update the existing record
+    /// when it conflicts on the unique `conflict_columns`
+    /// all the other columns are updated with the values of the object.
+    /// When all the columns are conflict columns, the existing record is left as is
+    /// and is selected with the values of the conflict columns, since it is not returned by the insert
+    pub fn upsert<T,D>(&self, t: &T, conflict_columns: Vec<&str>) -> Result<D, DbError>
+        where T: IsTable + IsDao, D: IsDao
+    {
+        let table = T::table_name();
+        let dao = t.to_dao();
+        let mut existing = Select::all();
+        existing.from(&table);
+        for column in &conflict_columns {
+            match dao.get(*column) {
+                Some(value) => existing.add_filter(&Filter::new(column, Equality::EQ, value)),
+                None => return Err(DbError::new(&format!("No value for the conflict column {}", column))),
             }
         }
-        q.insert(self.db)
+        let mut q = Insert::into(&table);
+        for c in &table.columns {
+            q.column(&c.column);
+        }
+        q.return_all();
+        for value in &column_values(&table, &dao) {
+            q.value(value);
+        }
+        let update_columns: Vec<&str> = table.columns
+            .iter()
+            .map(|c| &c.column[..])
+            .filter(|c| !conflict_columns.contains(c))
+            .collect();
+        if update_columns.is_empty() {
+            q.on_conflict(conflict_columns).do_nothing();
+        } else {
+            q.on_conflict(conflict_columns).do_update(update_columns);
+        }
+        let mut upserted = try!(self.db.insert_all(&q));
+        if !upserted.is_empty() {
+            return Ok(D::from_dao(&upserted.remove(0)));
+        }
+        // nothing is returned on DO NOTHING, or when the RETURNING clause is not supported
+        match try!(existing.retrieve_one(self.db)) {
+            Some(dao) => Ok(D::from_dao(&dao)),
+            None => Err(DbError::new("No result from upsert")),
+        }
+    }
+
+    /// get the node with the `id` key and all the nodes below it,
+    /// the table of T needs a foreign key referring to itself.
+    /// The tables should contain the table of T and the tables it refers to, ie: from the `get_all_tables` of the database
+    pub fn get_descendants<T>(&self, tables: &[Table], id: &ToValue, max_depth: usize) -> Result<Vec<TreeNode<T>>, DbError>
+        where T: IsTable + IsDao {
+        self.get_tree(tables, id, Traversal::DESCENDANTS, max_depth)
+    }
+
+    /// get the node with the `id` key and its parents up to the root
+    pub fn get_ancestors<T>(&self, tables: &[Table], id: &ToValue, max_depth: usize) -> Result<Vec<TreeNode<T>>, DbError>
+        where T: IsTable + IsDao {
+        self.get_tree(tables, id, Traversal::ANCESTORS, max_depth)
+    }
+
+    /// traverse the tree from the node with the `id` key following the self referencing foreign key,
+    /// up to `max_depth` levels from the node
+    pub fn get_tree<T>(&self,
+                       tables: &[Table],
+                       id: &ToValue,
+                       traversal: Traversal,
+                       max_depth: usize)
+                       -> Result<Vec<TreeNode<T>>, DbError>
+        where T: IsTable + IsDao {
+        let table_name = T::table_name();
+        let table = match tables.iter().find(|t| t.name == table_name.name && t.schema == table_name.schema) {
+            Some(table) => table,
+            None => return Err(DbError::new(&format!("table {} is not in the tables", table_name.name))),
+        };
+        let (parent, key) = match tree::self_reference(table, tables) {
+            Some(reference) => reference,
+            None => return Err(DbError::new(&format!("table {} has no foreign key referring to itself", table.name))),
+        };
+        let mut query = tree::tree_query(&table_name, key, &parent.name, id.to_db_type(), traversal, max_depth);
+        let result = try!(query.retrieve(self.db));
+        let mut nodes = vec![];
+        for dao in &result.dao {
+            let depth = match dao.get(TREE_DEPTH) {
+                Some(&Value::I32(depth)) => depth as usize,
+                Some(&Value::I64(depth)) => depth as usize,
+                _ => return Err(DbError::new("no depth in the tree node")),
+            };
+            let path = match dao.get(TREE_PATH) {
+                Some(&Value::String(ref path)) => path.to_owned(),
+                _ => return Err(DbError::new("no path in the tree node")),
+            };
+            nodes.push(TreeNode {
+                node: T::from_dao(dao),
+                depth: depth,
+                path: path,
+            });
+        }
+        Ok(nodes)
     }
 
     /// starts a database transaction
     /// the next succedding function calls will be
     /// wrapped in a transaction and will not effect the database
     /// until the commit at the end is called
-    pub fn begin(&self) {
+    pub fn begin(&self) -> Result<(), DbError> {
         self.db.begin()
     }
 
     /// commits a database transaction
-    pub fn commit(&self) {
+    pub fn commit(&self) -> Result<(), DbError> {
         self.db.commit()
     }
     /// when there is a problem with the transaction process, this can be called
-    pub fn rollback(&self) {
+    pub fn rollback(&self) -> Result<(), DbError> {
         self.db.rollback()
     }
 
@@ -31,6 +31,7 @@ extern crate time;
 pub mod query;
 pub mod dao;
 pub mod database;
+pub mod dialect;
 pub mod platform;
 pub mod table;
 pub mod writer;
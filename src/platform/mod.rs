@@ -1,4 +1,5 @@
 pub mod pool;
+pub mod statement_cache;
 #[cfg(feature = "postgres")]
 pub mod postgres;
 #[cfg(feature = "sqlite")]
@@ -3,7 +3,9 @@ use table::{Table, Column, Foreign};
 
 use dao::Value;
 use writer::SqlFrag;
-use database::{SqlOption, BuildMode};
+use database::{BuildMode, IdentifierQuoting};
+use dialect::{Dialect, MysqlDialect};
+use dialect::parse_version;
 use regex::Regex;
 
 use mysql::value::Value as MyValue;
@@ -13,12 +15,14 @@ use mysql::value::IntoValue;
 use mysql::error::MyResult;
 use mysql::value::from_row;
 use mysql::conn::Stmt;
+use mysql::conn::Transaction;
 use mysql::conn::pool::MyPool;
 use chrono::naive::datetime::NaiveDateTime;
 use chrono::datetime::DateTime;
 use chrono::offset::fixed::FixedOffset;
 use mysql::conn::{MyConn,MyOpts};
 use config::DbConfig;
+use std::cell::RefCell;
 
 use query::Operand;
 
@@ -27,7 +31,7 @@ use database::{Database, DatabaseDev, DatabaseDDL, DbError};
 use time::Timespec;
 use dao::Type;
 use query::Update;
-use query::Delete;
+use query::Rewriter;
 
 
 pub fn establish_connection(config: &DbConfig) -> Result<MyConn, DbError> {
@@ -45,14 +49,43 @@ pub fn establish_connection(config: &DbConfig) -> Result<MyConn, DbError> {
 
 pub struct Mysql {
     pool: Option<MyPool>,
+    /// the statements are executed in the connection of the transaction while it is started
+    transaction: RefCell<Option<Transaction<'static>>>,
+    dialect: MysqlDialect,
+    rewriters: Vec<Box<Rewriter>>,
 }
 impl Mysql {
     pub fn new() -> Self {
-        Mysql { pool: None }
+        Mysql {
+            pool: None,
+            transaction: RefCell::new(None),
+            dialect: MysqlDialect::new(),
+            rewriters: vec![],
+        }
     }
 
     pub fn with_pooled_connection(pool: MyPool) -> Self {
-        Mysql { pool: Some(pool) }
+        let mut my = Mysql {
+            pool: Some(pool),
+            transaction: RefCell::new(None),
+            dialect: MysqlDialect::new(),
+            rewriters: vec![],
+        };
+        // the window functions are only used when the server supports them
+        if let Ok(version) = my.version() {
+            my.dialect.version = parse_version(&version);
+        }
+        my
+    }
+
+    /// quote all the identifiers or only those that needs to be
+    pub fn set_identifier_quoting(&mut self, quoting: IdentifierQuoting) {
+        self.dialect.identifier_quoting = quoting;
+    }
+
+    /// rewrite the queries executed in this connection before the sql is built
+    pub fn add_rewriter(&mut self, rewriter: Box<Rewriter>) {
+        self.rewriters.push(rewriter);
     }
 
     fn from_rust_type_tosql(types: &[Value]) -> Vec<MyValue> {
@@ -365,12 +398,21 @@ impl Mysql {
     }
 
 
+    /// the statement prepared by the pool holds on to its pooled connection,
+    /// so the statements are not kept in a cache, which would take the connections out of the pool.
+    /// TODO: cache the statements per pooled connection once the connections are checked out of the pool,
+    /// until then there are no statement cache stats and nothing to invalidate in mysql
     fn get_prepared_statement<'a>(&'a self, sql: &'a str) -> MyResult<Stmt> {
         self.pool.as_ref().unwrap().prepare(sql)
     }
 }
 
 impl Database for Mysql {
+    /// the sql is generated by the MysqlDialect
+    fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
     fn version(&self) -> Result<String, DbError> {
         let sql = "SELECT version()";
         let dao = try!(self.execute_sql_with_one_return(sql, &vec![]));
@@ -390,37 +432,68 @@ impl Database for Mysql {
         }
     }
 
-    fn begin(&self) {
-       let _ = self.execute_sql("START TRANSACTION", &[]); 
+    fn rewriters(&self) -> &[Box<Rewriter>] {
+        &self.rewriters
     }
-    fn commit(&self) {
-       let _ = self.execute_sql("COMMIT", &[]); 
+
+    /// each statement executed in the pool can be given a different connection,
+    /// so the transaction holds on to one connection until it is committed or rolled back
+    fn begin(&self) -> Result<(), DbError> {
+        let mut transaction = self.transaction.borrow_mut();
+        if transaction.is_some() {
+            return Err(DbError::new("A transaction is already started"));
+        }
+        assert!(self.pool.is_some());
+        *transaction = Some(try!(self.pool.as_ref().unwrap().start_transaction(false, None, None)));
+        Ok(())
+    }
+    fn commit(&self) -> Result<(), DbError> {
+        match self.transaction.borrow_mut().take() {
+            Some(transaction) => {
+                try!(transaction.commit());
+                Ok(())
+            }
+            None => Err(DbError::new("No transaction to commit")),
+        }
     }
-    fn rollback(&self) {
-       let _ = self.execute_sql("ROLLBACK", &[]); 
+    fn rollback(&self) -> Result<(), DbError> {
+        match self.transaction.borrow_mut().take() {
+            Some(transaction) => {
+                try!(transaction.rollback());
+                Ok(())
+            }
+            None => Err(DbError::new("No transaction to rollback")),
+        }
     }
-
-
-    /// return this list of options, supported features in the database
-    fn sql_options(&self) -> Vec<SqlOption> {
-        vec![
-            SqlOption::UsesQuestionMark,//mysql uses question mark instead of the numbered params
-        ]
+    fn in_transaction(&self) -> bool {
+        self.transaction.borrow().is_some()
     }
 
-    fn update(&self, _query: &Update) -> Result<Dao, DbError> {
-        unimplemented!()
+
+    /// the maximum placeholders in a prepared statement
+    fn max_parameters(&self) -> usize {
+        65535
     }
 
-    fn delete(&self, _query: &Delete) -> Result<usize, DbError> {
-        unimplemented!()
+    /// mysql has no RETURNING clause, the updated record is selected prior to updating
+    fn update(&self, query: &Update) -> Result<Dao, DbError> {
+        let mut daos = try!(self.update_all(query));
+        if daos.is_empty() {
+            Err(DbError::new("No result from update"))
+        } else {
+            Ok(daos.remove(0))
+        }
     }
 
     fn execute_sql_with_return(&self, sql: &str, params: &[Value]) -> Result<Vec<Dao>, DbError> {
         debug!("SQL: \n{}", sql);
         debug!("param: {:?}", params);
         assert!(self.pool.is_some());
-        let mut stmt = try!(self.get_prepared_statement(sql));
+        let mut transaction = self.transaction.borrow_mut();
+        let mut stmt = match *transaction {
+            Some(ref mut transaction) => try!(transaction.prepare(sql)),
+            None => try!(self.get_prepared_statement(sql)),
+        };
         let mut columns = vec![];
         for col in stmt.columns_ref().unwrap() {
             let column_name = String::from_utf8(col.name.clone()).unwrap();
@@ -466,7 +539,11 @@ impl Database for Mysql {
         debug!("param: {:?}", params);
         let to_sql_types = Mysql::from_rust_type_tosql(params);
         assert!(self.pool.is_some());
-        let result = try!(self.pool.as_ref().unwrap().prep_exec(sql, &to_sql_types));
+        let mut transaction = self.transaction.borrow_mut();
+        let result = match *transaction {
+            Some(ref mut transaction) => try!(transaction.prep_exec(sql, &to_sql_types)),
+            None => try!(self.pool.as_ref().unwrap().prep_exec(sql, &to_sql_types)),
+        };
         Ok(result.affected_rows() as usize)
     }
 }
@@ -481,9 +558,9 @@ impl DatabaseDDL for Mysql {
     }
 
     fn build_create_table(&self, table: &Table) -> SqlFrag {
-        let mut w = SqlFrag::new(self.sql_options(), &BuildMode::Standard);
+        let mut w = self.dialect.sql_frag(&BuildMode::Standard);
         w.append("CREATE TABLE ");
-        w.append(&table.name);
+        w.identifier(&table.name);
         w.append("(");
         w.ln_tab();
         let mut do_comma = false;
@@ -493,7 +570,7 @@ impl DatabaseDDL for Mysql {
             } else {
                 do_comma = true;
             }
-            w.append(&c.name);
+            w.identifier(&c.name);
             w.append(" ");
             let dt = self.rust_type_to_dbtype(&c.data_type);
             w.append(&dt);
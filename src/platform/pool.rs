@@ -19,6 +19,10 @@ use mysql::conn::pool::MyPool;
 #[cfg(feature = "mysql")]
 use mysql::conn::MyOpts;
 use database::DbError;
+#[allow(unused)]
+use platform::statement_cache::{self, CachingConnectionManager};
+use database::IdentifierQuoting;
+use query::{Query, Rewriter};
 use std::ops::Deref;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
@@ -51,12 +55,30 @@ pub struct PoolConfig{
 /// pool size is 10;
 #[cfg(any(feature = "postgres",feature = "sqlite", feature ="mysql"))]
 pub fn db_with_url(db_url: &str) -> Result<Platform, DbError> {
-    let config = PoolConfig{
+    db_with_config(&global_config(db_url))
+}
+
+#[allow(unused)]
+fn global_config(db_url: &str) -> PoolConfig {
+    PoolConfig{
         connection_name: "GLOBAL".to_string(),
         db_url: db_url.to_string(),
         pool_size: 10
-    };
-    db_with_config(&config)
+    }
+}
+
+/// rewrite the queries of all the connections given by `db_with_url` for this url,
+/// the pool is created when there is none yet
+#[cfg(any(feature = "postgres",feature = "sqlite", feature ="mysql"))]
+pub fn add_rewriter_with_url(db_url: &str, rewriter: Arc<Rewriter + Send + Sync>) -> Result<(), DbError> {
+    let config = global_config(db_url);
+    let mut pools = DB_POOL.write().unwrap();
+    if !pools.contains_key(&config) {
+        let pool = try!(ManagedPool::init(&config.db_url, config.pool_size as usize));
+        pools.insert(config.clone(), pool);
+    }
+    pools.get_mut(&config).unwrap().add_rewriter(rewriter);
+    Ok(())
 }
 
 #[cfg(any(feature = "postgres",feature = "sqlite", feature ="mysql"))]
@@ -177,6 +199,30 @@ impl Platform {
             Platform::Mysql(ref my) => my,
         }
     }
+
+    /// quote all the identifiers or only those that needs to be
+    pub fn set_identifier_quoting(&mut self, quoting: IdentifierQuoting) {
+        match *self {
+            #[cfg(feature = "postgres")]
+            Platform::Postgres(ref mut pg) => pg.set_identifier_quoting(quoting),
+            #[cfg(feature = "sqlite")]
+            Platform::Sqlite(ref mut lite) => lite.set_identifier_quoting(quoting),
+            #[cfg(feature = "mysql")]
+            Platform::Mysql(ref mut my) => my.set_identifier_quoting(quoting),
+        }
+    }
+
+    /// rewrite the queries executed in this connection before the sql is built
+    pub fn add_rewriter(&mut self, rewriter: Box<Rewriter>) {
+        match *self {
+            #[cfg(feature = "postgres")]
+            Platform::Postgres(ref mut pg) => pg.add_rewriter(rewriter),
+            #[cfg(feature = "sqlite")]
+            Platform::Sqlite(ref mut lite) => lite.add_rewriter(rewriter),
+            #[cfg(feature = "mysql")]
+            Platform::Mysql(ref mut my) => my.add_rewriter(rewriter),
+        }
+    }
 }
 
 impl Deref for Platform {
@@ -196,18 +242,60 @@ impl Deref for Platform {
 }
 
 
+/// the pool of connections together with the rewriters
+/// applied to the queries of every connection checked out of the pool
+pub struct ManagedPool {
+    pool: PlatformPool,
+    rewriters: Vec<Arc<Rewriter + Send + Sync>>,
+}
+
+/// the rewriter of the pool, shared by the connections
+struct PoolRewriter(Arc<Rewriter + Send + Sync>);
+
+impl Rewriter for PoolRewriter {
+    fn rewrite(&self, query: Query) -> Result<Query, DbError> {
+        self.0.rewrite(query)
+    }
+}
+
 /// Postgres, Sqlite uses r2d2 connection manager,
 /// Mysql has its own connection pooling
-pub enum ManagedPool {
+pub enum PlatformPool {
     #[cfg(feature = "postgres")]
-    Postgres(Pool<PostgresConnectionManager>),
+    Postgres(Pool<CachingConnectionManager<PostgresConnectionManager>>),
     #[cfg(feature = "sqlite")]
-    Sqlite(Pool<SqliteConnectionManager>),
+    Sqlite(Pool<CachingConnectionManager<SqliteConnectionManager>>),
     #[cfg(feature = "mysql")]
     Mysql(Option<MyPool>),
 }
 
 impl ManagedPool {
+    /// initialize the pool
+    pub fn init(url: &str, pool_size: usize) -> Result<Self, DbError> {
+        let pool = try!(PlatformPool::init(url, pool_size));
+        Ok(ManagedPool {
+            pool: pool,
+            rewriters: vec![],
+        })
+    }
+
+    /// rewrite the queries of all the connections checked out of this pool before the sql is built,
+    /// these are applied before the rewriters added to the connection itself
+    pub fn add_rewriter(&mut self, rewriter: Arc<Rewriter + Send + Sync>) {
+        self.rewriters.push(rewriter);
+    }
+
+    /// a conection is created here
+    pub fn connect(&self) -> Result<Platform, DbError> {
+        let mut platform = try!(self.pool.connect());
+        for rewriter in &self.rewriters {
+            platform.add_rewriter(Box::new(PoolRewriter(rewriter.clone())));
+        }
+        Ok(platform)
+    }
+}
+
+impl PlatformPool {
     /// initialize the pool
     #[allow(unused)]
     pub fn init(url: &str, pool_size: usize) -> Result<Self, DbError> {
@@ -220,18 +308,20 @@ impl ManagedPool {
                     #[cfg(feature = "postgres")]
                     "postgres" => {
                         let manager = try!(PostgresConnectionManager::new(url, SslMode::None));
+                        let manager = CachingConnectionManager::discarding_stale(manager, statement_cache::DEFAULT_CAPACITY);
                         debug!("Creating a connection with a pool size of {}", pool_size);
                         let config = Config::builder().pool_size(pool_size).build();
                         let pool = try!(Pool::new(config, manager));
-                        Ok(ManagedPool::Postgres(pool))
+                        Ok(PlatformPool::Postgres(pool))
                     }
 
                     #[cfg(feature = "sqlite")]
                     "sqlite" => {
                         let manager = SqliteConnectionManager::new(&config.database);
+                        let manager = CachingConnectionManager::new(manager, statement_cache::DEFAULT_CAPACITY);
                         let config = Config::builder().pool_size(pool_size).build();
                         let pool = try!(Pool::new(config, manager));
-                        Ok(ManagedPool::Sqlite(pool))
+                        Ok(PlatformPool::Sqlite(pool))
                     }
                     #[cfg(feature = "mysql")]
                     "mysql" => {
@@ -244,7 +334,7 @@ impl ManagedPool {
                             ..Default::default()
                         };
                         let pool = try!(MyPool::new_manual(0, pool_size as usize, opts));
-                        Ok(ManagedPool::Mysql(Some(pool)))
+                        Ok(PlatformPool::Mysql(Some(pool)))
                     }
 
                     _ => unimplemented!(),
@@ -262,7 +352,7 @@ impl ManagedPool {
     pub fn connect(&self) -> Result<Platform, DbError> {
         match *self {
             #[cfg(feature = "postgres")]
-            ManagedPool::Postgres(ref pool) => {
+            PlatformPool::Postgres(ref pool) => {
                 match pool.get() {
                     Ok(conn) => {
                         let pg = Postgres::with_pooled_connection(conn);
@@ -272,7 +362,7 @@ impl ManagedPool {
                 }
             }
             #[cfg(feature = "sqlite")]
-            ManagedPool::Sqlite(ref pool) => {
+            PlatformPool::Sqlite(ref pool) => {
                 match pool.get() {
                     Ok(conn) => {
                         let lite = Sqlite::with_pooled_connection(conn);
@@ -282,7 +372,7 @@ impl ManagedPool {
                 }
             }
             #[cfg(feature = "mysql")]
-            ManagedPool::Mysql(ref pool) => {
+            PlatformPool::Mysql(ref pool) => {
                 let my = Mysql::with_pooled_connection(pool.clone().unwrap());// I hope cloning doesn't really clone the pool, just the Arc
                 Ok(Platform::Mysql(my))
             }
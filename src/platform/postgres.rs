@@ -1,8 +1,10 @@
 use table::{Table, Column, Foreign};
+use std::cell::Cell;
 use dao::Dao;
 
 use postgres::SslMode;
 use postgres::Connection as PgConnection;
+use postgres::stmt::Statement as PgStatement;
 use regex::Regex;
 use dao::Value;
 use database::{Database, DatabaseDev, DatabaseDDL, DbError};
@@ -10,11 +12,14 @@ use postgres::types::Type as PgType;
 use postgres::types::ToSql;
 use writer::SqlFrag;
 use postgres::rows::Row;
-use database::SqlOption;
+use database::IdentifierQuoting;
+use dialect::{Dialect, PostgresDialect};
 use r2d2::PooledConnection;
 use r2d2_postgres::PostgresConnectionManager;
+use platform::statement_cache::{CachingConnectionManager, CacheStats};
 use dao::Type;
 use query::Operand;
+use query::Rewriter;
 
 
 pub fn establish_connection(db_url: &str) -> Result<PgConnection, DbError>{
@@ -23,8 +28,12 @@ pub fn establish_connection(db_url: &str) -> Result<PgConnection, DbError>{
 }
 
 pub struct Postgres {
-    /// a connection pool is provided
-    pub pool: Option<PooledConnection<PostgresConnectionManager>>,
+    /// a connection pool is provided, the connection keeps the tracking of its cached statements
+    pub pool: Option<PooledConnection<CachingConnectionManager<PostgresConnectionManager>>>,
+    dialect: PostgresDialect,
+    rewriters: Vec<Box<Rewriter>>,
+    /// a transaction is begun and not yet committed or rolled back
+    in_transaction: Cell<bool>,
 }
 
 /// Build the Query into a SQL statements that is a valid
@@ -37,12 +46,53 @@ impl Postgres {
     /// useful when just building sql queries specific to this platform
     /// inexpensive operation, so can have multiple instances
     pub fn new() -> Self {
-        Postgres { pool: None }
+        Postgres {
+            pool: None,
+            dialect: PostgresDialect::new(),
+            rewriters: vec![],
+            in_transaction: Cell::new(false),
+        }
+    }
+
+
+    pub fn with_pooled_connection(pool: PooledConnection<CachingConnectionManager<PostgresConnectionManager>>) -> Self {
+        Postgres {
+            pool: Some(pool),
+            dialect: PostgresDialect::new(),
+            rewriters: vec![],
+            in_transaction: Cell::new(false),
+        }
     }
 
+    /// quote all the identifiers or only those that needs to be
+    pub fn set_identifier_quoting(&mut self, quoting: IdentifierQuoting) {
+        self.dialect.identifier_quoting = quoting;
+    }
 
-    pub fn with_pooled_connection(pool: PooledConnection<PostgresConnectionManager>) -> Self {
-        Postgres { pool: Some(pool) }
+    /// rewrite the queries executed in this connection before the sql is built
+    pub fn add_rewriter(&mut self, rewriter: Box<Rewriter>) {
+        self.rewriters.push(rewriter);
+    }
+
+    /// rust-postgres keeps the cached statements until the connection is closed,
+    /// so only the statements that are reused while still in the statement cache are prepared as cached,
+    /// and no more than the capacity of the cache are ever kept on the connection,
+    /// the other statements are prepared and closed on each call.
+    /// once the schema is changed the cached statements of the connection are not used anymore
+    fn prepare<'a>(&self, conn: &'a PgConnection, sql: &str) -> Result<PgStatement<'a>, DbError> {
+        let is_cached = match self.pool {
+            Some(ref pool) => {
+                let mut cache = pool.statement_cache.borrow_mut();
+                !pool.is_stale() && cache.touch(sql) && cache.keep(sql)
+            }
+            None => false,
+        };
+        let stmt = if is_cached {
+            try!(conn.prepare_cached(sql))
+        } else {
+            try!(conn.prepare(sql))
+        };
+        Ok(stmt)
     }
 
 
@@ -484,6 +534,11 @@ impl Postgres {
 
 
 impl Database for Postgres {
+    /// the sql is generated by the PostgresDialect
+    fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
     fn version(&self) -> Result<String, DbError> {
         let sql = "SHOW server_version";
         let dao = try!(self.execute_sql_with_one_return(sql, &vec![]));
@@ -503,33 +558,49 @@ impl Database for Postgres {
         }
     }
 
-    fn begin(&self) {
-       let _ = self.execute_sql("BEGIN", &[]); 
+    fn rewriters(&self) -> &[Box<Rewriter>] {
+        &self.rewriters
+    }
+
+    fn begin(&self) -> Result<(), DbError> {
+        if self.in_transaction.get() {
+            return Err(DbError::new("A transaction is already started"));
+        }
+        try!(self.execute_sql("BEGIN", &[]));
+        self.in_transaction.set(true);
+        Ok(())
+    }
+    fn commit(&self) -> Result<(), DbError> {
+        // the transaction is over even when the commit fails, postgresql rolls it back
+        self.in_transaction.set(false);
+        try!(self.execute_sql("COMMIT", &[]));
+        Ok(())
     }
-    fn commit(&self) {
-       let _ = self.execute_sql("COMMIT", &[]); 
+    fn rollback(&self) -> Result<(), DbError> {
+        self.in_transaction.set(false);
+        try!(self.execute_sql("ROLLBACK", &[]));
+        Ok(())
     }
-    fn rollback(&self) {
-       let _ = self.execute_sql("ROLLBACK", &[]); 
+    fn in_transaction(&self) -> bool {
+        self.in_transaction.get()
+    }
+
+    fn statement_cache_stats(&self) -> Option<CacheStats> {
+        self.pool.as_ref().map(|conn| conn.statement_cache.borrow().stats())
+    }
+
+    /// a cached `SELECT *` fails with `cached plan must not change result type` after the table is altered,
+    /// rust-postgres can not remove its cached statements so the stale connections
+    /// are closed by the pool instead of reused
+    fn invalidate_statement_cache(&self) {
+        if let Some(ref conn) = self.pool {
+            conn.invalidate();
+        }
     }
 
-    /// return this list of options, supported features in the database
-    /// TODO: make this features version specific
-    /// http://www.postgresql.org/about/featurematrix/
-    /// writer CTE  >= 9.1
-    /// Inheritance  >= 9.0
-    /// JSON >= 9.2
-    /// JSONB >= 9.4
-    /// Returning >= 8.2
-    fn sql_options(&self) -> Vec<SqlOption> {
-        vec![
-            SqlOption::UsesNumberedParam,  // uses numbered parameters
-            SqlOption::SupportsReturningClause, // supports returning clause, feature
-            SqlOption::SupportsCTE,
-            SqlOption::SupportsInheritance,
-            SqlOption::UsesSchema,
-            SqlOption::ReturnMetaColumns,// whether to use the column names returned in a statement
-        ]
+    /// the parameter count in the wire protocol is a 16 bit integer
+    fn max_parameters(&self) -> usize {
+        65535
     }
 
 
@@ -537,7 +608,7 @@ impl Database for Postgres {
         debug!("SQL: \n{}", sql);
         debug!("param: {:?}", params);
         let conn = self.get_connection();
-        let stmt = try!(conn.prepare(sql));
+        let stmt = try!(self.prepare(conn, sql));
         let mut daos = vec![];
         let param = self.from_rust_type_tosql(params);
         let rows = try!(stmt.query(&param));
@@ -567,7 +638,8 @@ impl Database for Postgres {
         debug!("param: {:?}", params);
         let to_sql_types = self.from_rust_type_tosql(params);
         let conn = self.get_connection();
-        let result = try!(conn.execute(sql, &to_sql_types));
+        let stmt = try!(self.prepare(conn, sql));
+        let result = try!(stmt.execute(&to_sql_types));
         Ok(result as usize)
     }
 }
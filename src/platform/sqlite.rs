@@ -1,10 +1,14 @@
 use dao::Dao;
+use std::cell::Cell;
 use dao::Value;
 use database::{Database, DatabaseDev, BuildMode};
 use writer::SqlFrag;
-use database::SqlOption;
+use database::IdentifierQuoting;
+use dialect::parse_version;
+use dialect::{Dialect, SqliteDialect};
 use rusqlite::Connection as SqliteConnection;
 use rusqlite::types::ToSql;
+use rusqlite::types::Value as SqliteValue;
 use rusqlite::Row as SqliteRow;
 use table::{Table, Column, Foreign};
 use database::DatabaseDDL;
@@ -12,12 +16,11 @@ use database::DbError;
 use r2d2::PooledConnection;
 use r2d2_sqlite::SqliteConnectionManager;
 use regex::Regex;
+use platform::statement_cache::{self, CachingConnectionManager, CacheStats};
 use std::collections::BTreeMap;
 use dao::Type;
 use query::Operand;
-use query::Insert;
-use query::Update;
-use query::Delete;
+use query::Rewriter;
 
 
 pub fn establish_connection(db_path: &str) -> Result<SqliteConnection, DbError> {
@@ -26,16 +29,54 @@ pub fn establish_connection(db_path: &str) -> Result<SqliteConnection, DbError>
 }
 
 pub struct Sqlite {
-    pool: Option<PooledConnection<SqliteConnectionManager>>,
+    /// the connection keeps the tracking of its cached statements
+    pool: Option<PooledConnection<CachingConnectionManager<SqliteConnectionManager>>>,
+    dialect: SqliteDialect,
+    rewriters: Vec<Box<Rewriter>>,
+    /// a transaction is begun and not yet committed or rolled back
+    in_transaction: Cell<bool>,
 }
 
 impl Sqlite {
     pub fn new() -> Self {
-        Sqlite { pool: None }
+        Sqlite {
+            pool: None,
+            dialect: SqliteDialect::new(),
+            rewriters: vec![],
+            in_transaction: Cell::new(false),
+        }
+    }
+
+    /// the prepared statements are cached in the connection by rusqlite,
+    /// with the same capacity and eviction as the statement cache.
+    /// the cached statements are flushed when the schema was changed since they were prepared
+    pub fn with_pooled_connection(pool: PooledConnection<CachingConnectionManager<SqliteConnectionManager>>) -> Self {
+        pool.set_prepared_statement_cache_capacity(statement_cache::DEFAULT_CAPACITY);
+        if pool.is_stale() {
+            pool.flush_prepared_statement_cache();
+            pool.flushed();
+        }
+        let mut lite = Sqlite {
+            pool: Some(pool),
+            dialect: SqliteDialect::new(),
+            rewriters: vec![],
+            in_transaction: Cell::new(false),
+        };
+        // the RETURNING clause is only used when the linked sqlite supports it
+        if let Ok(version) = lite.version() {
+            lite.dialect.version = parse_version(&version);
+        }
+        lite
+    }
+
+    /// quote all the identifiers or only those that needs to be
+    pub fn set_identifier_quoting(&mut self, quoting: IdentifierQuoting) {
+        self.dialect.identifier_quoting = quoting;
     }
 
-    pub fn with_pooled_connection(pool: PooledConnection<SqliteConnectionManager>) -> Self {
-        Sqlite { pool: Some(pool) }
+    /// rewrite the queries executed in this connection before the sql is built
+    pub fn add_rewriter(&mut self, rewriter: Box<Rewriter>) {
+        self.rewriters.push(rewriter);
     }
 
     fn from_rust_type_tosql<'a>(&self, types: &'a [Value]) -> Vec<&'a ToSql> {
@@ -51,6 +92,13 @@ impl Sqlite {
         params
     }
 
+    /// keep track of the statement in the cache of the connection
+    fn touch_statement(&self, sql: &str) {
+        if let Some(ref conn) = self.pool {
+            conn.statement_cache.borrow_mut().touch(sql);
+        }
+    }
+
     pub fn get_connection(&self) -> &SqliteConnection {
         match self.pool {
             Some(ref pool) => &pool,
@@ -59,11 +107,15 @@ impl Sqlite {
     }
 
     /// convert a record of a row into rust type
+    /// the value in the storage class of sqlite, NULL is None
     fn from_sql_to_rust_type(&self, row: &SqliteRow, index: usize) -> Option<Value> {
         let value = row.get_checked(index as i32);
         match value {
-            Ok(value) => Some(Value::String(value)),
-            Err(_) => None,
+            Ok(SqliteValue::Integer(value)) => Some(Value::I64(value)),
+            Ok(SqliteValue::Real(value)) => Some(Value::F64(value)),
+            Ok(SqliteValue::Text(value)) => Some(Value::String(value)),
+            Ok(SqliteValue::Blob(value)) => Some(Value::VecU8(value)),
+            Ok(SqliteValue::Null) | Err(_) => None,
         }
     }
 
@@ -307,6 +359,11 @@ impl Sqlite {
 }
 
 impl Database for Sqlite {
+    /// the sql is generated by the SqliteDialect
+    fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
     fn version(&self) -> Result<String, DbError> {
         let sql = "SELECT sqlite_version() AS version";
         let dao = try!(self.execute_sql_with_one_return(sql, &vec![]));
@@ -327,44 +384,42 @@ impl Database for Sqlite {
         }
     }
 
-    fn begin(&self) {
-       let _ = self.execute_sql("BEGIN TRANSACTION", &[]); 
+    fn rewriters(&self) -> &[Box<Rewriter>] {
+        &self.rewriters
+    }
+
+    fn begin(&self) -> Result<(), DbError> {
+        try!(self.execute_sql("BEGIN TRANSACTION", &[]));
+        self.in_transaction.set(true);
+        Ok(())
     }
-    fn commit(&self) {
-       let _ = self.execute_sql("COMMIT TRANSACTION", &[]); 
+    fn commit(&self) -> Result<(), DbError> {
+        // a failed commit leaves the transaction open in sqlite, ie: when the database is busy
+        try!(self.execute_sql("COMMIT TRANSACTION", &[]));
+        self.in_transaction.set(false);
+        Ok(())
     }
-    fn rollback(&self) {
-       let _ = self.execute_sql("ROLLBACK TRANSACTION", &[]); 
+    fn rollback(&self) -> Result<(), DbError> {
+        self.in_transaction.set(false);
+        try!(self.execute_sql("ROLLBACK TRANSACTION", &[]));
+        Ok(())
     }
-
-    /// return this list of options, supported features in the database
-    fn sql_options(&self) -> Vec<SqlOption> {
-        vec![
-            SqlOption::UsesNumberedParam,  // uses numbered parameters
-            SqlOption::SupportsCTE,
-        ]
+    fn in_transaction(&self) -> bool {
+        self.in_transaction.get()
     }
 
-    fn insert(&self, query: &Insert) -> Result<Dao, DbError> {
-        let sql_frag = self.build_insert(query, &BuildMode::Standard);
-        match self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params) {
-            Ok(Some(result)) => Ok(result),
-            Ok(None) => Err(DbError::new("No result from insert")),
-            Err(e) => Err(e),
-        }
+    fn statement_cache_stats(&self) -> Option<CacheStats> {
+        self.pool.as_ref().map(|conn| conn.statement_cache.borrow().stats())
     }
-    fn update(&self, query: &Update) -> Result<Dao,DbError> {
-        let sql_frag = self.build_update(query, &BuildMode::Standard);
-        match self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params) {
-            Ok(Some(result)) => Ok(result),
-            Ok(None) => Err(DbError::new("No result from insert")),
-            Err(e) => Err(e),
+
+    /// the other connections of the pool flush their statements on their next checkout
+    fn invalidate_statement_cache(&self) {
+        if let Some(ref conn) = self.pool {
+            conn.invalidate();
+            conn.flush_prepared_statement_cache();
+            conn.flushed();
         }
     }
-    fn delete(&self, query: &Delete) -> Result<usize, DbError> {
-        let sql_frag = &self.build_delete(query, &BuildMode::Standard);
-        self.execute_sql(&sql_frag.sql, &sql_frag.params)
-    }
 
     /// sqlite does not return the columns mentioned in the query,
     /// you have to specify it yourself
@@ -374,7 +429,8 @@ impl Database for Sqlite {
         debug!("SQL: \n{}", sql);
         debug!("param: {:?}", params);
         let conn = self.get_connection();
-        let mut stmt = conn.prepare(sql).unwrap();
+        self.touch_statement(sql);
+        let mut stmt = try!(conn.prepare_cached(sql));
         let mut daos = vec![];
         let param = self.from_rust_type_tosql(params);
         let mut columns = vec![];
@@ -421,7 +477,9 @@ impl Database for Sqlite {
         debug!("param: {:?}", params);
         let to_sql_types = self.from_rust_type_tosql(params);
         let conn = self.get_connection();
-        let result = conn.execute(sql, &to_sql_types);
+        self.touch_statement(sql);
+        let mut stmt = try!(conn.prepare_cached(sql));
+        let result = stmt.execute(&to_sql_types);
         match result {
             Ok(result) => Ok(result as usize),
             Err(e) => Err(DbError::new(&format!("Something is wrong, {}", e))),
@@ -441,9 +499,9 @@ impl DatabaseDDL for Sqlite {
     fn build_create_table(&self, table: &Table) -> SqlFrag {
 
 
-        let mut w = SqlFrag::new(self.sql_options(), &BuildMode::Standard);
+        let mut w = self.dialect.sql_frag(&BuildMode::Standard);
         w.append("CREATE TABLE ");
-        w.append(&table.name);
+        w.identifier(&table.name);
         w.append("(");
         w.ln_tab();
         let mut do_comma = false;
@@ -454,7 +512,7 @@ impl DatabaseDDL for Sqlite {
             } else {
                 do_comma = true;
             }
-            w.append(&c.name);
+            w.identifier(&c.name);
             w.append(" ");
             let dt = self.rust_type_to_dbtype(&c.data_type);
             w.append(&dt);
@@ -471,11 +529,13 @@ impl DatabaseDDL for Sqlite {
                     do_comma = true;
                 }
                 w.ln_tab();
-                w.append("FOREIGN KEY");
-                w.append(&format!("({})", c.name));
-                w.append(" REFERENCES ");
-                w.append(&format!("{}", foreign.table));
-                w.append(&format!("({})", foreign.column));
+                w.append("FOREIGN KEY(");
+                w.identifier(&c.name);
+                w.append(") REFERENCES ");
+                w.identifier(&foreign.table);
+                w.append("(");
+                w.identifier(&foreign.column);
+                w.append(")");
             }
         }
         w.ln();
@@ -488,6 +548,7 @@ impl DatabaseDDL for Sqlite {
             Ok(_) => debug!("created table.."),
             Err(e) => panic!("table not created {}", e),
         }
+        self.invalidate_statement_cache();
     }
 
     fn rename_table(&self, _table: &Table, _new_tablename: String) {
@@ -582,6 +643,7 @@ impl DatabaseDev for Sqlite {
                         Some(not_null) => {
                             match not_null {
                                 &Value::String(ref not_null) => not_null.to_owned(),
+                                &Value::I64(not_null) => not_null.to_string(),
                                 _ => unreachable!(),
                             }
                         }
@@ -591,6 +653,7 @@ impl DatabaseDev for Sqlite {
                         Some(pk) => {
                             match pk {
                                 &Value::String(ref pk) => pk.to_owned(),
+                                &Value::I64(pk) => pk.to_string(),
                                 _ => unreachable!(),
                             }
                         }
@@ -0,0 +1,233 @@
+use std::collections::{HashSet, VecDeque};
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ops::Deref;
+use r2d2::ManageConnection;
+
+/// the default number of prepared statements kept per connection
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// the usage of the prepared statement cache
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// the number of statements currently in the cache
+    pub size: usize,
+    pub capacity: usize,
+}
+
+/// A bounded least recently used cache of the prepared statements, keyed by the sql text.
+/// The prepared statements themselves are kept by the driver on the pooled connection,
+/// this keeps track of which statements are cached and how often they are reused,
+/// it lives with the pooled connection in a CachedConnection
+#[derive(Debug)]
+pub struct StatementCache {
+    capacity: usize,
+    /// the sql of the cached statements, the most recently used is at the back
+    statements: VecDeque<String>,
+    /// the sql of the statements the driver keeps prepared until the connection is closed,
+    /// no more than the capacity of the cache
+    kept: HashSet<String>,
+    hits: usize,
+    misses: usize,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        StatementCache {
+            capacity: capacity,
+            statements: VecDeque::with_capacity(capacity),
+            kept: HashSet::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// mark the statement as the most recently used,
+    /// evicting the least recently used statement when the cache is full.
+    /// returns true when the statement is already cached
+    pub fn touch(&mut self, sql: &str) -> bool {
+        match self.statements.iter().position(|s| s == sql) {
+            Some(index) => {
+                let cached = self.statements.remove(index).unwrap();
+                self.statements.push_back(cached);
+                self.hits += 1;
+                true
+            }
+            None => {
+                self.misses += 1;
+                if self.capacity > 0 {
+                    if self.statements.len() == self.capacity {
+                        self.statements.pop_front();
+                    }
+                    self.statements.push_back(sql.to_owned());
+                }
+                false
+            }
+        }
+    }
+
+    /// for the drivers which can not close a cached statement on eviction,
+    /// returns true when the statement is or can still be kept prepared without exceeding the capacity
+    pub fn keep(&mut self, sql: &str) -> bool {
+        if self.kept.contains(sql) {
+            true
+        } else if self.kept.len() < self.capacity {
+            self.kept.insert(sql.to_owned());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// remove all the cached statements, called when the schema is changed
+    pub fn invalidate(&mut self) {
+        self.statements.clear();
+    }
+
+    /// the driver removed all of its prepared statements
+    pub fn flush(&mut self) {
+        self.statements.clear();
+        self.kept.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            size: self.statements.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// The connection manager of the pool which keeps a statement cache with each of the pooled connections,
+/// so the cached statements are still tracked on the next checkout of the connection.
+/// The statements of all the connections are stale once the schema is changed in one of them
+pub struct CachingConnectionManager<M> {
+    manager: M,
+    capacity: usize,
+    /// the schema version of the pool, incremented every time the statement caches are invalidated
+    generation: Arc<AtomicUsize>,
+    /// the driver can not remove its cached statements, so the stale connections are closed
+    discard_stale: bool,
+}
+
+impl<M> CachingConnectionManager<M> {
+    /// the stale connections are flushed when they are checked out
+    pub fn new(manager: M, capacity: usize) -> Self {
+        CachingConnectionManager {
+            manager: manager,
+            capacity: capacity,
+            generation: Arc::new(AtomicUsize::new(0)),
+            discard_stale: false,
+        }
+    }
+
+    /// the stale connections are closed instead of put back into the pool
+    pub fn discarding_stale(manager: M, capacity: usize) -> Self {
+        CachingConnectionManager { discard_stale: true, ..CachingConnectionManager::new(manager, capacity) }
+    }
+}
+
+impl<M: ManageConnection> ManageConnection for CachingConnectionManager<M> {
+    type Connection = CachedConnection<M::Connection>;
+    type Error = M::Error;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let conn = try!(self.manager.connect());
+        Ok(CachedConnection {
+            conn: conn,
+            statement_cache: RefCell::new(StatementCache::new(self.capacity)),
+            generation: Cell::new(self.generation.load(Ordering::SeqCst)),
+            pool_generation: self.generation.clone(),
+        })
+    }
+
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.manager.is_valid(&mut conn.conn)
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        (self.discard_stale && conn.is_stale()) || self.manager.has_broken(&mut conn.conn)
+    }
+}
+
+/// a pooled connection together with the tracking of its cached statements
+pub struct CachedConnection<C> {
+    conn: C,
+    pub statement_cache: RefCell<StatementCache>,
+    /// the schema version of the pool when the statements of this connection were cached
+    generation: Cell<usize>,
+    pool_generation: Arc<AtomicUsize>,
+}
+
+impl<C> CachedConnection<C> {
+    /// the schema was changed by any of the connections of the pool after the statements were cached,
+    /// the cached statements of this connection must not be used anymore
+    pub fn is_stale(&self) -> bool {
+        self.generation.get() != self.pool_generation.load(Ordering::SeqCst)
+    }
+
+    /// mark the cached statements of all the connections of the pool as stale
+    pub fn invalidate(&self) {
+        self.statement_cache.borrow_mut().invalidate();
+        self.pool_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// call after the driver removed the cached statements of this connection
+    pub fn flushed(&self) {
+        self.statement_cache.borrow_mut().flush();
+        self.generation.set(self.pool_generation.load(Ordering::SeqCst));
+    }
+}
+
+impl<C> Deref for CachedConnection<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.conn
+    }
+}
+
+#[test]
+fn test_least_recently_used_is_evicted() {
+    let mut cache = StatementCache::new(2);
+    assert!(!cache.touch("SELECT 1"));
+    assert!(!cache.touch("SELECT 2"));
+    assert!(cache.touch("SELECT 1"));
+    assert!(!cache.touch("SELECT 3"));
+    assert!(!cache.touch("SELECT 2"));
+    assert!(cache.touch("SELECT 3"));
+    assert_eq!(cache.stats(),
+               CacheStats {
+                   hits: 2,
+                   misses: 4,
+                   size: 2,
+                   capacity: 2,
+               });
+    cache.invalidate();
+    assert_eq!(cache.stats().size, 0);
+}
+
+#[test]
+fn test_kept_statements_are_bounded() {
+    let mut cache = StatementCache::new(2);
+    assert!(cache.keep("SELECT 1"));
+    assert!(cache.keep("SELECT 2"));
+    assert!(!cache.keep("SELECT 3"));
+    assert!(cache.keep("SELECT 1"));
+    cache.invalidate();
+    assert!(!cache.keep("SELECT 3"));
+    cache.flush();
+    assert!(cache.keep("SELECT 3"));
+}
@@ -63,6 +63,7 @@ impl QueryBuilder {
                 fields: vec![],
                 query: query,
                 is_recursive: false,
+                recursive_query: None,
             };
         self.query.declared_query.push(declared_query);
         self
@@ -74,6 +75,7 @@ impl QueryBuilder {
                 fields: vec![],
                 query: query,
                 is_recursive: true,
+                recursive_query: None,
             };
         self.query.declared_query.push(declared_query);
         self
@@ -250,6 +252,39 @@ impl QueryBuilder {
         self
     }
 
+    /// lock the selected rows, ie: `SELECT().FROM(&job).FOR_UPDATE().SKIP_LOCKED()`
+    pub fn FOR_UPDATE(&mut self) -> &mut Self {
+        self.query.for_update();
+        self
+    }
+
+    pub fn FOR_SHARE(&mut self) -> &mut Self {
+        self.query.for_share();
+        self
+    }
+
+    /// lock only the rows of this table, after FOR_UPDATE or FOR_SHARE
+    pub fn OF(&mut self, table: &ToTableName) -> &mut Self {
+        if let Some(ref mut lock) = self.query.lock {
+            lock.of(table);
+        }
+        self
+    }
+
+    pub fn NOWAIT(&mut self) -> &mut Self {
+        if let Some(ref mut lock) = self.query.lock {
+            lock.nowait();
+        }
+        self
+    }
+
+    pub fn SKIP_LOCKED(&mut self) -> &mut Self {
+        if let Some(ref mut lock) = self.query.lock {
+            lock.skip_locked();
+        }
+        self
+    }
+
 
     /// build the query only, not executed, useful when debugging
     pub fn build(&mut self, db: &Database) -> SqlFrag {
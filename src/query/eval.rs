@@ -0,0 +1,416 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use regex::Regex;
+use dao::{Dao, Value};
+use database::DbError;
+use query::{Select, Filter, Condition, Equality, Connector, Operand};
+use query::{Order, Direction, NullsWhere, ColumnName};
+use query::source::QuerySource;
+
+/// evaluate the filter against the record in memory, without a database.
+/// A column that is missing in the record is NULL,
+/// the comparisons involving NULL are unknown, as in the three-valued logic of sql
+impl Filter {
+    /// `Some(true)` when the record matches, `Some(false)` when it does not,
+    /// `None` when it is unknown because of the NULLs.
+    /// The sub filters are evaluated as they are written in the sql, AND before OR
+    pub fn evaluate(&self, dao: &Dao) -> Result<Option<bool>, DbError> {
+        self.evaluate_with(dao, &mut Patterns::new())
+    }
+
+    /// only the records where the filter is true are matched, not the unknown
+    pub fn matches(&self, dao: &Dao) -> Result<bool, DbError> {
+        Ok(try!(self.evaluate(dao)) == Some(true))
+    }
+
+    /// evaluate with the patterns already compiled for the previous records
+    fn evaluate_with(&self, dao: &Dao, patterns: &mut Patterns) -> Result<Option<bool>, DbError> {
+        let mut disjuncts = vec![];
+        let mut conjunct = try!(evaluate_condition(&self.condition, dao, patterns));
+        for sub_filter in &self.sub_filters {
+            let truth = try!(sub_filter.evaluate_with(dao, patterns));
+            match sub_filter.connector {
+                Connector::And => conjunct = and(conjunct, truth),
+                Connector::Or => {
+                    disjuncts.push(conjunct);
+                    conjunct = truth;
+                }
+            }
+        }
+        Ok(disjuncts.into_iter().fold(conjunct, or))
+    }
+}
+
+/// the compiled regex of the LIKE, SIMILAR TO and regular expression patterns,
+/// keyed by the translated pattern so each one is compiled once for all of the records
+type Patterns = HashMap<String, Regex>;
+
+impl Select {
+    /// select from the records in memory, the FROM is ignored since the records are the source.
+    /// Supports the filters, ORDER BY, LIMIT, OFFSET, DISTINCT and the projection of columns,
+    /// the joins, grouping, functions and subqueries are not supported
+    pub fn evaluate(&self, daos: &[Dao]) -> Result<Vec<Dao>, DbError> {
+        if !self.joins.is_empty() || !self.group_by.is_empty() || !self.having.is_empty() ||
+           !self.distinct_on_columns.is_empty() || !self.declared_query.is_empty() {
+            return Err(DbError::new("Only the filters, ordering, range and columns of the select \
+                                     can be evaluated in memory"));
+        }
+        let mut matched = vec![];
+        let mut patterns = Patterns::new();
+        for dao in daos {
+            let mut is_match = true;
+            for filter in &self.filters {
+                if try!(filter.evaluate_with(dao, &mut patterns)) != Some(true) {
+                    is_match = false;
+                    break;
+                }
+            }
+            if is_match {
+                matched.push(dao.to_owned());
+            }
+        }
+        for order in &self.order_by {
+            try!(column_of(&order.operand));
+        }
+        matched.sort_by(|a, b| compare_daos(&self.order_by, a, b));
+        let mut rows = vec![];
+        for dao in &matched {
+            let row = try!(self.project(dao));
+            if !self.distinct || !rows.contains(&row) {
+                rows.push(row);
+            }
+        }
+        let offset = self.range.offset.unwrap_or(0);
+        let limit = self.range.limit.unwrap_or(rows.len());
+        Ok(rows.into_iter().skip(offset).take(limit).collect())
+    }
+
+    /// the enumerated columns of the record, all of the columns when none or `*` is enumerated
+    fn project(&self, dao: &Dao) -> Result<Dao, DbError> {
+        if self.enumerated_fields.is_empty() {
+            return Ok(dao.to_owned());
+        }
+        let mut row = Dao::new();
+        for field in &self.enumerated_fields {
+            match field.operand {
+                Operand::ColumnName(ref column) if column.column == "*" => {
+                    row.extend(dao.iter().map(|(k, v)| (k.to_owned(), v.to_owned())));
+                }
+                Operand::ColumnName(ref column) => {
+                    let name = field.name.clone().unwrap_or(column.column.to_owned());
+                    if let Some(value) = lookup(dao, column) {
+                        row.insert(name, value.to_owned());
+                    }
+                }
+                Operand::Value(ref value) => {
+                    match field.name {
+                        Some(ref name) => {
+                            row.insert(name.to_owned(), value.to_owned());
+                        }
+                        None => return Err(DbError::new("A value in the columns needs a name")),
+                    }
+                }
+                _ => return Err(DbError::new("Only columns and values can be selected in memory")),
+            }
+        }
+        Ok(row)
+    }
+}
+
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+fn not(a: Option<bool>) -> Option<bool> {
+    a.map(|a| !a)
+}
+
+/// the value of the column, looked up by the complete name `table.column` first
+fn lookup<'a>(dao: &'a Dao, column: &ColumnName) -> Option<&'a Value> {
+    dao.get(&column.complete_name()).or_else(|| dao.get(&column.column))
+}
+
+fn column_of(operand: &Operand) -> Result<&ColumnName, DbError> {
+    match *operand {
+        Operand::ColumnName(ref column) => Ok(column),
+        _ => Err(DbError::new("Only the columns can be ordered in memory")),
+    }
+}
+
+/// the value of the operand, `None` is NULL
+fn value_of<'a>(operand: &'a Operand, dao: &'a Dao) -> Result<Option<&'a Value>, DbError> {
+    match *operand {
+        Operand::ColumnName(ref column) => Ok(lookup(dao, column)),
+        Operand::Value(ref value) => Ok(Some(value)),
+        Operand::Null => Ok(None),
+        Operand::QuerySource(QuerySource::Query(_)) => {
+            Err(DbError::new("Subqueries can not be evaluated in memory"))
+        }
+        _ => Err(DbError::new("Only columns and values can be compared in memory")),
+    }
+}
+
+/// the values of the list in `IN (..)`, `BETWEEN`, `ANY (..)`
+fn values_of<'a>(operand: &'a Operand, dao: &'a Dao) -> Result<Vec<Option<&'a Value>>, DbError> {
+    match *operand {
+        Operand::Vec(ref operands) => {
+            let mut values = vec![];
+            for operand in operands {
+                values.push(try!(value_of(operand, dao)));
+            }
+            Ok(values)
+        }
+        _ => Ok(vec![try!(value_of(operand, dao))]),
+    }
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match *value {
+        Value::I8(v) => Some(v as i64),
+        Value::I16(v) => Some(v as i64),
+        Value::I32(v) => Some(v as i64),
+        Value::I64(v) => Some(v),
+        Value::U8(v) => Some(v as i64),
+        Value::U16(v) => Some(v as i64),
+        Value::U32(v) => Some(v as i64),
+        Value::U64(v) if v <= ::std::i64::MAX as u64 => Some(v as i64),
+        _ => None,
+    }
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match *value {
+        Value::F32(v) => Some(v as f64),
+        Value::F64(v) => Some(v),
+        Value::U64(v) => Some(v as f64),
+        _ => integer(value).map(|v| v as f64),
+    }
+}
+
+/// the numbers of any width are compared with each other,
+/// the values of different types are not comparable and the comparison is unknown,
+/// json can only be equal or not
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (integer(a), integer(b)) {
+        return Some(a.cmp(&b));
+    }
+    if let (Some(a), Some(b)) = (float(a), float(b)) {
+        return a.partial_cmp(&b);
+    }
+    match (a, b) {
+        (&Value::Bool(ref a), &Value::Bool(ref b)) => Some(a.cmp(b)),
+        (&Value::String(ref a), &Value::String(ref b)) => Some(a.cmp(b)),
+        (&Value::VecU8(ref a), &Value::VecU8(ref b)) => Some(a.cmp(b)),
+        (&Value::Uuid(ref a), &Value::Uuid(ref b)) => Some(a.as_bytes().cmp(b.as_bytes())),
+        (&Value::DateTime(ref a), &Value::DateTime(ref b)) => Some(a.cmp(b)),
+        (&Value::Json(ref a), &Value::Json(ref b)) if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// the comparison of the values, unknown when either is NULL
+fn compare_with<F>(a: Option<&Value>, b: Option<&Value>, test: F) -> Option<bool>
+    where F: Fn(Ordering) -> bool
+{
+    match (a, b) {
+        (Some(a), Some(b)) => compare(a, b).map(test),
+        _ => None,
+    }
+}
+
+fn evaluate_equality(equality: &Equality, a: Option<&Value>, b: Option<&Value>) -> Result<Option<bool>, DbError> {
+    let truth = match *equality {
+        Equality::EQ => compare_with(a, b, |o| o == Ordering::Equal),
+        Equality::NEQ => compare_with(a, b, |o| o != Ordering::Equal),
+        Equality::LT => compare_with(a, b, |o| o == Ordering::Less),
+        Equality::LTE => compare_with(a, b, |o| o != Ordering::Greater),
+        Equality::GT => compare_with(a, b, |o| o == Ordering::Greater),
+        Equality::GTE => compare_with(a, b, |o| o != Ordering::Less),
+        _ => return Err(DbError::new(&format!("{:?} can not be quantified", equality))),
+    };
+    Ok(truth)
+}
+
+fn evaluate_condition(condition: &Condition, dao: &Dao, patterns: &mut Patterns) -> Result<Option<bool>, DbError> {
+    let equality = &condition.equality;
+    let left = try!(value_of(&condition.left, dao));
+    let truth = match *equality {
+        Equality::IS_NULL => Some(left.is_none()),
+        Equality::IS_NOT_NULL => Some(left.is_some()),
+        Equality::IN | Equality::NOT_IN => {
+            let mut truth = Some(false);
+            for value in try!(values_of(&condition.right, dao)) {
+                truth = or(truth, compare_with(left, value, |o| o == Ordering::Equal));
+            }
+            match *equality {
+                Equality::NOT_IN => not(truth),
+                _ => truth,
+            }
+        }
+        Equality::BETWEEN | Equality::NOT_BETWEEN => {
+            let bounds = try!(values_of(&condition.right, dao));
+            if bounds.len() != 2 {
+                return Err(DbError::new("BETWEEN expects the low and the high bound"));
+            }
+            let truth = and(compare_with(left, bounds[0], |o| o != Ordering::Less),
+                            compare_with(left, bounds[1], |o| o != Ordering::Greater));
+            match *equality {
+                Equality::NOT_BETWEEN => not(truth),
+                _ => truth,
+            }
+        }
+        Equality::IS_DISTINCT_FROM | Equality::IS_NOT_DISTINCT_FROM => {
+            let right = try!(value_of(&condition.right, dao));
+            let same = match (left, right) {
+                (None, None) => true,
+                (Some(a), Some(b)) => compare(a, b) == Some(Ordering::Equal),
+                _ => false,
+            };
+            match *equality {
+                Equality::IS_DISTINCT_FROM => Some(!same),
+                _ => Some(same),
+            }
+        }
+        Equality::LIKE | Equality::NOT_LIKE | Equality::ILIKE | Equality::NOT_ILIKE |
+        Equality::SIMILAR_TO | Equality::NOT_SIMILAR_TO | Equality::REGEX | Equality::NOT_REGEX => {
+            let right = try!(value_of(&condition.right, dao));
+            let truth = match (left, right) {
+                (Some(&Value::String(ref text)), Some(&Value::String(ref pattern))) => {
+                    let regex = match *equality {
+                        Equality::LIKE | Equality::NOT_LIKE => like_regex(pattern, false),
+                        Equality::ILIKE | Equality::NOT_ILIKE => like_regex(pattern, true),
+                        Equality::SIMILAR_TO | Equality::NOT_SIMILAR_TO => similar_regex(pattern),
+                        _ => pattern.to_owned(),
+                    };
+                    if !patterns.contains_key(&regex) {
+                        let compiled = try!(Regex::new(&regex));
+                        patterns.insert(regex.to_owned(), compiled);
+                    }
+                    Some(patterns[&regex].is_match(text))
+                }
+                (Some(_), Some(_)) => return Err(DbError::new("Only strings can be matched with a pattern")),
+                _ => None,
+            };
+            match *equality {
+                Equality::NOT_LIKE | Equality::NOT_ILIKE | Equality::NOT_SIMILAR_TO |
+                Equality::NOT_REGEX => not(truth),
+                _ => truth,
+            }
+        }
+        Equality::ANY(ref quantified) | Equality::ALL(ref quantified) => {
+            let is_any = match *equality {
+                Equality::ANY(_) => true,
+                _ => false,
+            };
+            let mut truth = Some(!is_any);
+            for value in try!(values_of(&condition.right, dao)) {
+                let each = try!(evaluate_equality(quantified, left, value));
+                truth = if is_any { or(truth, each) } else { and(truth, each) };
+            }
+            truth
+        }
+        Equality::EXISTS | Equality::NOT_EXISTS => {
+            return Err(DbError::new("Subqueries can not be evaluated in memory"))
+        }
+        Equality::MATCH => {
+            return Err(DbError::new("Full text search can not be evaluated in memory"))
+        }
+        _ => {
+            let right = try!(value_of(&condition.right, dao));
+            try!(evaluate_equality(equality, left, right))
+        }
+    };
+    Ok(truth)
+}
+
+/// `%` is any number of characters, `_` is exactly 1 character
+fn like_regex(pattern: &str, case_insensitive: bool) -> String {
+    let mut regex = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&::regex::quote(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// SIMILAR TO is a regular expression that matches the whole string,
+/// with the `%` and `_` wildcards of LIKE.
+/// Only `| * + ? { } ( ) [ ]` are regex operators, the other characters such as `.`
+/// are literal, and `\` escapes the character that follows it
+fn similar_regex(pattern: &str) -> String {
+    let mut regex = String::from("(?s)^(?:");
+    let mut in_bracket = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next().unwrap_or('\\');
+                regex.push_str(&::regex::quote(&escaped.to_string()));
+            }
+            '[' if !in_bracket => {
+                in_bracket = true;
+                regex.push(c);
+            }
+            ']' if in_bracket => {
+                in_bracket = false;
+                regex.push(c);
+            }
+            c if in_bracket => regex.push(c),
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            '|' | '*' | '+' | '?' | '{' | '}' | '(' | ')' => regex.push(c),
+            c => regex.push_str(&::regex::quote(&c.to_string())),
+        }
+    }
+    regex.push_str(")$");
+    regex
+}
+
+/// NULLs are larger than the values, last in ascending and first in descending order,
+/// unless NULLS FIRST or NULLS LAST is specified
+fn compare_daos(order_by: &[Order], a: &Dao, b: &Dao) -> Ordering {
+    for order in order_by {
+        let column = match order.operand {
+            Operand::ColumnName(ref column) => column,
+            _ => continue,
+        };
+        let descending = match order.direction {
+            Some(Direction::DESC) => true,
+            _ => false,
+        };
+        let nulls_first = match order.nulls_where {
+            Some(NullsWhere::FIRST) => true,
+            Some(NullsWhere::LAST) => false,
+            None => descending,
+        };
+        let ordering = match (lookup(a, column), lookup(b, column)) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => if nulls_first { Ordering::Less } else { Ordering::Greater },
+            (Some(_), None) => if nulls_first { Ordering::Greater } else { Ordering::Less },
+            (Some(a), Some(b)) => {
+                let ordering = compare(a, b).unwrap_or(Ordering::Equal);
+                if descending { ordering.reverse() } else { ordering }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
@@ -1,5 +1,5 @@
 use query::Operand;
-use dao::ToValue;
+use dao::{Value, ToValue};
 use query::{ToColumnName};
 use query::operand::ToOperand;
 
@@ -38,6 +38,37 @@ pub enum Equality {
     ILIKE, // add ILIKE
     IS_NOT_NULL, // NOT_NULL,
     IS_NULL, // IS_NULL,
+    BETWEEN, // the right operand is a Vec of the low and high bound
+    NOT_BETWEEN,
+    NOT_LIKE,
+    NOT_ILIKE,
+    EXISTS, // the subquery is the left operand
+    NOT_EXISTS,
+    ANY(Box<Equality>), // ie: = ANY, > ANY
+    ALL(Box<Equality>), // ie: != ALL, < ALL
+    IS_DISTINCT_FROM,
+    IS_NOT_DISTINCT_FROM,
+    SIMILAR_TO,
+    NOT_SIMILAR_TO,
+    REGEX, // ~ in postgresql, REGEXP in mysql
+    NOT_REGEX,
+    MATCH, // full text search of the words, the right operand is the text searched
+}
+
+impl Equality {
+    /// the sql operator of the simple comparisons,
+    /// used when the comparison is quantified with ANY/ALL
+    pub fn operator(&self) -> Option<&'static str> {
+        match *self {
+            Equality::EQ => Some("="),
+            Equality::NEQ => Some("!="),
+            Equality::LT => Some("<"),
+            Equality::LTE => Some("<="),
+            Equality::GT => Some(">"),
+            Equality::GTE => Some(">="),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -93,6 +124,29 @@ pub trait HasEquality {
     fn IS_NOT_NULL(&self) -> Filter;
     fn IN(&self, to_operand: &ToOperand) -> Filter;
     fn NOT_IN(&self, to_operand: &ToOperand) -> Filter;
+    fn BETWEEN(&self, low: &ToOperand, high: &ToOperand) -> Filter;
+    fn NOT_BETWEEN(&self, low: &ToOperand, high: &ToOperand) -> Filter;
+    fn NOT_LIKE(&self, to_value: &ToValue) -> Filter;
+    fn NOT_ILIKE(&self, to_value: &ToValue) -> Filter;
+    /// the operand is expected to be a query, ie: `select.EXISTS()`
+    fn EXISTS(&self) -> Filter;
+    fn NOT_EXISTS(&self) -> Filter;
+    /// `column = ANY (subquery)`, the equality is the comparison to be done
+    fn ANY(&self, equality: Equality, to_operand: &ToOperand) -> Filter;
+    /// `column != ALL (subquery)`, the equality is the comparison to be done
+    fn ALL(&self, equality: Equality, to_operand: &ToOperand) -> Filter;
+    fn IS_DISTINCT_FROM(&self, to_operand: &ToOperand) -> Filter;
+    fn IS_NOT_DISTINCT_FROM(&self, to_operand: &ToOperand) -> Filter;
+    fn SIMILAR_TO(&self, to_value: &ToValue) -> Filter;
+    fn NOT_SIMILAR_TO(&self, to_value: &ToValue) -> Filter;
+    fn REGEX(&self, to_value: &ToValue) -> Filter;
+    fn NOT_REGEX(&self, to_value: &ToValue) -> Filter;
+    /// full text search of the words in the text, all of the words should match,
+    /// `to_tsvector(config, column) @@ plainto_tsquery(config, text)` in postgresql,
+    /// where config is the text search configuration, ie: `"english"`,
+    /// `column MATCH text` of the fts5 table in sqlite,
+    /// `MATCH (column) AGAINST (text)` in mysql, which needs a FULLTEXT index on the column
+    fn MATCH(&self, config: &str, text: &ToValue) -> Filter;
 }
 
 macro_rules! fn_has_equality_operand{
@@ -146,6 +200,58 @@ macro_rules! fn_has_equality_nulls{
 	)
 }
 
+macro_rules! fn_has_equality_range{
+	($f:ident, $eq: expr) => (
+		fn $f(&self, low: &ToOperand, high: &ToOperand)->Filter{
+			let cond = Condition{
+				left: self.to_operand(), 
+				equality: $eq,
+				right: Operand::Vec(vec![low.to_operand(), high.to_operand()]),
+			};
+			Filter{
+				connector: Connector::And,
+				condition:cond,
+				sub_filters: vec![]
+			}
+		}
+	)
+}
+
+macro_rules! fn_has_equality_text_search{
+	($f:ident, $eq: expr) => (
+		fn $f(&self, config: &str, text: &ToValue)->Filter{
+			let cond = Condition{
+				left: self.to_operand(), 
+				equality: $eq,
+				right: Operand::Vec(vec![Operand::Value(Value::String(config.to_owned())),
+				                         Operand::Value(text.to_db_type())]),
+			};
+			Filter{
+				connector: Connector::And,
+				condition:cond,
+				sub_filters: vec![]
+			}
+		}
+	)
+}
+
+macro_rules! fn_has_equality_quantified{
+	($f:ident, $eq: path) => (
+		fn $f(&self, equality: Equality, to_operand: &ToOperand)->Filter{
+			let cond = Condition{
+				left: self.to_operand(), 
+				equality: $eq(Box::new(equality)),
+				right: to_operand.to_operand(),
+			};
+			Filter{
+				connector: Connector::And,
+				condition:cond,
+				sub_filters: vec![]
+			}
+		}
+	)
+}
+
 /// implementation of HasEquality for objects that can yield Operand
 impl<T> HasEquality for T
     where T: ToOperand
@@ -162,4 +268,19 @@ impl<T> HasEquality for T
     fn_has_equality_to_value!(ILIKE, Equality::ILIKE);
     fn_has_equality_nulls!(IS_NULL, Equality::IS_NULL);
     fn_has_equality_nulls!(IS_NOT_NULL, Equality::IS_NOT_NULL);
+    fn_has_equality_range!(BETWEEN, Equality::BETWEEN);
+    fn_has_equality_range!(NOT_BETWEEN, Equality::NOT_BETWEEN);
+    fn_has_equality_to_value!(NOT_LIKE, Equality::NOT_LIKE);
+    fn_has_equality_to_value!(NOT_ILIKE, Equality::NOT_ILIKE);
+    fn_has_equality_nulls!(EXISTS, Equality::EXISTS);
+    fn_has_equality_nulls!(NOT_EXISTS, Equality::NOT_EXISTS);
+    fn_has_equality_quantified!(ANY, Equality::ANY);
+    fn_has_equality_quantified!(ALL, Equality::ALL);
+    fn_has_equality_operand!(IS_DISTINCT_FROM, Equality::IS_DISTINCT_FROM);
+    fn_has_equality_operand!(IS_NOT_DISTINCT_FROM, Equality::IS_NOT_DISTINCT_FROM);
+    fn_has_equality_to_value!(SIMILAR_TO, Equality::SIMILAR_TO);
+    fn_has_equality_to_value!(NOT_SIMILAR_TO, Equality::NOT_SIMILAR_TO);
+    fn_has_equality_to_value!(REGEX, Equality::REGEX);
+    fn_has_equality_to_value!(NOT_REGEX, Equality::NOT_REGEX);
+    fn_has_equality_text_search!(MATCH, Equality::MATCH);
 }
@@ -0,0 +1,252 @@
+use query::{Query, Select, Insert, Update, Delete, DeclaredQuery};
+use query::{Filter, Condition, Operand, Field, Join, Order};
+use query::{ColumnName, TableName, Function};
+use query::{OnConflict, ConflictAction};
+use query::source::{SourceField, QuerySource};
+use query::query::Data;
+use database::DbError;
+
+/// rewrite the parts of a query, each method returns the rewritten part,
+/// by default the parts are walked into and returned unchanged.
+/// Override a method to rewrite that part, and call the matching `walk_*` function
+/// to continue rewriting the parts inside it.
+///
+/// ```rust,ignore
+/// struct Shadow;
+/// impl Fold for Shadow {
+///     fn fold_table_name(&mut self, table_name: TableName) -> TableName {
+///         TableName { name: format!("{}_shadow", table_name.name), ..table_name }
+///     }
+/// }
+/// let query = Shadow.fold_query(query);
+/// ```
+pub trait Fold {
+    fn fold_query(&mut self, query: Query) -> Query {
+        walk_query(self, query)
+    }
+
+    fn fold_select(&mut self, select: Select) -> Select {
+        walk_select(self, select)
+    }
+
+    fn fold_insert(&mut self, insert: Insert) -> Insert {
+        walk_insert(self, insert)
+    }
+
+    fn fold_update(&mut self, update: Update) -> Update {
+        walk_update(self, update)
+    }
+
+    fn fold_delete(&mut self, delete: Delete) -> Delete {
+        walk_delete(self, delete)
+    }
+
+    fn fold_filter(&mut self, filter: Filter) -> Filter {
+        walk_filter(self, filter)
+    }
+
+    fn fold_operand(&mut self, operand: Operand) -> Operand {
+        walk_operand(self, operand)
+    }
+
+    fn fold_join(&mut self, join: Join) -> Join {
+        walk_join(self, join)
+    }
+
+    fn fold_source_field(&mut self, source_field: SourceField) -> SourceField {
+        walk_source_field(self, source_field)
+    }
+
+    fn fold_order(&mut self, order: Order) -> Order {
+        walk_order(self, order)
+    }
+
+    /// the tables in the FROM, the joins, and the table that is inserted to, updated or deleted from
+    fn fold_table_name(&mut self, table_name: TableName) -> TableName {
+        table_name
+    }
+
+    fn fold_column_name(&mut self, column_name: ColumnName) -> ColumnName {
+        column_name
+    }
+}
+
+/// a rewrite applied to every query executed in the database, before the sql is built,
+/// registered with `add_rewriter` of the database platform.
+/// The rewriters are applied in the order they are added.
+pub trait Rewriter {
+    /// the rewritten query, or an error to refuse the execution of the query
+    fn rewrite(&self, query: Query) -> Result<Query, DbError>;
+}
+
+/// closures can be used as rewriters,
+/// ie: `db.add_rewriter(Box::new(|query: Query| Ok(Tenant(7).fold_query(query))))`
+impl<F> Rewriter for F
+    where F: Fn(Query) -> Result<Query, DbError>
+{
+    fn rewrite(&self, query: Query) -> Result<Query, DbError> {
+        self(query)
+    }
+}
+
+pub fn walk_query<F: Fold + ?Sized>(folder: &mut F, query: Query) -> Query {
+    match query {
+        Query::Select(select) => Query::Select(folder.fold_select(select)),
+        Query::Insert(insert) => Query::Insert(folder.fold_insert(insert)),
+        Query::Update(update) => Query::Update(folder.fold_update(update)),
+        Query::Delete(delete) => Query::Delete(folder.fold_delete(delete)),
+    }
+}
+
+pub fn walk_select<F: Fold + ?Sized>(folder: &mut F, select: Select) -> Select {
+    let mut select = select;
+    select.declared_query = select.declared_query
+        .into_iter()
+        .map(|declared| {
+            DeclaredQuery {
+                query: folder.fold_select(declared.query),
+                recursive_query: declared.recursive_query.map(|q| folder.fold_select(q)),
+                ..declared
+            }
+        })
+        .collect();
+    select.enumerated_fields = select.enumerated_fields
+        .into_iter()
+        .map(|field| {
+            Field {
+                operand: folder.fold_operand(field.operand),
+                name: field.name,
+            }
+        })
+        .collect();
+    select.from = select.from.into_iter().map(|s| folder.fold_source_field(s)).collect();
+    select.joins = select.joins.into_iter().map(|j| folder.fold_join(j)).collect();
+    select.filters = select.filters.into_iter().map(|f| folder.fold_filter(f)).collect();
+    select.group_by = select.group_by.into_iter().map(|o| folder.fold_operand(o)).collect();
+    select.having = select.having.into_iter().map(|f| folder.fold_filter(f)).collect();
+    select.order_by = select.order_by.into_iter().map(|o| folder.fold_order(o)).collect();
+    select
+}
+
+pub fn walk_insert<F: Fold + ?Sized>(folder: &mut F, insert: Insert) -> Insert {
+    let mut insert = insert;
+    insert.into = folder.fold_table_name(insert.into);
+    insert.columns = insert.columns.into_iter().map(|c| folder.fold_column_name(c)).collect();
+    insert.data = match insert.data {
+        Data::Values(values) => {
+            Data::Values(values.into_iter().map(|v| folder.fold_operand(v)).collect())
+        }
+        Data::Rows(rows) => {
+            Data::Rows(rows.into_iter()
+                .map(|row| row.into_iter().map(|v| folder.fold_operand(v)).collect())
+                .collect())
+        }
+        Data::Query(select) => Data::Query(folder.fold_select(select)),
+    };
+    insert.on_conflict = insert.on_conflict.map(|on_conflict| {
+        let action = match on_conflict.action {
+            ConflictAction::DoNothing => ConflictAction::DoNothing,
+            ConflictAction::DoUpdate { columns, values } => {
+                ConflictAction::DoUpdate {
+                    columns: columns.into_iter().map(|c| folder.fold_column_name(c)).collect(),
+                    values: values.into_iter().map(|v| folder.fold_operand(v)).collect(),
+                }
+            }
+        };
+        OnConflict {
+            columns: on_conflict.columns.into_iter().map(|c| folder.fold_column_name(c)).collect(),
+            action: action,
+        }
+    });
+    insert.return_columns = insert.return_columns
+        .into_iter()
+        .map(|c| folder.fold_column_name(c))
+        .collect();
+    insert
+}
+
+pub fn walk_update<F: Fold + ?Sized>(folder: &mut F, update: Update) -> Update {
+    let mut update = update;
+    update.table = folder.fold_table_name(update.table);
+    update.columns = update.columns.into_iter().map(|c| folder.fold_column_name(c)).collect();
+    update.values = update.values.into_iter().map(|v| folder.fold_operand(v)).collect();
+    update.joins = update.joins.into_iter().map(|j| folder.fold_join(j)).collect();
+    update.filters = update.filters.into_iter().map(|f| folder.fold_filter(f)).collect();
+    update.return_columns = update.return_columns
+        .into_iter()
+        .map(|c| folder.fold_column_name(c))
+        .collect();
+    update
+}
+
+pub fn walk_delete<F: Fold + ?Sized>(folder: &mut F, delete: Delete) -> Delete {
+    let mut delete = delete;
+    delete.from_table = folder.fold_table_name(delete.from_table);
+    delete.joins = delete.joins.into_iter().map(|j| folder.fold_join(j)).collect();
+    delete.filters = delete.filters.into_iter().map(|f| folder.fold_filter(f)).collect();
+    delete.return_columns = delete.return_columns
+        .into_iter()
+        .map(|c| folder.fold_column_name(c))
+        .collect();
+    delete
+}
+
+pub fn walk_filter<F: Fold + ?Sized>(folder: &mut F, filter: Filter) -> Filter {
+    Filter {
+        connector: filter.connector,
+        condition: Condition {
+            left: folder.fold_operand(filter.condition.left),
+            equality: filter.condition.equality,
+            right: folder.fold_operand(filter.condition.right),
+        },
+        sub_filters: filter.sub_filters.into_iter().map(|f| folder.fold_filter(f)).collect(),
+    }
+}
+
+pub fn walk_operand<F: Fold + ?Sized>(folder: &mut F, operand: Operand) -> Operand {
+    match operand {
+        Operand::ColumnName(column_name) => Operand::ColumnName(folder.fold_column_name(column_name)),
+        Operand::QuerySource(query_source) => {
+            Operand::QuerySource(walk_query_source(folder, query_source))
+        }
+        Operand::Vec(operands) => {
+            Operand::Vec(operands.into_iter().map(|o| folder.fold_operand(o)).collect())
+        }
+        operand => operand,
+    }
+}
+
+pub fn walk_join<F: Fold + ?Sized>(folder: &mut F, join: Join) -> Join {
+    let mut join = join;
+    join.source = folder.fold_source_field(join.source);
+    join.on = join.on.map(|on| folder.fold_filter(on));
+    join
+}
+
+pub fn walk_source_field<F: Fold + ?Sized>(folder: &mut F, source_field: SourceField) -> SourceField {
+    SourceField {
+        source: walk_query_source(folder, source_field.source),
+        rename: source_field.rename,
+    }
+}
+
+/// the table, the subquery or the arguments of the function
+pub fn walk_query_source<F: Fold + ?Sized>(folder: &mut F, query_source: QuerySource) -> QuerySource {
+    match query_source {
+        QuerySource::TableName(table_name) => QuerySource::TableName(folder.fold_table_name(table_name)),
+        QuerySource::Query(select) => QuerySource::Query(folder.fold_select(select)),
+        QuerySource::Function(function) => {
+            let params = function.params.into_iter().map(|p| folder.fold_operand(p)).collect();
+            QuerySource::Function(Function::new(&function.function, params))
+        }
+        query_source => query_source,
+    }
+}
+
+pub fn walk_order<F: Fold + ?Sized>(folder: &mut F, order: Order) -> Order {
+    Order {
+        operand: folder.fold_operand(order.operand),
+        direction: order.direction,
+        nulls_where: order.nulls_where,
+    }
+}
@@ -1,9 +1,12 @@
 use query::Operand;
 use query::operand::ToOperand;
 use query::source::QuerySource;
+use dao::Value;
 
 
 /// function in a sql statement
+/// the function names are in their canonical (postgresql) form,
+/// each database platform maps them into their equivalent when building the sql
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Function {
@@ -11,6 +14,83 @@ pub struct Function {
     pub params: Vec<Operand>,
 }
 
+impl Function {
+    pub fn new(function: &str, params: Vec<Operand>) -> Self {
+        Function {
+            function: function.to_owned(),
+            params: params,
+        }
+    }
+
+    /// the unit and the operand of a DATE_TRUNC function
+    /// the unit needs to be a literal string since it is translated into a date format
+    /// in databases that don't have date_trunc
+    pub fn date_trunc_parts(&self) -> Result<(&str, &Operand), String> {
+        if self.params.len() != 2 {
+            return Err("DATE_TRUNC expects the unit and the operand".to_owned());
+        }
+        match self.params[0] {
+            Operand::Value(Value::String(ref unit)) => Ok((&unit[..], &self.params[1])),
+            _ => Err("DATE_TRUNC expects the unit to be a string".to_owned()),
+        }
+    }
+
+    /// the separator of a STRING_AGG function
+    pub fn string_agg_separator(&self) -> Result<&str, String> {
+        if self.params.len() != 2 {
+            return Err("STRING_AGG expects the operand and the separator".to_owned());
+        }
+        match self.params[1] {
+            Operand::Value(Value::String(ref separator)) => Ok(&separator[..]),
+            _ => Err("STRING_AGG expects the separator to be a string".to_owned()),
+        }
+    }
+
+    /// the operand and the data type of a CAST function
+    pub fn cast_parts(&self) -> Result<(&Operand, &str), String> {
+        if self.params.len() != 2 {
+            return Err("CAST expects the operand and the data type".to_owned());
+        }
+        match self.params[1] {
+            Operand::Value(Value::String(ref data_type)) => Ok((&self.params[0], &data_type[..])),
+            _ => Err("CAST expects the data type to be a string".to_owned()),
+        }
+    }
+
+    /// the searched column, the text search configuration and the text of a TS_RANK or TS_HEADLINE function
+    pub fn text_search_parts(&self) -> Result<(&Operand, &str, &Operand), String> {
+        if self.params.len() != 3 {
+            return Err(format!("{} expects the column, the text search configuration and the text searched",
+                               self.function));
+        }
+        match self.params[1] {
+            Operand::Value(Value::String(ref config)) => Ok((&self.params[0], &config[..], &self.params[2])),
+            _ => Err(format!("{} expects the text search configuration to be a string", self.function)),
+        }
+    }
+}
+
+/// the units of DATE_TRUNC that are supported in every database
+pub const DATE_TRUNC_UNITS: &'static [&'static str] = &["year", "month", "day", "hour", "minute", "second"];
+
+/// the names of the functions provided here, the ones each database platform knows how to build
+pub const FUNCTIONS: &'static [&'static str] = &["COUNT", "MAX", "MIN", "NOW", "SUM", "AVG", "COALESCE",
+                                                 "NULLIF", "LOWER", "UPPER", "TRIM", "LENGTH", "SUBSTRING",
+                                                 "ROUND", "DATE_TRUNC", "STRING_AGG", "CONCAT", "CAST",
+                                                 "TS_RANK", "TS_HEADLINE"];
+
+fn function(name: &str, params: Vec<Operand>) -> Operand {
+    Operand::QuerySource(QuerySource::Function(Function::new(name, params)))
+}
+
+/// call any database function by name,
+/// useful for functions that are not covered here, ie: `call("st_distance", &[&"location", &point])`
+/// the function name is used as is, it will not be translated for each database
+pub fn call(name: &str, params: &[&ToOperand]) -> Operand {
+    let params = params.iter().map(|p| p.to_operand()).collect();
+    function(name, params)
+}
+
 
 /// A database function COUNT
 pub fn COUNT(to_operand: &ToOperand) -> Operand {
@@ -43,3 +123,106 @@ pub fn NOW() -> Operand {
         params: vec![],
     }))
 }
+
+/// database function SUM
+pub fn SUM(to_operand: &ToOperand) -> Operand {
+    function("SUM", vec![to_operand.to_operand()])
+}
+
+/// database function AVG
+pub fn AVG(to_operand: &ToOperand) -> Operand {
+    function("AVG", vec![to_operand.to_operand()])
+}
+
+/// the first non-null of the operands
+pub fn COALESCE(to_operands: &[&ToOperand]) -> Operand {
+    let params = to_operands.iter().map(|p| p.to_operand()).collect();
+    function("COALESCE", params)
+}
+
+/// null if the 2 operands are equal, otherwise the first operand
+pub fn NULLIF(to_operand: &ToOperand, other: &ToOperand) -> Operand {
+    function("NULLIF", vec![to_operand.to_operand(), other.to_operand()])
+}
+
+pub fn LOWER(to_operand: &ToOperand) -> Operand {
+    function("LOWER", vec![to_operand.to_operand()])
+}
+
+pub fn UPPER(to_operand: &ToOperand) -> Operand {
+    function("UPPER", vec![to_operand.to_operand()])
+}
+
+pub fn TRIM(to_operand: &ToOperand) -> Operand {
+    function("TRIM", vec![to_operand.to_operand()])
+}
+
+/// the number of characters of the string
+/// CHAR_LENGTH in mysql since LENGTH there counts the bytes
+pub fn LENGTH(to_operand: &ToOperand) -> Operand {
+    function("LENGTH", vec![to_operand.to_operand()])
+}
+
+/// substring starting from the 1-based index `from` with `length` characters,
+/// SUBSTR in sqlite
+pub fn SUBSTRING(to_operand: &ToOperand, from: i32, length: i32) -> Operand {
+    function("SUBSTRING",
+             vec![to_operand.to_operand(), Operand::Value(Value::I32(from)), Operand::Value(Value::I32(length))])
+}
+
+/// round the number to the specified decimal places
+pub fn ROUND(to_operand: &ToOperand, decimal_places: i32) -> Operand {
+    function("ROUND",
+             vec![to_operand.to_operand(), Operand::Value(Value::I32(decimal_places))])
+}
+
+/// truncate the date to the unit: year, month, day, hour, minute, second
+/// emulated with strftime in sqlite and DATE_FORMAT in mysql
+pub fn DATE_TRUNC(unit: &str, to_operand: &ToOperand) -> Operand {
+    function("DATE_TRUNC",
+             vec![Operand::Value(Value::String(unit.to_owned())), to_operand.to_operand()])
+}
+
+/// concatenate the values of the group with the separator,
+/// GROUP_CONCAT in sqlite and mysql
+pub fn STRING_AGG(to_operand: &ToOperand, separator: &str) -> Operand {
+    function("STRING_AGG",
+             vec![to_operand.to_operand(), Operand::Value(Value::String(separator.to_owned()))])
+}
+
+/// concatenate the operands as text, the || operator in sqlite
+pub fn CONCAT(to_operands: &[&ToOperand]) -> Operand {
+    let params = to_operands.iter().map(|p| p.to_operand()).collect();
+    function("CONCAT", params)
+}
+
+/// convert the operand to the data type, ie: `CAST(&"price", "TEXT")`
+/// the data type is written as is, except TEXT which is mapped to CHAR in mysql
+pub fn CAST(to_operand: &ToOperand, data_type: &str) -> Operand {
+    function("CAST",
+             vec![to_operand.to_operand(), Operand::Value(Value::String(data_type.to_owned()))])
+}
+
+/// the relevance of the record to the searched text, the higher the better,
+/// `ts_rank` in postgresql, the negated `bm25` of the fts5 table in sqlite
+/// and `MATCH .. AGAINST` in mysql.
+/// The config is the text search configuration of postgresql, ie: `"english"`, ignored in the others.
+/// The column should be qualified with the fts5 table in sqlite, ie: `"product_fts.description"`,
+/// where the rank is of the words searched with MATCH in the query
+pub fn TS_RANK(to_operand: &ToOperand, config: &str, text: &str) -> Operand {
+    function("TS_RANK",
+             vec![to_operand.to_operand(),
+                  Operand::Value(Value::String(config.to_owned())),
+                  Operand::Value(Value::String(text.to_owned()))])
+}
+
+/// the fragment of the text with the matched words enclosed in `<b>` and `</b>`,
+/// `ts_headline` in postgresql and `snippet` of the fts5 table in sqlite,
+/// where the words searched with MATCH in the query are highlighted.
+/// Not supported in mysql
+pub fn TS_HEADLINE(to_operand: &ToOperand, config: &str, text: &str) -> Operand {
+    function("TS_HEADLINE",
+             vec![to_operand.to_operand(),
+                  Operand::Value(Value::String(config.to_owned())),
+                  Operand::Value(Value::String(text.to_owned()))])
+}
@@ -1,6 +1,7 @@
 use table::Table;
 use query::Filter;
-use query::table_name::{TableName,ToTableName};
+use query::table_name::ToTableName;
+use query::source::{SourceField, QuerySource, ToSourceField};
 
 
 #[derive(Debug)]
@@ -19,41 +20,134 @@ pub enum Modifier {
     FULL,
 }
 
+/// the joined source can be a table, a subquery or a function,
+/// renamed so the same table can be joined more than once,
+/// ie: `"employee".AS("manager").ON("manager.employee_id".EQ(&"employee.manager_id"))`
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Join {
     pub modifier: Option<Modifier>,
     pub join_type: Option<JoinType>,
-    pub table_name: TableName,
-    pub on: Filter,
+    pub source: SourceField,
+    /// LATERAL, the source can refer to the columns of the preceding sources
+    pub lateral: bool,
+    pub on: Option<Filter>,
+    /// the columns with the same name in both sources, used instead of ON
+    pub using: Vec<String>,
+}
+
+impl Join {
+    pub fn new(source: SourceField) -> Self {
+        Join {
+            modifier: None,
+            join_type: None,
+            source: source,
+            lateral: false,
+            on: None,
+            using: vec![],
+        }
+    }
+
+    /// CROSS JOIN, every row of the source is joined without a condition
+    pub fn cross(to_source_field: &ToSourceField) -> Self {
+        let mut join = Self::from_source(to_source_field);
+        join.join_type = Some(JoinType::CROSS);
+        join
+    }
+
+    fn from_source(to_source_field: &ToSourceField) -> Self {
+        let mut sources = to_source_field.to_source_field();
+        assert!(sources.len() == 1, "only 1 source can be joined at a time");
+        Join::new(sources.remove(0))
+    }
+
+    /// the alias of the joined source, or the name of the table
+    pub fn name(&self) -> Option<String> {
+        match self.source.rename {
+            Some(ref rename) => Some(rename.to_owned()),
+            None => {
+                match self.source.source {
+                    QuerySource::TableName(ref table_name) => Some(table_name.name.to_owned()),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    pub fn LEFT(mut self) -> Self {
+        self.modifier = Some(Modifier::LEFT);
+        self
+    }
+
+    pub fn RIGHT(mut self) -> Self {
+        self.modifier = Some(Modifier::RIGHT);
+        self
+    }
+
+    pub fn FULL(mut self) -> Self {
+        self.modifier = Some(Modifier::FULL);
+        self
+    }
+
+    pub fn INNER(mut self) -> Self {
+        self.join_type = Some(JoinType::INNER);
+        self
+    }
+
+    pub fn LATERAL(mut self) -> Self {
+        self.lateral = true;
+        self
+    }
 }
 
 
 pub trait ToJoin {
     fn ON(&self, filter: Filter) -> Join;
+
+    /// join on the columns with the same name in both sources
+    fn USING(&self, columns: Vec<&str>) -> Join;
 }
 
 impl<F> ToJoin for F
     where F: Fn() -> Table
 {
     fn ON(&self, filter: Filter) -> Join {
-        let table = self();
-        Join {
-            modifier: None,
-            join_type: None,
-            table_name: table.to_table_name(),
-            on: filter,
-        }
+        let mut join = Join::from_source(&self().to_table_name());
+        join.on = Some(filter);
+        join
+    }
+
+    fn USING(&self, columns: Vec<&str>) -> Join {
+        let mut join = Join::from_source(&self().to_table_name());
+        join.using = columns.iter().map(|c| c.to_string()).collect();
+        join
     }
 }
 
 impl<'a> ToJoin for &'a str {
     fn ON(&self, filter: Filter) -> Join {
-        Join {
-            modifier: None,
-            join_type: None,
-            table_name: self.to_table_name(),
-            on: filter,
-        }
+        let mut join = Join::from_source(&self.to_table_name());
+        join.on = Some(filter);
+        join
+    }
+
+    fn USING(&self, columns: Vec<&str>) -> Join {
+        let mut join = Join::from_source(&self.to_table_name());
+        join.using = columns.iter().map(|c| c.to_string()).collect();
+        join
+    }
+}
+
+impl ToJoin for SourceField {
+    fn ON(&self, filter: Filter) -> Join {
+        let mut join = Join::new(self.to_owned());
+        join.on = Some(filter);
+        join
+    }
+
+    fn USING(&self, columns: Vec<&str>) -> Join {
+        let mut join = Join::new(self.to_owned());
+        join.using = columns.iter().map(|c| c.to_string()).collect();
+        join
     }
 }
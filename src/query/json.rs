@@ -0,0 +1,1023 @@
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use chrono::datetime::DateTime;
+use rustc_serialize::json::Json;
+use rustc_serialize::base64::{ToBase64, FromBase64, STANDARD};
+use dao::Value;
+use database::DbError;
+use query::{Select, DeclaredQuery, Range};
+use query::{Filter, Condition, Equality, Connector};
+use query::{Operand, Field, Function, ColumnName, TableName};
+use query::{Join, JoinType, Modifier};
+use query::{Order, Direction, NullsWhere};
+use query::{Lock, LockMode, LockWait, Keyset};
+use query::source::{SourceField, QuerySource};
+use query::function::{FUNCTIONS, DATE_TRUNC_UNITS};
+use query::fold::{Fold, walk_select, walk_operand, walk_source_field, walk_join};
+
+/// the version of the json format of the queries,
+/// increased when the format changes in a way the older readers can not load
+pub const VERSION: u64 = 1;
+
+/// the json representation of the query, stable across the releases,
+/// to persist the saved searches or to send the queries between services.
+/// The values keep their types, ie: `{"type": "I32", "value": 100}`,
+/// the variants of the enums are objects with a single key, ie: `{"column": {"column": "price"}}`,
+/// and the members that are empty or false are omitted.
+impl Select {
+    /// `{"version": 1, "select": {...}}`
+    pub fn to_json(&self) -> Json {
+        versioned("select", encode_select(self))
+    }
+
+    /// load the select from its json, the json is validated against the format,
+    /// the error mentions the path of the invalid part, ie: `select.filters[0].condition.left`.
+    /// The json is not trusted, the raw sql and the functions that are not in `query::function`
+    /// are refused since they are written into the sql as is,
+    /// so are the LATERAL joins which are not supported in every database
+    pub fn from_json(json: &Json) -> Result<Select, DbError> {
+        let select = try!(Select::from_trusted_json(json));
+        Untrusted::check(select, |untrusted, select| untrusted.fold_select(select))
+    }
+
+    /// load the select from the json written by the application itself,
+    /// the raw sql and any function are loaded
+    pub fn from_trusted_json(json: &Json) -> Result<Select, DbError> {
+        let select = try!(unversioned(json, "select"));
+        decode_select(select, "select")
+    }
+}
+
+impl Filter {
+    /// `{"version": 1, "filter": {...}}`
+    pub fn to_json(&self) -> Json {
+        versioned("filter", encode_filter(self))
+    }
+
+    /// the raw sql and the unknown functions are refused, as in `Select::from_json`
+    pub fn from_json(json: &Json) -> Result<Filter, DbError> {
+        let filter = try!(Filter::from_trusted_json(json));
+        Untrusted::check(filter, |untrusted, filter| untrusted.fold_filter(filter))
+    }
+
+    pub fn from_trusted_json(json: &Json) -> Result<Filter, DbError> {
+        let filter = try!(unversioned(json, "filter"));
+        decode_filter(filter, "filter")
+    }
+}
+
+/// walks the query loaded from an untrusted json, for the parts that are written into the sql as is:
+/// the raw sql, the name of the function, the data type of CAST, the unit of DATE_TRUNC
+/// and the separator of STRING_AGG, and for the parameters of the functions
+struct Untrusted {
+    error: Option<String>,
+}
+
+impl Untrusted {
+    fn check<T, F>(loaded: T, fold: F) -> Result<T, DbError>
+        where F: Fn(&mut Untrusted, T) -> T
+    {
+        let mut untrusted = Untrusted { error: None };
+        let loaded = fold(&mut untrusted, loaded);
+        match untrusted.error {
+            Some(error) => Err(invalid("", &error)),
+            None => Ok(loaded),
+        }
+    }
+
+    fn refuse(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn check_source(&mut self, query_source: &QuerySource) {
+        match *query_source {
+            QuerySource::Raw(..) => self.refuse("raw sql is only loaded from a trusted json".to_owned()),
+            QuerySource::Function(ref function) => self.check_function(function),
+            _ => (),
+        }
+    }
+
+    fn check_function(&mut self, function: &Function) {
+        if !FUNCTIONS.contains(&&*function.function) {
+            return self.refuse(format!("unknown function `{}`", function.function));
+        }
+        let checked = match &*function.function {
+            "CAST" => {
+                // only a plain type name, ie: `VARCHAR(20)`, `NUMERIC(10, 2)`, `DOUBLE PRECISION`
+                function.cast_parts().and_then(|(_, data_type)| {
+                    if data_type.chars().all(|c| c.is_alphanumeric() || " _(),".contains(c)) {
+                        Ok(())
+                    } else {
+                        Err(format!("`{}` is not a data type", data_type))
+                    }
+                })
+            }
+            "DATE_TRUNC" => {
+                function.date_trunc_parts().and_then(|(unit, _)| {
+                    if DATE_TRUNC_UNITS.contains(&unit) {
+                        Ok(())
+                    } else {
+                        Err(format!("unknown DATE_TRUNC unit `{}`", unit))
+                    }
+                })
+            }
+            "STRING_AGG" => {
+                function.string_agg_separator().and_then(|separator| {
+                    if separator.contains(|c: char| c == '\'' || c == '\\') {
+                        Err("the STRING_AGG separator can not have quotes or backslashes".to_owned())
+                    } else {
+                        Ok(())
+                    }
+                })
+            }
+            "TS_RANK" | "TS_HEADLINE" => function.text_search_parts().map(|_| ()),
+            _ => Ok(()),
+        };
+        if let Err(error) = checked {
+            self.refuse(error);
+        }
+    }
+}
+
+impl Fold for Untrusted {
+    fn fold_select(&mut self, select: Select) -> Select {
+        let mut select = walk_select(self, select);
+        // the values and the returned fields are not walked by the folds
+        select.values = select.values.into_iter().map(|v| self.fold_operand(v)).collect();
+        select.enumerated_returns = select.enumerated_returns
+            .into_iter()
+            .map(|field| {
+                Field {
+                    operand: self.fold_operand(field.operand),
+                    name: field.name,
+                }
+            })
+            .collect();
+        select
+    }
+
+    fn fold_operand(&mut self, operand: Operand) -> Operand {
+        match operand {
+            Operand::Raw(..) => self.refuse("raw sql is only loaded from a trusted json".to_owned()),
+            Operand::QuerySource(ref query_source) => self.check_source(query_source),
+            _ => (),
+        }
+        walk_operand(self, operand)
+    }
+
+    fn fold_source_field(&mut self, source_field: SourceField) -> SourceField {
+        self.check_source(&source_field.source);
+        walk_source_field(self, source_field)
+    }
+
+    fn fold_join(&mut self, join: Join) -> Join {
+        if join.lateral {
+            self.refuse("LATERAL joins are only loaded from a trusted json".to_owned());
+        }
+        walk_join(self, join)
+    }
+}
+
+fn versioned(key: &str, json: Json) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert("version".to_owned(), Json::U64(VERSION));
+    object.insert(key.to_owned(), json);
+    Json::Object(object)
+}
+
+fn unversioned<'a>(json: &'a Json, key: &str) -> Result<&'a Json, DbError> {
+    let members = try!(Members::new(json, "", &["version", key]));
+    match members.get("version").and_then(|v| v.as_u64()) {
+        Some(version) if version >= 1 && version <= VERSION => (),
+        Some(version) => return Err(invalid("version", &format!("unsupported version {}", version))),
+        None => return Err(invalid("version", "missing or not a number")),
+    }
+    members.required(key)
+}
+
+fn invalid(path: &str, message: &str) -> DbError {
+    if path.is_empty() {
+        DbError::new(&format!("invalid query json: {}", message))
+    } else {
+        DbError::new(&format!("invalid query json at `{}`: {}", path, message))
+    }
+}
+
+/// the members of a json object, the keys that are not expected are rejected.
+/// A missing member and a null member are the same
+struct Members<'a> {
+    path: String,
+    object: &'a BTreeMap<String, Json>,
+}
+
+impl<'a> Members<'a> {
+    fn new(json: &'a Json, path: &str, keys: &[&str]) -> Result<Self, DbError> {
+        let object = match json.as_object() {
+            Some(object) => object,
+            None => return Err(invalid(path, "expecting an object")),
+        };
+        for key in object.keys() {
+            if !keys.contains(&&key[..]) {
+                return Err(invalid(path, &format!("unexpected member `{}`", key)));
+            }
+        }
+        Ok(Members {
+            path: path.to_owned(),
+            object: object,
+        })
+    }
+
+    fn path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Json> {
+        match self.object.get(key) {
+            Some(&Json::Null) | None => None,
+            Some(json) => Some(json),
+        }
+    }
+
+    fn required(&self, key: &str) -> Result<&'a Json, DbError> {
+        match self.get(key) {
+            Some(json) => Ok(json),
+            None => Err(invalid(&self.path(key), "missing")),
+        }
+    }
+
+    fn decode<T, F>(&self, key: &str, decode: F) -> Result<T, DbError>
+        where F: Fn(&Json, &str) -> Result<T, DbError>
+    {
+        let json = try!(self.required(key));
+        decode(json, &self.path(key))
+    }
+
+    fn optional<T, F>(&self, key: &str, decode: F) -> Result<Option<T>, DbError>
+        where F: Fn(&Json, &str) -> Result<T, DbError>
+    {
+        match self.get(key) {
+            Some(json) => decode(json, &self.path(key)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn list<T, F>(&self, key: &str, decode: F) -> Result<Vec<T>, DbError>
+        where F: Fn(&Json, &str) -> Result<T, DbError>
+    {
+        match self.get(key) {
+            Some(json) => decode_list(json, &self.path(key), decode),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn boolean(&self, key: &str) -> Result<bool, DbError> {
+        match self.get(key) {
+            Some(json) => {
+                match json.as_boolean() {
+                    Some(b) => Ok(b),
+                    None => Err(invalid(&self.path(key), "expecting a boolean")),
+                }
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// the tag and the content of an enum variant, `{"tag": content}`
+fn variant<'a>(json: &'a Json, path: &str) -> Result<(&'a str, &'a Json, String), DbError> {
+    match json.as_object() {
+        Some(object) if object.len() == 1 => {
+            let (tag, content) = object.iter().next().unwrap();
+            Ok((&tag[..], content, format!("{}.{}", path, tag)))
+        }
+        _ => Err(invalid(path, "expecting an object with a single member")),
+    }
+}
+
+fn tagged(tag: &str, content: Json) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert(tag.to_owned(), content);
+    Json::Object(object)
+}
+
+fn decode_list<T, F>(json: &Json, path: &str, decode: F) -> Result<Vec<T>, DbError>
+    where F: Fn(&Json, &str) -> Result<T, DbError>
+{
+    match json.as_array() {
+        Some(array) => {
+            let mut list = vec![];
+            for (i, item) in array.iter().enumerate() {
+                list.push(try!(decode(item, &format!("{}[{}]", path, i))));
+            }
+            Ok(list)
+        }
+        None => Err(invalid(path, "expecting an array")),
+    }
+}
+
+fn decode_string(json: &Json, path: &str) -> Result<String, DbError> {
+    match json.as_string() {
+        Some(s) => Ok(s.to_owned()),
+        None => Err(invalid(path, "expecting a string")),
+    }
+}
+
+fn decode_usize(json: &Json, path: &str) -> Result<usize, DbError> {
+    match json.as_u64() {
+        Some(n) => Ok(n as usize),
+        None => Err(invalid(path, "expecting a positive number")),
+    }
+}
+
+fn encode_strings(strings: &[String]) -> Json {
+    Json::Array(strings.iter().map(|s| Json::String(s.to_owned())).collect())
+}
+
+fn encode_list<T, F>(list: &[T], encode: F) -> Json
+    where F: Fn(&T) -> Json
+{
+    Json::Array(list.iter().map(encode).collect())
+}
+
+/// collects the members of an object, the empty members are left out
+struct Builder {
+    object: BTreeMap<String, Json>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Builder { object: BTreeMap::new() }
+    }
+
+    fn set(&mut self, key: &str, json: Json) -> &mut Self {
+        self.object.insert(key.to_owned(), json);
+        self
+    }
+
+    fn set_string(&mut self, key: &str, s: &Option<String>) -> &mut Self {
+        if let Some(ref s) = *s {
+            self.set(key, Json::String(s.to_owned()));
+        }
+        self
+    }
+
+    fn set_list(&mut self, key: &str, json: Json) -> &mut Self {
+        let is_empty = json.as_array().map(|a| a.is_empty()).unwrap_or(false);
+        if !is_empty {
+            self.set(key, json);
+        }
+        self
+    }
+
+    fn set_true(&mut self, key: &str, b: bool) -> &mut Self {
+        if b {
+            self.set(key, Json::Boolean(true));
+        }
+        self
+    }
+
+    fn build(&mut self) -> Json {
+        Json::Object(self.object.clone())
+    }
+}
+
+macro_rules! decode_signed {
+    ($json:expr, $path:expr, $t:ident, $variant:ident) => (
+        match $json.as_i64() {
+            Some(n) if n >= ::std::$t::MIN as i64 && n <= ::std::$t::MAX as i64 => Ok(Value::$variant(n as $t)),
+            _ => Err(invalid($path, concat!("expecting a ", stringify!($t)))),
+        }
+    )
+}
+
+macro_rules! decode_unsigned {
+    ($json:expr, $path:expr, $t:ident, $variant:ident) => (
+        match $json.as_u64() {
+            Some(n) if n <= ::std::$t::MAX as u64 => Ok(Value::$variant(n as $t)),
+            _ => Err(invalid($path, concat!("expecting a ", stringify!($t)))),
+        }
+    )
+}
+
+/// `{"type": "I32", "value": 100}`
+fn encode_value(value: &Value) -> Json {
+    let (value_type, json) = match *value {
+        Value::Bool(b) => ("Bool", Json::Boolean(b)),
+        Value::I8(n) => ("I8", Json::I64(n as i64)),
+        Value::I16(n) => ("I16", Json::I64(n as i64)),
+        Value::I32(n) => ("I32", Json::I64(n as i64)),
+        Value::I64(n) => ("I64", Json::I64(n)),
+        Value::U8(n) => ("U8", Json::U64(n as u64)),
+        Value::U16(n) => ("U16", Json::U64(n as u64)),
+        Value::U32(n) => ("U32", Json::U64(n as u64)),
+        Value::U64(n) => ("U64", Json::U64(n)),
+        Value::F32(n) => ("F32", encode_float(n as f64)),
+        Value::F64(n) => ("F64", encode_float(n)),
+        Value::String(ref s) => ("String", Json::String(s.to_owned())),
+        Value::VecU8(ref bytes) => ("VecU8", Json::String(bytes.to_base64(STANDARD))),
+        Value::Json(ref json) => ("Json", json.clone()),
+        Value::Uuid(ref uuid) => ("Uuid", Json::String(uuid.to_string())),
+        Value::DateTime(ref date) => ("DateTime", Json::String(date.to_rfc3339())),
+    };
+    Builder::new()
+        .set("type", Json::String(value_type.to_owned()))
+        .set("value", json)
+        .build()
+}
+
+fn decode_value(json: &Json, path: &str) -> Result<Value, DbError> {
+    let members = try!(Members::new(json, path, &["type", "value"]));
+    let value_type = try!(members.decode("type", decode_string));
+    let path = &members.path("value");
+    // a null json value is a value, not a missing member
+    let json = match members.object.get("value") {
+        Some(json) => json,
+        None => return Err(invalid(path, "missing")),
+    };
+    match &*value_type {
+        "Bool" => {
+            match json.as_boolean() {
+                Some(b) => Ok(Value::Bool(b)),
+                None => Err(invalid(path, "expecting a boolean")),
+            }
+        }
+        "I8" => decode_signed!(json, path, i8, I8),
+        "I16" => decode_signed!(json, path, i16, I16),
+        "I32" => decode_signed!(json, path, i32, I32),
+        "I64" => decode_signed!(json, path, i64, I64),
+        "U8" => decode_unsigned!(json, path, u8, U8),
+        "U16" => decode_unsigned!(json, path, u16, U16),
+        "U32" => decode_unsigned!(json, path, u32, U32),
+        "U64" => decode_unsigned!(json, path, u64, U64),
+        "F32" => decode_float(json, path).map(|n| Value::F32(n as f32)),
+        "F64" => decode_float(json, path).map(Value::F64),
+        "String" => decode_string(json, path).map(Value::String),
+        "VecU8" => {
+            let base64 = try!(decode_string(json, path));
+            match base64.from_base64() {
+                Ok(bytes) => Ok(Value::VecU8(bytes)),
+                Err(_) => Err(invalid(path, "expecting base64 encoded bytes")),
+            }
+        }
+        "Json" => Ok(Value::Json(json.clone())),
+        "Uuid" => {
+            let uuid = try!(decode_string(json, path));
+            match Uuid::parse_str(&uuid) {
+                Ok(uuid) => Ok(Value::Uuid(uuid)),
+                Err(_) => Err(invalid(path, "expecting a uuid")),
+            }
+        }
+        "DateTime" => {
+            let date = try!(decode_string(json, path));
+            match DateTime::parse_from_rfc3339(&date) {
+                Ok(date) => Ok(Value::DateTime(date)),
+                Err(_) => Err(invalid(path, "expecting a rfc3339 date")),
+            }
+        }
+        _ => Err(invalid(&members.path("type"), &format!("unknown type `{}`", value_type))),
+    }
+}
+
+/// json has no NaN and infinity, they are written as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`
+fn encode_float(n: f64) -> Json {
+    if n.is_nan() {
+        Json::String("NaN".to_owned())
+    } else if n.is_infinite() && n > 0.0 {
+        Json::String("Infinity".to_owned())
+    } else if n.is_infinite() {
+        Json::String("-Infinity".to_owned())
+    } else {
+        Json::F64(n)
+    }
+}
+
+fn decode_float(json: &Json, path: &str) -> Result<f64, DbError> {
+    match json.as_string() {
+        Some("NaN") => Ok(::std::f64::NAN),
+        Some("Infinity") => Ok(::std::f64::INFINITY),
+        Some("-Infinity") => Ok(::std::f64::NEG_INFINITY),
+        _ => {
+            match json.as_f64() {
+                Some(n) => Ok(n),
+                None => Err(invalid(path, "expecting a number, NaN, Infinity or -Infinity")),
+            }
+        }
+    }
+}
+
+fn encode_column_name(column_name: &ColumnName) -> Json {
+    Builder::new()
+        .set("column", Json::String(column_name.column.to_owned()))
+        .set_string("table", &column_name.table)
+        .set_string("schema", &column_name.schema)
+        .build()
+}
+
+fn decode_column_name(json: &Json, path: &str) -> Result<ColumnName, DbError> {
+    let members = try!(Members::new(json, path, &["column", "table", "schema"]));
+    Ok(ColumnName {
+        column: try!(members.decode("column", decode_string)),
+        table: try!(members.optional("table", decode_string)),
+        schema: try!(members.optional("schema", decode_string)),
+    })
+}
+
+fn encode_table_name(table_name: &TableName) -> Json {
+    Builder::new()
+        .set("name", Json::String(table_name.name.to_owned()))
+        .set_string("schema", &table_name.schema)
+        .set_list("columns", encode_list(&table_name.columns, encode_column_name))
+        .build()
+}
+
+fn decode_table_name(json: &Json, path: &str) -> Result<TableName, DbError> {
+    let members = try!(Members::new(json, path, &["name", "schema", "columns"]));
+    Ok(TableName {
+        name: try!(members.decode("name", decode_string)),
+        schema: try!(members.optional("schema", decode_string)),
+        columns: try!(members.list("columns", decode_column_name)),
+    })
+}
+
+fn encode_raw(sql: &str, params: &[Value]) -> Json {
+    Builder::new()
+        .set("sql", Json::String(sql.to_owned()))
+        .set_list("params", encode_list(params, encode_value))
+        .build()
+}
+
+fn decode_raw(json: &Json, path: &str) -> Result<(String, Vec<Value>), DbError> {
+    let members = try!(Members::new(json, path, &["sql", "params"]));
+    let sql = try!(members.decode("sql", decode_string));
+    let params = try!(members.list("params", decode_value));
+    Ok((sql, params))
+}
+
+fn encode_operand(operand: &Operand) -> Json {
+    match *operand {
+        Operand::ColumnName(ref column_name) => tagged("column", encode_column_name(column_name)),
+        Operand::QuerySource(ref query_source) => tagged("source", encode_query_source(query_source)),
+        Operand::Value(ref value) => tagged("value", encode_value(value)),
+        Operand::Vec(ref operands) => tagged("vec", encode_list(operands, encode_operand)),
+        Operand::Raw(ref sql, ref params) => tagged("raw", encode_raw(sql, params)),
+        Operand::Null => tagged("null", Json::Null),
+        Operand::None => tagged("none", Json::Null),
+    }
+}
+
+fn decode_operand(json: &Json, path: &str) -> Result<Operand, DbError> {
+    let (tag, content, path) = try!(variant(json, path));
+    match tag {
+        "column" => decode_column_name(content, &path).map(Operand::ColumnName),
+        "source" => decode_query_source(content, &path).map(Operand::QuerySource),
+        "value" => decode_value(content, &path).map(Operand::Value),
+        "vec" => decode_list(content, &path, decode_operand).map(Operand::Vec),
+        "raw" => {
+            let (sql, params) = try!(decode_raw(content, &path));
+            Ok(Operand::Raw(sql, params))
+        }
+        "null" if content.is_null() => Ok(Operand::Null),
+        "none" if content.is_null() => Ok(Operand::None),
+        _ => Err(invalid(&path, "unknown operand")),
+    }
+}
+
+fn encode_function(function: &Function) -> Json {
+    Builder::new()
+        .set("function", Json::String(function.function.to_owned()))
+        .set_list("params", encode_list(&function.params, encode_operand))
+        .build()
+}
+
+fn decode_function(json: &Json, path: &str) -> Result<Function, DbError> {
+    let members = try!(Members::new(json, path, &["function", "params"]));
+    let function = try!(members.decode("function", decode_string));
+    let params = try!(members.list("params", decode_operand));
+    Ok(Function::new(&function, params))
+}
+
+fn encode_query_source(query_source: &QuerySource) -> Json {
+    match *query_source {
+        QuerySource::TableName(ref table_name) => tagged("table", encode_table_name(table_name)),
+        QuerySource::Query(ref select) => tagged("query", encode_select(select)),
+        QuerySource::Function(ref function) => tagged("function", encode_function(function)),
+        QuerySource::Raw(ref sql, ref params) => tagged("raw", encode_raw(sql, params)),
+    }
+}
+
+fn decode_query_source(json: &Json, path: &str) -> Result<QuerySource, DbError> {
+    let (tag, content, path) = try!(variant(json, path));
+    match tag {
+        "table" => decode_table_name(content, &path).map(QuerySource::TableName),
+        "query" => decode_select(content, &path).map(QuerySource::Query),
+        "function" => decode_function(content, &path).map(QuerySource::Function),
+        "raw" => {
+            let (sql, params) = try!(decode_raw(content, &path));
+            Ok(QuerySource::Raw(sql, params))
+        }
+        _ => Err(invalid(&path, "unknown source")),
+    }
+}
+
+fn encode_source_field(source_field: &SourceField) -> Json {
+    Builder::new()
+        .set("source", encode_query_source(&source_field.source))
+        .set_string("rename", &source_field.rename)
+        .build()
+}
+
+fn decode_source_field(json: &Json, path: &str) -> Result<SourceField, DbError> {
+    let members = try!(Members::new(json, path, &["source", "rename"]));
+    Ok(SourceField {
+        source: try!(members.decode("source", decode_query_source)),
+        rename: try!(members.optional("rename", decode_string)),
+    })
+}
+
+fn encode_field(field: &Field) -> Json {
+    Builder::new()
+        .set("operand", encode_operand(&field.operand))
+        .set_string("name", &field.name)
+        .build()
+}
+
+fn decode_field(json: &Json, path: &str) -> Result<Field, DbError> {
+    let members = try!(Members::new(json, path, &["operand", "name"]));
+    Ok(Field {
+        operand: try!(members.decode("operand", decode_operand)),
+        name: try!(members.optional("name", decode_string)),
+    })
+}
+
+/// `"EQ"`, or `{"ANY": "EQ"}` for the quantified comparisons
+fn encode_equality(equality: &Equality) -> Json {
+    let name = match *equality {
+        Equality::ANY(ref equality) => return tagged("ANY", encode_equality(equality)),
+        Equality::ALL(ref equality) => return tagged("ALL", encode_equality(equality)),
+        Equality::EQ => "EQ",
+        Equality::NEQ => "NEQ",
+        Equality::LT => "LT",
+        Equality::LTE => "LTE",
+        Equality::GT => "GT",
+        Equality::GTE => "GTE",
+        Equality::IN => "IN",
+        Equality::NOT_IN => "NOT_IN",
+        Equality::LIKE => "LIKE",
+        Equality::ILIKE => "ILIKE",
+        Equality::IS_NOT_NULL => "IS_NOT_NULL",
+        Equality::IS_NULL => "IS_NULL",
+        Equality::BETWEEN => "BETWEEN",
+        Equality::NOT_BETWEEN => "NOT_BETWEEN",
+        Equality::NOT_LIKE => "NOT_LIKE",
+        Equality::NOT_ILIKE => "NOT_ILIKE",
+        Equality::EXISTS => "EXISTS",
+        Equality::NOT_EXISTS => "NOT_EXISTS",
+        Equality::IS_DISTINCT_FROM => "IS_DISTINCT_FROM",
+        Equality::IS_NOT_DISTINCT_FROM => "IS_NOT_DISTINCT_FROM",
+        Equality::SIMILAR_TO => "SIMILAR_TO",
+        Equality::NOT_SIMILAR_TO => "NOT_SIMILAR_TO",
+        Equality::REGEX => "REGEX",
+        Equality::NOT_REGEX => "NOT_REGEX",
+        Equality::MATCH => "MATCH",
+    };
+    Json::String(name.to_owned())
+}
+
+fn decode_equality(json: &Json, path: &str) -> Result<Equality, DbError> {
+    if json.is_object() {
+        let (tag, content, path) = try!(variant(json, path));
+        let equality = Box::new(try!(decode_equality(content, &path)));
+        if equality.operator().is_none() {
+            return Err(invalid(&path, "ANY/ALL can only quantify a comparison"));
+        }
+        return match tag {
+            "ANY" => Ok(Equality::ANY(equality)),
+            "ALL" => Ok(Equality::ALL(equality)),
+            _ => Err(invalid(&path, "unknown quantifier")),
+        };
+    }
+    let name = try!(decode_string(json, path));
+    let equality = match &*name {
+        "EQ" => Equality::EQ,
+        "NEQ" => Equality::NEQ,
+        "LT" => Equality::LT,
+        "LTE" => Equality::LTE,
+        "GT" => Equality::GT,
+        "GTE" => Equality::GTE,
+        "IN" => Equality::IN,
+        "NOT_IN" => Equality::NOT_IN,
+        "LIKE" => Equality::LIKE,
+        "ILIKE" => Equality::ILIKE,
+        "IS_NOT_NULL" => Equality::IS_NOT_NULL,
+        "IS_NULL" => Equality::IS_NULL,
+        "BETWEEN" => Equality::BETWEEN,
+        "NOT_BETWEEN" => Equality::NOT_BETWEEN,
+        "NOT_LIKE" => Equality::NOT_LIKE,
+        "NOT_ILIKE" => Equality::NOT_ILIKE,
+        "EXISTS" => Equality::EXISTS,
+        "NOT_EXISTS" => Equality::NOT_EXISTS,
+        "IS_DISTINCT_FROM" => Equality::IS_DISTINCT_FROM,
+        "IS_NOT_DISTINCT_FROM" => Equality::IS_NOT_DISTINCT_FROM,
+        "SIMILAR_TO" => Equality::SIMILAR_TO,
+        "NOT_SIMILAR_TO" => Equality::NOT_SIMILAR_TO,
+        "REGEX" => Equality::REGEX,
+        "NOT_REGEX" => Equality::NOT_REGEX,
+        "MATCH" => Equality::MATCH,
+        _ => return Err(invalid(path, &format!("unknown equality `{}`", name))),
+    };
+    Ok(equality)
+}
+
+fn encode_filter(filter: &Filter) -> Json {
+    let connector = match filter.connector {
+        Connector::And => "AND",
+        Connector::Or => "OR",
+    };
+    let condition = Builder::new()
+        .set("left", encode_operand(&filter.condition.left))
+        .set("equality", encode_equality(&filter.condition.equality))
+        .set("right", encode_operand(&filter.condition.right))
+        .build();
+    Builder::new()
+        .set("connector", Json::String(connector.to_owned()))
+        .set("condition", condition)
+        .set_list("sub_filters", encode_list(&filter.sub_filters, encode_filter))
+        .build()
+}
+
+fn decode_filter(json: &Json, path: &str) -> Result<Filter, DbError> {
+    let members = try!(Members::new(json, path, &["connector", "condition", "sub_filters"]));
+    let connector = match &*try!(members.decode("connector", decode_string)) {
+        "AND" => Connector::And,
+        "OR" => Connector::Or,
+        _ => return Err(invalid(&members.path("connector"), "expecting AND or OR")),
+    };
+    Ok(Filter {
+        connector: connector,
+        condition: try!(members.decode("condition", decode_condition)),
+        sub_filters: try!(members.list("sub_filters", decode_filter)),
+    })
+}
+
+fn decode_condition(json: &Json, path: &str) -> Result<Condition, DbError> {
+    let members = try!(Members::new(json, path, &["left", "equality", "right"]));
+    Ok(Condition {
+        left: try!(members.decode("left", decode_operand)),
+        equality: try!(members.decode("equality", decode_equality)),
+        right: try!(members.decode("right", decode_operand)),
+    })
+}
+
+fn encode_order(order: &Order) -> Json {
+    let mut builder = Builder::new();
+    builder.set("operand", encode_operand(&order.operand));
+    match order.direction {
+        Some(Direction::ASC) => builder.set("direction", Json::String("ASC".to_owned())),
+        Some(Direction::DESC) => builder.set("direction", Json::String("DESC".to_owned())),
+        None => &mut builder,
+    };
+    match order.nulls_where {
+        Some(NullsWhere::FIRST) => builder.set("nulls", Json::String("FIRST".to_owned())),
+        Some(NullsWhere::LAST) => builder.set("nulls", Json::String("LAST".to_owned())),
+        None => &mut builder,
+    };
+    builder.build()
+}
+
+fn decode_direction(json: &Json, path: &str) -> Result<Direction, DbError> {
+    match &*try!(decode_string(json, path)) {
+        "ASC" => Ok(Direction::ASC),
+        "DESC" => Ok(Direction::DESC),
+        _ => Err(invalid(path, "expecting ASC or DESC")),
+    }
+}
+
+fn decode_order(json: &Json, path: &str) -> Result<Order, DbError> {
+    let members = try!(Members::new(json, path, &["operand", "direction", "nulls"]));
+    let nulls_where = try!(members.optional("nulls", |json, path| {
+        match &*try!(decode_string(json, path)) {
+            "FIRST" => Ok(NullsWhere::FIRST),
+            "LAST" => Ok(NullsWhere::LAST),
+            _ => Err(invalid(path, "expecting FIRST or LAST")),
+        }
+    }));
+    Ok(Order {
+        operand: try!(members.decode("operand", decode_operand)),
+        direction: try!(members.optional("direction", decode_direction)),
+        nulls_where: nulls_where,
+    })
+}
+
+fn encode_join(join: &Join) -> Json {
+    let mut builder = Builder::new();
+    match join.modifier {
+        Some(Modifier::LEFT) => builder.set("modifier", Json::String("LEFT".to_owned())),
+        Some(Modifier::RIGHT) => builder.set("modifier", Json::String("RIGHT".to_owned())),
+        Some(Modifier::FULL) => builder.set("modifier", Json::String("FULL".to_owned())),
+        None => &mut builder,
+    };
+    match join.join_type {
+        Some(JoinType::CROSS) => builder.set("join_type", Json::String("CROSS".to_owned())),
+        Some(JoinType::INNER) => builder.set("join_type", Json::String("INNER".to_owned())),
+        Some(JoinType::OUTER) => builder.set("join_type", Json::String("OUTER".to_owned())),
+        Some(JoinType::NATURAL) => builder.set("join_type", Json::String("NATURAL".to_owned())),
+        None => &mut builder,
+    };
+    if let Some(ref on) = join.on {
+        builder.set("on", encode_filter(on));
+    }
+    builder.set("source", encode_source_field(&join.source))
+        .set_true("lateral", join.lateral)
+        .set_list("using", encode_strings(&join.using))
+        .build()
+}
+
+fn decode_join(json: &Json, path: &str) -> Result<Join, DbError> {
+    let members = try!(Members::new(json,
+                                    path,
+                                    &["modifier", "join_type", "source", "lateral", "on", "using"]));
+    let modifier = try!(members.optional("modifier", |json, path| {
+        match &*try!(decode_string(json, path)) {
+            "LEFT" => Ok(Modifier::LEFT),
+            "RIGHT" => Ok(Modifier::RIGHT),
+            "FULL" => Ok(Modifier::FULL),
+            _ => Err(invalid(path, "expecting LEFT, RIGHT or FULL")),
+        }
+    }));
+    let join_type = try!(members.optional("join_type", |json, path| {
+        match &*try!(decode_string(json, path)) {
+            "CROSS" => Ok(JoinType::CROSS),
+            "INNER" => Ok(JoinType::INNER),
+            "OUTER" => Ok(JoinType::OUTER),
+            "NATURAL" => Ok(JoinType::NATURAL),
+            _ => Err(invalid(path, "expecting CROSS, INNER, OUTER or NATURAL")),
+        }
+    }));
+    Ok(Join {
+        modifier: modifier,
+        join_type: join_type,
+        source: try!(members.decode("source", decode_source_field)),
+        lateral: try!(members.boolean("lateral")),
+        on: try!(members.optional("on", decode_filter)),
+        using: try!(members.list("using", decode_string)),
+    })
+}
+
+fn encode_declared_query(declared: &DeclaredQuery) -> Json {
+    let mut builder = Builder::new();
+    if let Some(ref recursive_query) = declared.recursive_query {
+        builder.set("recursive_query", encode_select(recursive_query));
+    }
+    builder.set("name", Json::String(declared.name.to_owned()))
+        .set_list("fields", encode_strings(&declared.fields))
+        .set("query", encode_select(&declared.query))
+        .set_true("is_recursive", declared.is_recursive)
+        .build()
+}
+
+fn decode_declared_query(json: &Json, path: &str) -> Result<DeclaredQuery, DbError> {
+    let members = try!(Members::new(json,
+                                    path,
+                                    &["name", "fields", "query", "is_recursive", "recursive_query"]));
+    Ok(DeclaredQuery {
+        name: try!(members.decode("name", decode_string)),
+        fields: try!(members.list("fields", decode_string)),
+        query: try!(members.decode("query", decode_select)),
+        is_recursive: try!(members.boolean("is_recursive")),
+        recursive_query: try!(members.optional("recursive_query", decode_select)),
+    })
+}
+
+fn encode_lock(lock: &Lock) -> Json {
+    let mode = match lock.mode {
+        LockMode::UPDATE => "UPDATE",
+        LockMode::SHARE => "SHARE",
+    };
+    let mut builder = Builder::new();
+    match lock.wait {
+        Some(LockWait::NOWAIT) => builder.set("wait", Json::String("NOWAIT".to_owned())),
+        Some(LockWait::SKIP_LOCKED) => builder.set("wait", Json::String("SKIP_LOCKED".to_owned())),
+        None => &mut builder,
+    };
+    builder.set("mode", Json::String(mode.to_owned()))
+        .set_list("of_tables", encode_list(&lock.of_tables, encode_table_name))
+        .build()
+}
+
+fn decode_lock(json: &Json, path: &str) -> Result<Lock, DbError> {
+    let members = try!(Members::new(json, path, &["mode", "of_tables", "wait"]));
+    let mode = match &*try!(members.decode("mode", decode_string)) {
+        "UPDATE" => LockMode::UPDATE,
+        "SHARE" => LockMode::SHARE,
+        _ => return Err(invalid(&members.path("mode"), "expecting UPDATE or SHARE")),
+    };
+    let wait = try!(members.optional("wait", |json, path| {
+        match &*try!(decode_string(json, path)) {
+            "NOWAIT" => Ok(LockWait::NOWAIT),
+            "SKIP_LOCKED" => Ok(LockWait::SKIP_LOCKED),
+            _ => Err(invalid(path, "expecting NOWAIT or SKIP_LOCKED")),
+        }
+    }));
+    Ok(Lock {
+        mode: mode,
+        of_tables: try!(members.list("of_tables", decode_table_name)),
+        wait: wait,
+    })
+}
+
+fn encode_keyset(keyset: &Keyset) -> Json {
+    let direction = match keyset.direction {
+        Direction::ASC => "ASC",
+        Direction::DESC => "DESC",
+    };
+    Builder::new()
+        .set("columns", encode_list(&keyset.columns, encode_column_name))
+        .set("direction", Json::String(direction.to_owned()))
+        .set_list("after", encode_list(&keyset.after, encode_value))
+        .build()
+}
+
+fn decode_keyset(json: &Json, path: &str) -> Result<Keyset, DbError> {
+    let members = try!(Members::new(json, path, &["columns", "direction", "after"]));
+    let keyset = Keyset {
+        columns: try!(members.list("columns", decode_column_name)),
+        direction: try!(members.decode("direction", decode_direction)),
+        after: try!(members.list("after", decode_value)),
+    };
+    if !keyset.after.is_empty() && keyset.after.len() != keyset.columns.len() {
+        return Err(invalid(path, "the values of `after` should match the keyset columns"));
+    }
+    Ok(keyset)
+}
+
+fn encode_select(select: &Select) -> Json {
+    let mut builder = Builder::new();
+    if let Some(limit) = select.range.limit {
+        builder.set("limit", Json::U64(limit as u64));
+    }
+    if let Some(offset) = select.range.offset {
+        builder.set("offset", Json::U64(offset as u64));
+    }
+    if let Some(ref lock) = select.lock {
+        builder.set("lock", encode_lock(lock));
+    }
+    if let Some(ref keyset) = select.keyset {
+        builder.set("keyset", encode_keyset(keyset));
+    }
+    builder.set_true("distinct", select.distinct)
+        .set_true("enumerate_all", select.enumerate_all)
+        .set_list("declared_query", encode_list(&select.declared_query, encode_declared_query))
+        .set_list("enumerated_fields", encode_list(&select.enumerated_fields, encode_field))
+        .set_list("distinct_on_columns", encode_strings(&select.distinct_on_columns))
+        .set_list("from", encode_list(&select.from, encode_source_field))
+        .set_list("joins", encode_list(&select.joins, encode_join))
+        .set_list("filters", encode_list(&select.filters, encode_filter))
+        .set_list("order_by", encode_list(&select.order_by, encode_order))
+        .set_list("group_by", encode_list(&select.group_by, encode_operand))
+        .set_list("having", encode_list(&select.having, encode_filter))
+        .set_list("excluded_columns", encode_list(&select.excluded_columns, encode_column_name))
+        .set_list("values", encode_list(&select.values, encode_operand))
+        .set_list("enumerated_returns", encode_list(&select.enumerated_returns, encode_field))
+        .set_true("enable_query_stat", select.enable_query_stat)
+        .build()
+}
+
+fn decode_select(json: &Json, path: &str) -> Result<Select, DbError> {
+    let members = try!(Members::new(json,
+                                    path,
+                                    &["distinct", "enumerate_all", "declared_query",
+                                      "enumerated_fields", "distinct_on_columns", "from",
+                                      "joins", "filters", "order_by", "group_by", "having",
+                                      "excluded_columns", "limit", "offset", "values",
+                                      "enumerated_returns", "enable_query_stat", "lock",
+                                      "keyset"]));
+    let mut select = Select::new();
+    select.distinct = try!(members.boolean("distinct"));
+    select.enumerate_all = try!(members.boolean("enumerate_all"));
+    select.declared_query = try!(members.list("declared_query", decode_declared_query));
+    select.enumerated_fields = try!(members.list("enumerated_fields", decode_field));
+    select.distinct_on_columns = try!(members.list("distinct_on_columns", decode_string));
+    select.from = try!(members.list("from", decode_source_field));
+    select.joins = try!(members.list("joins", decode_join));
+    select.filters = try!(members.list("filters", decode_filter));
+    select.order_by = try!(members.list("order_by", decode_order));
+    select.group_by = try!(members.list("group_by", decode_operand));
+    select.having = try!(members.list("having", decode_filter));
+    select.excluded_columns = try!(members.list("excluded_columns", decode_column_name));
+    select.range = Range {
+        limit: try!(members.optional("limit", decode_usize)),
+        offset: try!(members.optional("offset", decode_usize)),
+    };
+    select.values = try!(members.list("values", decode_operand));
+    select.enumerated_returns = try!(members.list("enumerated_returns", decode_field));
+    select.enable_query_stat = try!(members.boolean("enable_query_stat"));
+    select.lock = try!(members.optional("lock", decode_lock));
+    select.keyset = try!(members.optional("keyset", decode_keyset));
+    Ok(select)
+}
@@ -0,0 +1,51 @@
+use dao::Value;
+use query::ColumnName;
+use query::Direction;
+use database::DbError;
+use rustc_serialize::json;
+use rustc_serialize::base64::{ToBase64, FromBase64, URL_SAFE};
+
+
+/// keyset pagination, seeks the records that comes after the last row of the previous page
+/// instead of skipping the records with OFFSET
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Keyset {
+    /// the columns the records are ordered by, the combination should be unique
+    pub columns: Vec<ColumnName>,
+    pub direction: Direction,
+    /// the values of the columns of the last row of the previous page,
+    /// empty for the first page
+    pub after: Vec<Value>,
+}
+
+impl Keyset {
+    /// the opaque token of the position after the row with these values
+    pub fn encode_cursor(values: &[Value]) -> String {
+        let json = json::encode(&values).unwrap();
+        json.as_bytes().to_base64(URL_SAFE)
+    }
+
+    /// the values of the row from the cursor token
+    pub fn decode_cursor(cursor: &str) -> Result<Vec<Value>, DbError> {
+        let bytes = match cursor.from_base64() {
+            Ok(bytes) => bytes,
+            Err(_) => return Err(DbError::new("Invalid cursor")),
+        };
+        let json = match String::from_utf8(bytes) {
+            Ok(json) => json,
+            Err(_) => return Err(DbError::new("Invalid cursor")),
+        };
+        match json::decode(&json) {
+            Ok(values) => Ok(values),
+            Err(_) => Err(DbError::new("Invalid cursor")),
+        }
+    }
+}
+
+#[test]
+fn test_cursor_round_trip() {
+    let values = vec![Value::String("2016-01-01".to_owned()), Value::I32(42)];
+    let cursor = Keyset::encode_cursor(&values);
+    assert_eq!(Keyset::decode_cursor(&cursor).unwrap(), values);
+}
@@ -0,0 +1,56 @@
+use query::table_name::{TableName, ToTableName};
+
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum LockMode {
+    UPDATE,
+    SHARE,
+}
+
+/// what to do when the rows are already locked by other transaction
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum LockWait {
+    NOWAIT,
+    SKIP_LOCKED,
+}
+
+/// the row locking clause of the select, ie: `FOR UPDATE OF job SKIP LOCKED`
+/// supported in postgresql and mysql >= 8.0,
+/// sqlite locks the whole database in a transaction instead
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Lock {
+    pub mode: LockMode,
+    /// lock only the rows of these tables
+    pub of_tables: Vec<TableName>,
+    pub wait: Option<LockWait>,
+}
+
+impl Lock {
+    pub fn new(mode: LockMode) -> Self {
+        Lock {
+            mode: mode,
+            of_tables: vec![],
+            wait: None,
+        }
+    }
+
+    pub fn of(&mut self, table: &ToTableName) -> &mut Self {
+        self.of_tables.push(table.to_table_name());
+        self
+    }
+
+    /// error right away when a row can not be locked
+    pub fn nowait(&mut self) -> &mut Self {
+        self.wait = Some(LockWait::NOWAIT);
+        self
+    }
+
+    /// skip the rows that can not be locked
+    pub fn skip_locked(&mut self) -> &mut Self {
+        self.wait = Some(LockWait::SKIP_LOCKED);
+        self
+    }
+}
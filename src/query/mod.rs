@@ -7,6 +7,14 @@ pub mod operand;
 pub mod order;
 pub mod field;
 pub mod source;
+pub mod lock;
+pub mod keyset;
+pub mod tree;
+pub mod parser;
+pub mod fold;
+pub mod eval;
+pub mod rest;
+pub mod json;
 pub mod query;
 
 pub use self::column_name::{ColumnName, ToColumnName};
@@ -15,6 +23,9 @@ pub use self::filter::{Filter, Condition, Equality, Connector, HasEquality};
 pub use self::function::COUNT;
 pub use self::function::Function;
 pub use self::join::{Join, JoinType, Modifier};
+pub use self::lock::{Lock, LockMode, LockWait};
+pub use self::keyset::Keyset;
+pub use self::tree::Traversal;
 pub use self::operand::Operand;
 pub use self::order::{Order, ToOrder, HasDirection, NullsWhere, Direction};
 pub use self::field::{Field, ToField};
@@ -23,8 +34,10 @@ pub use self::source::{QuerySource, ToSourceField};
 
 pub use self::query::{Range,DeclaredQuery,Error};
 pub use self::query::{Select,Insert,Update,Delete};
+pub use self::query::{OnConflict, ConflictAction, excluded};
 pub use self::query::Query;
 pub use self::query::IsQuery;
+pub use self::fold::{Fold, Rewriter};
 
 
 
@@ -4,6 +4,7 @@ use table::Column;
 use dao::ToValue;
 use query::source::QuerySource;
 use query::column_name::ToColumnName;
+use query::Select;
 use uuid::Uuid;
 use chrono::datetime::DateTime;
 use rustc_serialize::json::Json;
@@ -22,8 +23,18 @@ pub enum Operand {
     QuerySource(QuerySource),
     Value(Value),
     Vec(Vec<Operand>),
+    /// raw sql with `?` placeholders for the values,
+    /// for expressions that can not be built with the query api
+    Raw(String, Vec<Value>),
+    /// the NULL value, there is no null in `Value`
+    Null,
     None,
 }
+
+/// a raw sql expression, ie: `raw("age(now(), ?)", vec![birthday])`
+pub fn raw(sql: &str, params: Vec<Value>) -> Operand {
+    Operand::Raw(sql.to_owned(), params)
+}
 /// work around for &ToOperand argument for Operand
 impl ToOperand for Operand {
     fn to_operand(&self) -> Operand {
@@ -38,6 +49,13 @@ impl ToOperand for Value{
     }
 }
 
+/// a query used as an operand, ie: subqueries in EXISTS, IN, ANY
+impl ToOperand for Select {
+    fn to_operand(&self) -> Operand {
+        Operand::QuerySource(QuerySource::Query(self.to_owned()))
+    }
+}
+
 
 /// implementation to convert Function that returns Column to yield an Operand
 macro_rules! impl_to_operand_for_fn_column{
@@ -0,0 +1,1026 @@
+use query::{Query, Select, Insert, Update, Delete, Error};
+use query::{Filter, Condition, Equality, Connector};
+use query::{Join, JoinType, Modifier};
+use query::{Order, Direction, NullsWhere};
+use query::{OnConflict, ConflictAction};
+use query::{ColumnName, TableName, Operand, Field, Function};
+use query::source::{SourceField, QuerySource};
+use query::query::Data;
+use dao::Value;
+
+/// the words that end an expression, they can not be used as an alias or a column
+/// name unless they are quoted
+const KEYWORDS: &'static [&'static str] = &["ALL", "AND", "ANY", "AS", "ASC", "BETWEEN", "BY", "CASE",
+                                            "CROSS", "DELETE", "DESC", "DISTINCT", "DO", "ELSE",
+                                            "END", "EXCEPT", "EXISTS", "FETCH", "FOR", "FROM",
+                                            "FULL", "GROUP", "HAVING", "ILIKE", "IN", "INNER",
+                                            "INSERT", "INTERSECT", "INTO", "IS", "JOIN", "LATERAL",
+                                            "LEFT", "LIKE", "LIMIT", "NATURAL", "NOT", "NULL",
+                                            "NULLS", "OFFSET", "ON", "OR", "ORDER", "OUTER",
+                                            "REGEXP", "RETURNING", "RIGHT", "SELECT", "SET",
+                                            "SIMILAR", "SOME", "THEN", "UNION", "UPDATE", "USING",
+                                            "VALUES", "WHEN", "WHERE", "WINDOW"];
+
+/// parse the sql statement into a query, so it can be modified
+/// and built again for any of the database platforms.
+/// Only the sql that can be expressed in the query api is supported,
+/// the values should be literals since placeholders have no values to bind to.
+///
+/// ```rust,ignore
+/// let query = parse("SELECT name FROM product WHERE price > 100 ORDER BY name LIMIT 10")?;
+/// ```
+pub fn parse(sql: &str) -> Result<Query, Error> {
+    let tokens = try!(tokenize(sql));
+    let mut parser = Parser {
+        tokens: tokens,
+        pos: 0,
+    };
+    let query = try!(parser.parse_statement());
+    parser.eat_symbol(";");
+    match parser.peek() {
+        Some(_) => Err(parser.unexpected()),
+        None => Ok(query),
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+enum Token {
+    /// unquoted identifier or keyword
+    Word(String),
+    /// "quoted", `quoted` or [quoted] identifier
+    Quoted(String),
+    /// 'string' literal
+    Str(String),
+    Number(String),
+    Symbol(String),
+}
+
+impl Token {
+    fn to_sql(&self) -> String {
+        match *self {
+            Token::Word(ref word) => word.to_owned(),
+            Token::Quoted(ref name) => format!("\"{}\"", name),
+            Token::Str(ref s) => format!("'{}'", s),
+            Token::Number(ref n) => n.to_owned(),
+            Token::Symbol(ref s) => s.to_owned(),
+        }
+    }
+}
+
+fn syntax_error(message: &str) -> Error {
+    Error::SqlError(message.to_owned())
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, Error> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(syntax_error("unterminated comment"));
+            }
+            i += 2;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().cloned().collect()));
+        } else if c.is_digit(10) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_digit(10) || chars[i] == '.') {
+                i += 1;
+            }
+            // the exponents and the hex numbers are not supported, rather than read as an alias
+            if i < chars.len() && (chars[i].is_alphabetic() || chars[i] == '_') {
+                return Err(syntax_error(&format!("unsupported number {}",
+                                                 chars[start..i + 1].iter().cloned().collect::<String>())));
+            }
+            tokens.push(Token::Number(chars[start..i].iter().cloned().collect()));
+        } else if c == '\'' || c == '"' || c == '`' || c == '[' {
+            let close = match c {
+                '[' => ']',
+                _ => c,
+            };
+            let mut quoted = String::new();
+            i += 1;
+            loop {
+                match chars.get(i).cloned() {
+                    // the quote is escaped by doubling it
+                    Some(q) if q == close && chars.get(i + 1) == Some(&close) => {
+                        quoted.push(close);
+                        i += 2;
+                    }
+                    Some(q) if q == close => {
+                        i += 1;
+                        break;
+                    }
+                    Some(q) => {
+                        quoted.push(q);
+                        i += 1;
+                    }
+                    None => return Err(syntax_error(&format!("unterminated quote {}", c))),
+                }
+            }
+            if c == '\'' {
+                tokens.push(Token::Str(quoted));
+            } else {
+                tokens.push(Token::Quoted(quoted));
+            }
+        } else if c == '?' || c == '$' || c == '@' || c == ':' && next != Some(':') {
+            return Err(syntax_error("placeholders are not supported, the values should be literals"));
+        } else {
+            let two: String = chars[i..::std::cmp::min(i + 2, chars.len())].iter().cloned().collect();
+            let symbol = match &*two {
+                "<=" | ">=" | "<>" | "!=" | "!~" | "::" | "||" => two.to_owned(),
+                _ => {
+                    match c {
+                        '(' | ')' | ',' | '.' | ';' | '*' | '=' | '<' | '>' | '~' | '+' | '-' |
+                        '/' | '%' => c.to_string(),
+                        _ => return Err(syntax_error(&format!("unexpected character {}", c))),
+                    }
+                }
+            };
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+/// the boolean expression before it is flattened into filters,
+/// AND binds tighter than OR
+enum Predicate {
+    Condition(Condition),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+}
+
+impl Predicate {
+    /// the items of a top level AND are separate filters
+    fn into_filters(self) -> Vec<Filter> {
+        match self {
+            Predicate::And(items) => items.into_iter().map(|p| p.into_filter()).collect(),
+            predicate => vec![predicate.into_filter()],
+        }
+    }
+
+    /// the filter is rendered as `( condition AND sub_filter AND sub_filter )`
+    /// with the sub filters that has sub filters of their own in parenthesis,
+    /// the first plain condition of the group is used as the condition of the filter,
+    /// when all are groups, `1 = 1 AND ..` or `1 = 0 OR ..` is used instead
+    fn into_filter(self) -> Filter {
+        let (connector, mut items) = match self {
+            Predicate::Condition(condition) => {
+                return Filter {
+                    connector: Connector::And,
+                    condition: condition,
+                    sub_filters: vec![],
+                }
+            }
+            Predicate::And(items) => (Connector::And, items),
+            Predicate::Or(items) => (Connector::Or, items),
+        };
+        let position = items.iter().position(|p| match *p {
+            Predicate::Condition(_) => true,
+            _ => false,
+        });
+        let condition = match position.map(|index| items.remove(index)) {
+            Some(Predicate::Condition(condition)) => condition,
+            _ => {
+                let neutral = match connector {
+                    Connector::And => "1",
+                    Connector::Or => "0",
+                };
+                Condition {
+                    left: Operand::Raw("1".to_owned(), vec![]),
+                    equality: Equality::EQ,
+                    right: Operand::Raw(neutral.to_owned(), vec![]),
+                }
+            }
+        };
+        let sub_filters = items.into_iter()
+            .map(|p| {
+                let mut filter = p.into_filter();
+                filter.connector = connector.clone();
+                filter
+            })
+            .collect();
+        Filter {
+            connector: Connector::And,
+            condition: condition,
+            sub_filters: sub_filters,
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Some(token) => {
+                syntax_error(&format!("unexpected `{}` at token {}", token.to_sql(), self.pos + 1))
+            }
+            None => syntax_error("unexpected end of the sql statement"),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Word(ref word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Symbol(ref s)) => s == symbol,
+            _ => false,
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if self.is_symbol(symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), Error> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// an unquoted identifier that is not a keyword, or a quoted identifier
+    fn is_identifier(&self) -> bool {
+        match self.peek() {
+            Some(&Token::Word(ref word)) => !KEYWORDS.contains(&&*word.to_uppercase()),
+            Some(&Token::Quoted(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, Error> {
+        if !self.is_identifier() {
+            return Err(self.unexpected());
+        }
+        let name = match self.tokens[self.pos] {
+            Token::Word(ref word) => word.to_owned(),
+            Token::Quoted(ref name) => name.to_owned(),
+            _ => unreachable!(),
+        };
+        self.pos += 1;
+        Ok(name)
+    }
+
+    fn identifiers(&mut self) -> Result<Vec<String>, Error> {
+        let mut names = vec![try!(self.identifier())];
+        while self.eat_symbol(",") {
+            names.push(try!(self.identifier()));
+        }
+        Ok(names)
+    }
+
+    /// `(a, b, c)`
+    fn column_names(&mut self) -> Result<Vec<ColumnName>, Error> {
+        try!(self.expect_symbol("("));
+        let names = try!(self.identifiers());
+        try!(self.expect_symbol(")"));
+        Ok(names.into_iter().map(plain_column).collect())
+    }
+
+    fn usize_literal(&mut self) -> Result<usize, Error> {
+        let n = match self.peek() {
+            Some(&Token::Number(ref n)) => n.parse::<usize>().ok(),
+            _ => None,
+        };
+        match n {
+            Some(n) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            None => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Query, Error> {
+        if self.is_keyword("SELECT") {
+            Ok(Query::Select(try!(self.parse_select())))
+        } else if self.is_keyword("INSERT") {
+            Ok(Query::Insert(try!(self.parse_insert())))
+        } else if self.is_keyword("UPDATE") {
+            Ok(Query::Update(try!(self.parse_update())))
+        } else if self.is_keyword("DELETE") {
+            Ok(Query::Delete(try!(self.parse_delete())))
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn parse_select(&mut self) -> Result<Select, Error> {
+        try!(self.expect_keyword("SELECT"));
+        let mut select = Select::new();
+        if self.eat_keyword("DISTINCT") {
+            if self.eat_keyword("ON") {
+                try!(self.expect_symbol("("));
+                select.distinct_on_columns = try!(self.identifiers());
+                try!(self.expect_symbol(")"));
+            } else {
+                select.distinct = true;
+            }
+        }
+        select.enumerated_fields.push(try!(self.parse_field()));
+        while self.eat_symbol(",") {
+            select.enumerated_fields.push(try!(self.parse_field()));
+        }
+        if !self.eat_keyword("FROM") {
+            return Err(syntax_error("SELECT without FROM is not supported"));
+        }
+        select.from.push(try!(self.parse_source_field()));
+        while self.eat_symbol(",") {
+            select.from.push(try!(self.parse_source_field()));
+        }
+        while let Some(join) = try!(self.parse_join()) {
+            select.joins.push(join);
+        }
+        if self.eat_keyword("WHERE") {
+            select.filters = try!(self.parse_or()).into_filters();
+        }
+        if self.eat_keyword("GROUP") {
+            try!(self.expect_keyword("BY"));
+            select.group_by.push(try!(self.parse_operand()));
+            while self.eat_symbol(",") {
+                select.group_by.push(try!(self.parse_operand()));
+            }
+        }
+        if self.eat_keyword("HAVING") {
+            select.having = try!(self.parse_or()).into_filters();
+        }
+        if self.eat_keyword("ORDER") {
+            try!(self.expect_keyword("BY"));
+            select.order_by.push(try!(self.parse_order()));
+            while self.eat_symbol(",") {
+                select.order_by.push(try!(self.parse_order()));
+            }
+        }
+        loop {
+            if select.range.limit.is_none() && self.eat_keyword("LIMIT") {
+                let limit = try!(self.usize_literal());
+                select.set_limit(limit);
+            } else if select.range.offset.is_none() && self.eat_keyword("OFFSET") {
+                let offset = try!(self.usize_literal());
+                select.set_offset(offset);
+            } else {
+                break;
+            }
+        }
+        Ok(select)
+    }
+
+    fn parse_alias(&mut self) -> Result<Option<String>, Error> {
+        if self.eat_keyword("AS") {
+            Ok(Some(try!(self.identifier())))
+        } else if self.is_identifier() {
+            Ok(Some(try!(self.identifier())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn parse_field(&mut self) -> Result<Field, Error> {
+        let operand = try!(self.parse_operand());
+        Ok(Field {
+            operand: operand,
+            name: try!(self.parse_alias()),
+        })
+    }
+
+    /// a table, a subquery or a function in the FROM clause or in a join
+    fn parse_source_field(&mut self) -> Result<SourceField, Error> {
+        let source = if self.eat_symbol("(") {
+            let query = try!(self.parse_select());
+            try!(self.expect_symbol(")"));
+            QuerySource::Query(query)
+        } else {
+            let name = try!(self.identifier());
+            if self.eat_symbol("(") {
+                let params = try!(self.parse_params());
+                QuerySource::Function(Function::new(&name.to_uppercase(), params))
+            } else {
+                QuerySource::TableName(try!(self.parse_table_name_after(name)))
+            }
+        };
+        Ok(SourceField {
+            source: source,
+            rename: try!(self.parse_alias()),
+        })
+    }
+
+    fn parse_table_name(&mut self) -> Result<TableName, Error> {
+        let name = try!(self.identifier());
+        self.parse_table_name_after(name)
+    }
+
+    /// `table` or `schema.table`
+    fn parse_table_name_after(&mut self, name: String) -> Result<TableName, Error> {
+        if self.eat_symbol(".") {
+            Ok(TableName {
+                schema: Some(name),
+                name: try!(self.identifier()),
+                columns: vec![],
+            })
+        } else {
+            Ok(TableName {
+                schema: None,
+                name: name,
+                columns: vec![],
+            })
+        }
+    }
+
+    fn parse_join(&mut self) -> Result<Option<Join>, Error> {
+        let starts_join = ["JOIN", "LEFT", "RIGHT", "FULL", "INNER", "CROSS", "NATURAL"]
+            .iter()
+            .any(|k| self.is_keyword(k));
+        if !starts_join {
+            return Ok(None);
+        }
+        let mut join_type = if self.eat_keyword("NATURAL") {
+            Some(JoinType::NATURAL)
+        } else if self.eat_keyword("CROSS") {
+            Some(JoinType::CROSS)
+        } else {
+            None
+        };
+        let modifier = if self.eat_keyword("LEFT") {
+            Some(Modifier::LEFT)
+        } else if self.eat_keyword("RIGHT") {
+            Some(Modifier::RIGHT)
+        } else if self.eat_keyword("FULL") {
+            Some(Modifier::FULL)
+        } else {
+            None
+        };
+        if join_type.is_some() && modifier.is_some() {
+            return Err(syntax_error("NATURAL and CROSS joins can not be LEFT, RIGHT or FULL"));
+        }
+        if join_type.is_none() {
+            if self.eat_keyword("INNER") {
+                join_type = Some(JoinType::INNER);
+            } else if self.eat_keyword("OUTER") {
+                join_type = Some(JoinType::OUTER);
+            }
+        }
+        try!(self.expect_keyword("JOIN"));
+        let lateral = self.eat_keyword("LATERAL");
+        let mut join = Join::new(try!(self.parse_source_field()));
+        join.modifier = modifier;
+        join.join_type = join_type;
+        join.lateral = lateral;
+        if self.eat_keyword("ON") {
+            join.on = Some(try!(self.parse_or()).into_filter());
+        } else if self.eat_keyword("USING") {
+            try!(self.expect_symbol("("));
+            join.using = try!(self.identifiers());
+            try!(self.expect_symbol(")"));
+        }
+        Ok(Some(join))
+    }
+
+    fn parse_order(&mut self) -> Result<Order, Error> {
+        let operand = try!(self.parse_operand());
+        let direction = if self.eat_keyword("ASC") {
+            Some(Direction::ASC)
+        } else if self.eat_keyword("DESC") {
+            Some(Direction::DESC)
+        } else {
+            None
+        };
+        let nulls_where = if self.eat_keyword("NULLS") {
+            if self.eat_keyword("FIRST") {
+                Some(NullsWhere::FIRST)
+            } else {
+                try!(self.expect_keyword("LAST"));
+                Some(NullsWhere::LAST)
+            }
+        } else {
+            None
+        };
+        Ok(Order {
+            operand: operand,
+            direction: direction,
+            nulls_where: nulls_where,
+        })
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, Error> {
+        let mut items = vec![try!(self.parse_and())];
+        while self.eat_keyword("OR") {
+            items.push(try!(self.parse_and()));
+        }
+        if items.len() == 1 {
+            Ok(items.remove(0))
+        } else {
+            Ok(Predicate::Or(items))
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate, Error> {
+        let mut items = vec![try!(self.parse_predicate())];
+        while self.eat_keyword("AND") {
+            items.push(try!(self.parse_predicate()));
+        }
+        if items.len() == 1 {
+            Ok(items.remove(0))
+        } else {
+            Ok(Predicate::And(items))
+        }
+    }
+
+    fn parse_predicate(&mut self) -> Result<Predicate, Error> {
+        // a parenthesized group of conditions, otherwise the parenthesis belongs to the operand
+        if self.is_symbol("(") && !self.is_subquery() {
+            let start = self.pos;
+            self.pos += 1;
+            if let Ok(predicate) = self.parse_or() {
+                if self.eat_symbol(")") {
+                    return Ok(predicate);
+                }
+            }
+            self.pos = start;
+        }
+        let exists = if self.eat_keyword("EXISTS") {
+            Some(Equality::EXISTS)
+        } else if self.is_keyword("NOT") && self.peek_is_keyword(1, "EXISTS") {
+            self.pos += 2;
+            Some(Equality::NOT_EXISTS)
+        } else {
+            None
+        };
+        if let Some(equality) = exists {
+            if !self.is_subquery() {
+                return Err(self.unexpected());
+            }
+            let query = try!(self.parse_operand());
+            return Ok(Predicate::Condition(Condition {
+                left: query,
+                equality: equality,
+                right: Operand::None,
+            }));
+        }
+        if self.is_keyword("NOT") {
+            return Err(syntax_error("NOT is only supported in NOT EXISTS, NOT IN, NOT BETWEEN, \
+                                     NOT LIKE and IS NOT"));
+        }
+        let left = try!(self.parse_operand());
+        let (equality, right) = try!(self.parse_comparison());
+        Ok(Predicate::Condition(Condition {
+            left: left,
+            equality: equality,
+            right: right,
+        }))
+    }
+
+    fn peek_is_keyword(&self, offset: usize, keyword: &str) -> bool {
+        match self.peek_at(offset) {
+            Some(&Token::Word(ref word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    /// `(SELECT ..`
+    fn is_subquery(&self) -> bool {
+        self.is_symbol("(") && self.peek_is_keyword(1, "SELECT")
+    }
+
+    /// the equality and the right operand of the condition
+    fn parse_comparison(&mut self) -> Result<(Equality, Operand), Error> {
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            if self.eat_keyword("NULL") {
+                let equality = if negated {
+                    Equality::IS_NOT_NULL
+                } else {
+                    Equality::IS_NULL
+                };
+                return Ok((equality, Operand::None));
+            }
+            try!(self.expect_keyword("DISTINCT"));
+            try!(self.expect_keyword("FROM"));
+            let equality = if negated {
+                Equality::IS_NOT_DISTINCT_FROM
+            } else {
+                Equality::IS_DISTINCT_FROM
+            };
+            return Ok((equality, try!(self.parse_operand())));
+        }
+        let negated = self.eat_keyword("NOT");
+        let equality = if self.eat_keyword("IN") {
+            if negated { Equality::NOT_IN } else { Equality::IN }
+        } else if self.eat_keyword("BETWEEN") {
+            let low = try!(self.parse_operand());
+            try!(self.expect_keyword("AND"));
+            let high = try!(self.parse_operand());
+            let equality = if negated {
+                Equality::NOT_BETWEEN
+            } else {
+                Equality::BETWEEN
+            };
+            return Ok((equality, Operand::Vec(vec![low, high])));
+        } else if self.eat_keyword("LIKE") {
+            if negated { Equality::NOT_LIKE } else { Equality::LIKE }
+        } else if self.eat_keyword("ILIKE") {
+            if negated { Equality::NOT_ILIKE } else { Equality::ILIKE }
+        } else if self.eat_keyword("SIMILAR") {
+            try!(self.expect_keyword("TO"));
+            if negated { Equality::NOT_SIMILAR_TO } else { Equality::SIMILAR_TO }
+        } else if self.eat_keyword("REGEXP") {
+            if negated { Equality::NOT_REGEX } else { Equality::REGEX }
+        } else if negated {
+            return Err(self.unexpected());
+        } else {
+            return self.parse_operator_comparison();
+        };
+        if equality_is_in(&equality) {
+            try!(self.expect_symbol("("));
+            if self.is_keyword("SELECT") {
+                let query = try!(self.parse_select());
+                try!(self.expect_symbol(")"));
+                return Ok((equality, Operand::QuerySource(QuerySource::Query(query))));
+            }
+            let params = try!(self.parse_params());
+            return Ok((equality, Operand::Vec(params)));
+        }
+        Ok((equality, try!(self.parse_operand())))
+    }
+
+    /// `= b`, `> ANY (SELECT ..)`, `~ 'regex'`
+    fn parse_operator_comparison(&mut self) -> Result<(Equality, Operand), Error> {
+        let equality = match self.peek() {
+            Some(&Token::Symbol(ref s)) => {
+                match &**s {
+                    "=" => Equality::EQ,
+                    "!=" | "<>" => Equality::NEQ,
+                    "<" => Equality::LT,
+                    "<=" => Equality::LTE,
+                    ">" => Equality::GT,
+                    ">=" => Equality::GTE,
+                    "~" => Equality::REGEX,
+                    "!~" => Equality::NOT_REGEX,
+                    _ => return Err(self.unexpected()),
+                }
+            }
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        let quantified = equality.operator().is_some();
+        if quantified && (self.eat_keyword("ANY") || self.eat_keyword("SOME")) {
+            let right = try!(self.parse_quantified_operand());
+            return Ok((Equality::ANY(Box::new(equality)), right));
+        }
+        if quantified && self.eat_keyword("ALL") {
+            let right = try!(self.parse_quantified_operand());
+            return Ok((Equality::ALL(Box::new(equality)), right));
+        }
+        Ok((equality, try!(self.parse_operand())))
+    }
+
+    /// the subquery or the array in `ANY (..)`
+    fn parse_quantified_operand(&mut self) -> Result<Operand, Error> {
+        try!(self.expect_symbol("("));
+        let operand = if self.is_keyword("SELECT") {
+            Operand::QuerySource(QuerySource::Query(try!(self.parse_select())))
+        } else {
+            try!(self.parse_operand())
+        };
+        try!(self.expect_symbol(")"));
+        Ok(operand)
+    }
+
+    /// the comma separated operands up to the closing parenthesis
+    fn parse_params(&mut self) -> Result<Vec<Operand>, Error> {
+        let mut params = vec![];
+        if self.eat_symbol(")") {
+            return Ok(params);
+        }
+        params.push(try!(self.parse_operand()));
+        while self.eat_symbol(",") {
+            params.push(try!(self.parse_operand()));
+        }
+        try!(self.expect_symbol(")"));
+        Ok(params)
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, Error> {
+        let mut operand = try!(self.parse_primary());
+        while self.eat_symbol("::") {
+            let data_type = try!(self.parse_data_type(false));
+            operand = cast(operand, data_type);
+        }
+        let arithmetic = ["+", "-", "*", "/", "%", "||"].iter().any(|s| self.is_symbol(s));
+        if arithmetic {
+            return Err(syntax_error(&format!("arithmetic expressions are not supported: `{}`",
+                                             self.peek().unwrap().to_sql())));
+        }
+        Ok(operand)
+    }
+
+    fn parse_primary(&mut self) -> Result<Operand, Error> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected()),
+        };
+        match token {
+            Token::Number(ref n) => {
+                self.pos += 1;
+                number(n)
+            }
+            Token::Symbol(ref s) if s == "-" => {
+                match self.peek_at(1).cloned() {
+                    Some(Token::Number(ref n)) => {
+                        self.pos += 2;
+                        number(&format!("-{}", n))
+                    }
+                    _ => Err(self.unexpected()),
+                }
+            }
+            Token::Str(ref s) => {
+                self.pos += 1;
+                Ok(Operand::Value(Value::String(s.to_owned())))
+            }
+            Token::Symbol(ref s) if s == "*" => {
+                self.pos += 1;
+                Ok(Operand::ColumnName(plain_column("*".to_owned())))
+            }
+            Token::Symbol(ref s) if s == "(" => {
+                self.pos += 1;
+                if self.is_keyword("SELECT") {
+                    let query = try!(self.parse_select());
+                    try!(self.expect_symbol(")"));
+                    return Ok(Operand::QuerySource(QuerySource::Query(query)));
+                }
+                let mut operands = try!(self.parse_params());
+                if operands.len() == 1 {
+                    Ok(operands.remove(0))
+                } else {
+                    Ok(Operand::Vec(operands))
+                }
+            }
+            Token::Word(ref word) if word.eq_ignore_ascii_case("TRUE") => {
+                self.pos += 1;
+                Ok(Operand::Value(Value::Bool(true)))
+            }
+            Token::Word(ref word) if word.eq_ignore_ascii_case("FALSE") => {
+                self.pos += 1;
+                Ok(Operand::Value(Value::Bool(false)))
+            }
+            Token::Word(ref word) if word.eq_ignore_ascii_case("NULL") => {
+                self.pos += 1;
+                Ok(Operand::Raw("NULL".to_owned(), vec![]))
+            }
+            Token::Word(ref word) if word.eq_ignore_ascii_case("CAST") &&
+                                     self.peek_at(1) == Some(&Token::Symbol("(".to_owned())) => {
+                self.pos += 2;
+                let operand = try!(self.parse_operand());
+                try!(self.expect_keyword("AS"));
+                let data_type = try!(self.parse_data_type(true));
+                try!(self.expect_symbol(")"));
+                Ok(cast(operand, data_type))
+            }
+            Token::Word(ref word) if self.peek_at(1) == Some(&Token::Symbol("(".to_owned())) &&
+                                     self.is_identifier() => {
+                self.pos += 2;
+                if self.is_keyword("DISTINCT") {
+                    return Err(syntax_error("DISTINCT in aggregate functions is not supported"));
+                }
+                let params = try!(self.parse_params());
+                Ok(Operand::QuerySource(QuerySource::Function(Function::new(&word.to_uppercase(),
+                                                                             params))))
+            }
+            _ => self.parse_column_name(),
+        }
+    }
+
+    /// `column`, `table.column`, `schema.table.column` or `table.*`
+    fn parse_column_name(&mut self) -> Result<Operand, Error> {
+        let mut parts = vec![try!(self.identifier())];
+        while self.eat_symbol(".") {
+            if self.eat_symbol("*") {
+                parts.push("*".to_owned());
+                break;
+            }
+            parts.push(try!(self.identifier()));
+        }
+        let column = parts.pop().unwrap();
+        let table = parts.pop();
+        let schema = parts.pop();
+        if !parts.is_empty() {
+            return Err(syntax_error(&format!("too many qualifiers in column {}", column)));
+        }
+        Ok(Operand::ColumnName(ColumnName {
+            column: column,
+            table: table,
+            schema: schema,
+        }))
+    }
+
+    /// the type name in `CAST(x AS type)` or `x::type`, ie: `TEXT`, `VARCHAR(10)`,
+    /// the type can have more than 1 word only in CAST, ie: `DOUBLE PRECISION`
+    fn parse_data_type(&mut self, multi_word: bool) -> Result<String, Error> {
+        let mut words = vec![try!(self.identifier()).to_uppercase()];
+        while multi_word && self.is_identifier() {
+            words.push(try!(self.identifier()).to_uppercase());
+        }
+        let mut data_type = words.join(" ");
+        if self.eat_symbol("(") {
+            let mut sizes = vec![try!(self.usize_literal()).to_string()];
+            while self.eat_symbol(",") {
+                sizes.push(try!(self.usize_literal()).to_string());
+            }
+            try!(self.expect_symbol(")"));
+            data_type = format!("{}({})", data_type, sizes.join(","));
+        }
+        Ok(data_type)
+    }
+
+    fn parse_insert(&mut self) -> Result<Insert, Error> {
+        try!(self.expect_keyword("INSERT"));
+        try!(self.expect_keyword("INTO"));
+        let mut insert = Insert::into(&try!(self.parse_table_name()));
+        if self.is_symbol("(") {
+            insert.columns = try!(self.column_names());
+        }
+        if self.eat_keyword("VALUES") {
+            let mut rows = vec![];
+            loop {
+                try!(self.expect_symbol("("));
+                rows.push(try!(self.parse_params()));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+            insert.data = if rows.len() == 1 {
+                Data::Values(rows.remove(0))
+            } else {
+                Data::Rows(rows)
+            };
+        } else if self.is_keyword("SELECT") {
+            insert.data = Data::Query(try!(self.parse_select()));
+        } else {
+            return Err(self.unexpected());
+        }
+        if self.eat_keyword("ON") {
+            try!(self.expect_keyword("CONFLICT"));
+            let mut on_conflict = OnConflict::new(vec![]);
+            if self.is_symbol("(") {
+                on_conflict.columns = try!(self.column_names());
+            }
+            try!(self.expect_keyword("DO"));
+            if !self.eat_keyword("NOTHING") {
+                try!(self.expect_keyword("UPDATE"));
+                try!(self.expect_keyword("SET"));
+                let (columns, values) = try!(self.parse_assignments());
+                on_conflict.action = ConflictAction::DoUpdate {
+                    columns: columns,
+                    values: values,
+                };
+            }
+            insert.on_conflict = Some(on_conflict);
+        }
+        insert.return_columns = try!(self.parse_returning());
+        Ok(insert)
+    }
+
+    /// `a = 1, b = EXCLUDED.b`
+    fn parse_assignments(&mut self) -> Result<(Vec<ColumnName>, Vec<Operand>), Error> {
+        let mut columns = vec![];
+        let mut values = vec![];
+        loop {
+            columns.push(plain_column(try!(self.identifier())));
+            try!(self.expect_symbol("="));
+            values.push(try!(self.parse_operand()));
+            if !self.eat_symbol(",") {
+                break;
+            }
+        }
+        Ok((columns, values))
+    }
+
+    fn parse_returning(&mut self) -> Result<Vec<ColumnName>, Error> {
+        if !self.eat_keyword("RETURNING") {
+            return Ok(vec![]);
+        }
+        if self.eat_symbol("*") {
+            return Ok(vec![plain_column("*".to_owned())]);
+        }
+        Ok(try!(self.identifiers()).into_iter().map(plain_column).collect())
+    }
+
+    fn parse_update(&mut self) -> Result<Update, Error> {
+        try!(self.expect_keyword("UPDATE"));
+        let mut update = Update::table(&try!(self.parse_table_name()));
+        try!(self.expect_keyword("SET"));
+        let (columns, values) = try!(self.parse_assignments());
+        update.columns = columns;
+        update.values = values;
+        if self.eat_keyword("WHERE") {
+            update.filters = try!(self.parse_or()).into_filters();
+        }
+        update.return_columns = try!(self.parse_returning());
+        Ok(update)
+    }
+
+    fn parse_delete(&mut self) -> Result<Delete, Error> {
+        try!(self.expect_keyword("DELETE"));
+        try!(self.expect_keyword("FROM"));
+        let mut delete = Delete::from(&try!(self.parse_table_name()));
+        if self.eat_keyword("WHERE") {
+            delete.filters = try!(self.parse_or()).into_filters();
+        }
+        delete.return_columns = try!(self.parse_returning());
+        Ok(delete)
+    }
+}
+
+fn equality_is_in(equality: &Equality) -> bool {
+    match *equality {
+        Equality::IN | Equality::NOT_IN => true,
+        _ => false,
+    }
+}
+
+fn plain_column(column: String) -> ColumnName {
+    ColumnName {
+        column: column,
+        table: None,
+        schema: None,
+    }
+}
+
+fn cast(operand: Operand, data_type: String) -> Operand {
+    let params = vec![operand, Operand::Value(Value::String(data_type))];
+    Operand::QuerySource(QuerySource::Function(Function::new("CAST", params)))
+}
+
+/// integers are i32 when they fit, otherwise i64, the rest are f64
+fn number(n: &str) -> Result<Operand, Error> {
+    let value = if let Ok(i) = n.parse::<i32>() {
+        Value::I32(i)
+    } else if let Ok(i) = n.parse::<i64>() {
+        Value::I64(i)
+    } else if let Ok(f) = n.parse::<f64>() {
+        Value::F64(f)
+    } else {
+        return Err(syntax_error(&format!("invalid number {}", n)));
+    };
+    Ok(Operand::Value(value))
+}
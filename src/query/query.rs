@@ -1,6 +1,7 @@
 use query::Operand;
 use query::operand::ToOperand;
 use dao::ToValue;
+use dao::Value;
 use database::Database;
 use dao::DaoResult;
 use dao::IsDao;
@@ -8,6 +9,7 @@ use dao::Dao;
 use writer::SqlFrag;
 use database::DbError;
 use database::BuildMode;
+use dialect::Dialect;
 use query::ColumnName;
 use query::column_name::ToColumnName;
 use query::{TableName, ToTableName};
@@ -15,6 +17,8 @@ use query::{Filter, Equality};
 //use query::QueryBuilder;
 use query::Function;
 use query::Join;
+use query::{Lock, LockMode};
+use query::{Keyset, Direction};
 use query::Order;
 use query::Field;
 use query::SourceField;
@@ -42,6 +46,7 @@ pub trait IsQuery{
 
 
 /// Query Error
+#[derive(Debug)]
 pub enum Error {
     NoTableSpecified(String),
     NoColumnSpecified(String),
@@ -82,7 +87,34 @@ pub struct DeclaredQuery{
     pub name: String,
     pub fields: Vec<String>,
     pub query: Select,
-    pub is_recursive: bool
+    pub is_recursive: bool,
+    /// the recursive term of a recursive query, appended to the query with UNION ALL
+    pub recursive_query: Option<Select>,
+}
+
+impl DeclaredQuery {
+    /// a query declared with `WITH name AS (query)`
+    pub fn new(name: &str, query: Select) -> Self {
+        DeclaredQuery {
+            name: name.to_owned(),
+            fields: vec![],
+            query: query,
+            is_recursive: false,
+            recursive_query: None,
+        }
+    }
+
+    /// `WITH RECURSIVE name AS (query UNION ALL recursive_query)`,
+    /// the recursive query refers to the rows produced so far by the name of this declared query
+    pub fn recursive(name: &str, query: Select, recursive_query: Select) -> Self {
+        DeclaredQuery {
+            name: name.to_owned(),
+            fields: vec![],
+            query: query,
+            is_recursive: true,
+            recursive_query: Some(recursive_query),
+        }
+    }
 }
 
 
@@ -140,6 +172,12 @@ pub struct Select {
 
     /// enable query stats
     pub enable_query_stat: bool,
+
+    /// lock the selected rows, FOR UPDATE | FOR SHARE
+    pub lock: Option<Lock>,
+
+    /// seek the page with the ordering columns instead of OFFSET
+    pub keyset: Option<Keyset>,
 }
 
 impl Select {
@@ -162,6 +200,8 @@ impl Select {
             values: vec![],
             enumerated_returns: vec![],
             enable_query_stat: true,
+            lock: None,
+            keyset: None,
         }
     }
 
@@ -220,6 +260,13 @@ impl Select {
         }
     }
 
+    /// select only the distinct records
+    pub fn distinct(&mut self) {
+        self.distinct = true;
+    }
+
+    /// select only the first record of each distinct set of the columns,
+    /// the ORDER BY determines which record is the first
     pub fn distinct_on_columns(&mut self, columns: &Vec<String>) {
         let columns = columns.clone();
         for c in columns {
@@ -243,6 +290,65 @@ impl Select {
         self.range.to_owned()
     }
 
+    /// paginate by seeking the rows after the last row of the previous page,
+    /// the records are ordered by the columns, which should be unique when combined.
+    /// The total count is not computed in this mode, the next page is retrieved with the cursor of the result
+    pub fn keyset(&mut self, columns: Vec<&str>, direction: Direction, page_size: usize) {
+        let columns: Vec<ColumnName> = columns.iter().map(|c| c.to_column_name()).collect();
+        for c in &columns {
+            self.order_by.push(Order {
+                operand: Operand::ColumnName(c.to_owned()),
+                direction: Some(direction.to_owned()),
+                nulls_where: None,
+            });
+        }
+        self.set_limit(page_size);
+        self.keyset = Some(Keyset {
+            columns: columns,
+            direction: direction,
+            after: vec![],
+        });
+    }
+
+    /// the values of the keyset columns of the last row of the previous page
+    pub fn after(&mut self, last_values: Vec<Value>) -> Result<(), DbError> {
+        match self.keyset {
+            Some(ref mut keyset) => {
+                if keyset.columns.len() != last_values.len() {
+                    return Err(DbError::new("The values should match the keyset columns"));
+                }
+                keyset.after = last_values;
+                Ok(())
+            }
+            None => Err(DbError::new("Specify the keyset columns first")),
+        }
+    }
+
+    /// continue from the cursor returned with the previous page
+    pub fn after_cursor(&mut self, cursor: &str) -> Result<(), DbError> {
+        let values = try!(Keyset::decode_cursor(cursor));
+        match self.keyset {
+            Some(ref keyset) if keyset.columns.len() != values.len() => {
+                return Err(DbError::new("The cursor does not match the keyset columns"))
+            }
+            _ => (),
+        }
+        self.after(values)
+    }
+
+    /// lock the selected rows from being modified by other transactions,
+    /// `query.for_update().skip_locked()`
+    pub fn for_update(&mut self) -> &mut Lock {
+        self.lock = Some(Lock::new(LockMode::UPDATE));
+        self.lock.as_mut().unwrap()
+    }
+
+    /// lock the selected rows from being modified, but can still be read by other transactions
+    pub fn for_share(&mut self) -> &mut Lock {
+        self.lock = Some(Lock::new(LockMode::SHARE));
+        self.lock.as_mut().unwrap()
+    }
+
     /// enumerate only the columns that is coming from this table
     /// this will invalidate enumerate_all
     pub fn only_from(&mut self, table: &ToTableName) {
@@ -264,6 +370,20 @@ impl Select {
         self.from.push(sf);
     }
 
+    /// a raw sql to select from, ie: `generate_series(1, ?)`
+    pub fn from_raw(&mut self, sql: &str, params: Vec<Value>, alias: &str) {
+        let sf = SourceField {
+            source: QuerySource::Raw(sql.to_owned(), params),
+            rename: Some(alias.to_owned()),
+        };
+        self.from.push(sf);
+    }
+
+    /// declare a query with WITH, which can be selected from by its name
+    pub fn with(&mut self, declared_query: DeclaredQuery) {
+        self.declared_query.push(declared_query);
+    }
+
     pub fn from(&mut self, to_source_field: &ToSourceField) {
         self.from.append(&mut to_source_field.to_source_field());
     }
@@ -312,18 +432,35 @@ impl Select {
 
     pub fn get_involved_tables(&self) -> Vec<TableName> {
         let mut tables = vec![];
-        let from_table = self.get_from_table();
-        if let Some(from) = from_table {
-            tables.push(from.clone());
-        }
-        for j in &self.joins {
-            if !tables.contains(&&j.table_name) {
-                tables.push(j.table_name.clone());
+        for (table_name, _) in self.get_involved_sources() {
+            if !tables.contains(&table_name) {
+                tables.push(table_name);
             }
         }
         tables
     }
 
+    /// the tables in the from clause and the joins together with their alias,
+    /// the same table joined with different aliases are different sources
+    fn get_involved_sources(&self) -> Vec<(TableName, Option<String>)> {
+        let mut sources = vec![];
+        let source_fields = self.from.iter().chain(self.joins.iter().map(|j| &j.source));
+        for sf in source_fields {
+            if let QuerySource::TableName(ref table_name) = sf.source {
+                let source = (table_name.to_owned(), sf.rename.to_owned());
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+        sources
+    }
+
+    /// join a table, subquery or function
+    pub fn join(&mut self, join: Join) {
+        self.joins.push(join);
+    }
+
     /// preprocess the missing fields of the query,
     /// such as mentioning the columns of the from_table
     /// enumerate the columns of the involved tables
@@ -333,11 +470,11 @@ impl Select {
     /// do a select all
     pub fn finalize(&mut self) -> &Self {
 
-        let involved_models = self.get_involved_tables();
-        if involved_models.len() > 1 {
+        let involved_sources = self.get_involved_sources();
+        if involved_sources.len() > 1 {
             // enumerate all columns when there is a join
             if self.enumerate_all {
-                self.enumerate_involved_tables_columns(&involved_models);
+                self.enumerate_involved_tables_columns(&involved_sources);
             }
             self.rename_conflicting_columns(); // rename an enumerated columns that conflicts
         }
@@ -351,10 +488,20 @@ impl Select {
         self
     }
 
-    fn enumerate_involved_tables_columns(&mut self, involved_models: &Vec<TableName>) {
-        for m in involved_models {
-            for c in &m.columns {
-                self.enumerate(c.clone());
+    /// the columns of aliased tables are qualified with the alias
+    fn enumerate_involved_tables_columns(&mut self, involved_sources: &Vec<(TableName, Option<String>)>) {
+        for &(ref table_name, ref alias) in involved_sources {
+            for c in &table_name.columns {
+                match *alias {
+                    Some(ref alias) => {
+                        self.enumerate(ColumnName {
+                            column: c.column.to_owned(),
+                            table: Some(alias.to_owned()),
+                            schema: None,
+                        });
+                    }
+                    None => self.enumerate(c.clone()),
+                }
             }
         }
     }
@@ -526,31 +673,146 @@ impl Select {
             None => Err(DbError::new("No entry to collect found.")),
         }
     }
+
+    /// execute the query, then convert the result into a struct that is not a table,
+    /// useful for the reports of GROUP BY queries, the aggregates should be renamed
+    /// to the fields of the struct, ie: `COUNT(&"*")` as `orders`
+    pub fn collect_as<T: IsDao>(&mut self, db: &Database) -> Result<Vec<T>, DbError> {
+        let result = try!(self.retrieve_without_stat(db));
+        Ok(result.cast_as())
+    }
+
+    /// execute the query then collect only 1 record into a struct that is not a table
+    pub fn collect_one_as<T: IsDao>(&mut self, db: &Database) -> Result<T, DbError> {
+        let result = try!(self.retrieve_without_stat(db));
+        match result.cast_one_as() {
+            Some(res) => Ok(res),
+            None => Err(DbError::new("No entry to collect found.")),
+        }
+    }
+
+    /// the structs have no use of the total of the records,
+    /// so the COUNT(*) of the query stat is not executed
+    fn retrieve_without_stat(&mut self, db: &Database) -> Result<DaoResult, DbError> {
+        let enable_query_stat = self.enable_query_stat;
+        self.enable_query_stat = false;
+        let result = self.retrieve(db);
+        self.enable_query_stat = enable_query_stat;
+        result
+    }
+}
+
+impl Select {
+    /// build the sql with the dialect, no database connection is needed
+    pub fn build_with(&self, dialect: &Dialect) -> SqlFrag {
+        let mut query = self.clone();
+        query.finalize();
+        dialect.build_select(&query, &BuildMode::Standard)
+    }
 }
 
 impl IsQuery for Select{
     
     fn build(&mut self, db: &Database) -> SqlFrag{
         self.finalize();
-        db.build_select(self, &BuildMode::Standard) 
+        db.dialect().build_select(self, &BuildMode::Standard) 
     }
     
     fn debug_build(&mut self, db: &Database) -> SqlFrag{
         self.finalize();
-        db.build_select(self, &BuildMode::Debug) 
+        db.dialect().build_select(self, &BuildMode::Debug) 
     }
 }
  
+ #[derive(Clone)]
  pub enum Data{
     Values(Vec<Operand>),
+    /// multiple rows of values, inserted in one statement
+    Rows(Vec<Vec<Operand>>),
     Query(Select),
  }
 
+#[derive(Clone)]
 pub struct Insert{
     pub into: TableName,
     pub columns: Vec<ColumnName>,
     pub data: Data,
-    pub return_columns: Vec<ColumnName>
+    pub return_columns: Vec<ColumnName>,
+    /// what to do when the inserted row conflicts with an existing row
+    pub on_conflict: Option<OnConflict>,
+}
+
+/// the table alias of the row proposed for insertion in the ON CONFLICT clause
+pub const EXCLUDED: &'static str = "EXCLUDED";
+
+/// the value of the column in the row that was proposed for insertion,
+/// used when updating the conflicting row, ie: `SET price = EXCLUDED.price`
+pub fn excluded(column: &str) -> Operand {
+    Operand::ColumnName(ColumnName {
+        column: column.to_owned(),
+        table: Some(EXCLUDED.to_owned()),
+        schema: None,
+    })
+}
+
+#[derive(Clone)]
+pub enum ConflictAction {
+    DoNothing,
+    /// update the conflicting row, setting the columns to the values
+    DoUpdate {
+        columns: Vec<ColumnName>,
+        values: Vec<Operand>,
+    },
+}
+
+/// ON CONFLICT in postgresql and sqlite,
+/// ON DUPLICATE KEY UPDATE in mysql
+#[derive(Clone)]
+pub struct OnConflict {
+    /// the unique columns where the conflict is checked,
+    /// mysql uses all the unique keys of the table instead
+    pub columns: Vec<ColumnName>,
+    pub action: ConflictAction,
+}
+
+impl OnConflict {
+    pub fn new(columns: Vec<&str>) -> Self {
+        OnConflict {
+            columns: columns.iter().map(|c| c.to_column_name()).collect(),
+            action: ConflictAction::DoNothing,
+        }
+    }
+
+    /// leave the existing row as is, the existing row is not returned by the RETURNING clause.
+    /// In mysql this is emulated by setting a conflict column to itself, which is still an update:
+    /// the existing row is locked and is counted as an affected row when the connection uses CLIENT_FOUND_ROWS
+    pub fn do_nothing(&mut self) -> &mut Self {
+        self.action = ConflictAction::DoNothing;
+        self
+    }
+
+    /// update the columns of the existing row with the values of the row being inserted
+    pub fn do_update(&mut self, columns: Vec<&str>) -> &mut Self {
+        for c in columns {
+            self.set(c, &excluded(c));
+        }
+        self
+    }
+
+    /// set the column of the existing row to a value
+    pub fn set(&mut self, column: &str, value: &ToOperand) -> &mut Self {
+        if let ConflictAction::DoNothing = self.action {
+            self.action = ConflictAction::DoUpdate {
+                columns: vec![],
+                values: vec![],
+            };
+        }
+        if let ConflictAction::DoUpdate { ref mut columns, ref mut values } = self.action {
+            columns.push(column.to_column_name());
+            values.push(value.to_operand());
+        }
+        self
+    }
 }
 
 impl Insert{
@@ -560,10 +822,18 @@ impl Insert{
             into: table.to_table_name(),
             columns: vec![],
             data: Data::Values(vec![]),
-            return_columns: vec![]
+            return_columns: vec![],
+            on_conflict: None,
         }
     }
 
+    /// specify the unique columns that may conflict with existing records,
+    /// then what to do: `insert.on_conflict(vec!["id"]).do_update(vec!["name"])`
+    pub fn on_conflict(&mut self, columns: Vec<&str>) -> &mut OnConflict {
+        self.on_conflict = Some(OnConflict::new(columns));
+        self.on_conflict.as_mut().unwrap()
+    }
+
     pub fn columns(&mut self, columns: Vec<&str>){
         for c in columns{
             self.column(c);
@@ -585,10 +855,62 @@ impl Insert{
             Data::Values(ref mut values) => {
                 (*values).push(operand.to_operand());
             },
+            Data::Rows(_) => panic!("use row to add values to a multiple rows insert"),
             Data::Query(_) => panic!("can not add value to query")
         }
     }
 
+    /// add a row of values, the values added prior becomes the first row
+    pub fn row(&mut self, operands: Vec<&ToOperand>) {
+        let row = operands.iter().map(|op| op.to_operand()).collect();
+        self.push_row(row);
+    }
+
+    /// insert multiple rows in one statement
+    pub fn rows(&mut self, rows: Vec<Vec<Value>>) {
+        for row in rows {
+            self.push_row(row.into_iter().map(|v| Operand::Value(v)).collect());
+        }
+    }
+
+    fn push_row(&mut self, row: Vec<Operand>) {
+        let data = match self.data {
+            Data::Values(ref mut values) => {
+                let mut rows = vec![];
+                if !values.is_empty() {
+                    rows.push(values.clone());
+                }
+                rows.push(row);
+                Data::Rows(rows)
+            }
+            Data::Rows(ref mut rows) => {
+                rows.push(row);
+                return;
+            }
+            Data::Query(_) => panic!("can not add rows to query"),
+        };
+        self.data = data;
+    }
+
+    /// split the rows into several inserts,
+    /// each having at most `max_parameters` values
+    pub fn batches(&self, max_parameters: usize) -> Vec<Insert> {
+        match self.data {
+            Data::Rows(ref rows) => {
+                let row_len = if self.columns.is_empty() { 1 } else { self.columns.len() };
+                let max_rows = ::std::cmp::max(1, max_parameters / row_len);
+                rows.chunks(max_rows)
+                    .map(|chunk| {
+                        let mut batch = self.clone();
+                        batch.data = Data::Rows(chunk.to_vec());
+                        batch
+                    })
+                    .collect()
+            }
+            _ => vec![self.clone()],
+        }
+    }
+
     pub fn return_columns(&mut self, columns: Vec<&str>){
         self.return_columns.clear();
         for c in columns{
@@ -605,7 +927,12 @@ impl Insert{
     }
 
     pub fn debug_build(&mut self, db: &Database) -> SqlFrag {
-        db.build_insert(&self, &BuildMode::Debug)
+        db.dialect().build_insert(&self, &BuildMode::Debug)
+    }
+
+    /// build the sql with the dialect, no database connection is needed
+    pub fn build_with(&self, dialect: &Dialect) -> SqlFrag {
+        dialect.build_insert(self, &BuildMode::Standard)
     }
 
     pub fn insert<D: IsDao>(&mut self, db: &Database) -> Result<D, DbError>{
@@ -616,6 +943,13 @@ impl Insert{
         }
     }
 
+    /// insert all the rows, split into batches when needed,
+    /// returns the inserted records when the database supports the RETURNING clause
+    pub fn insert_all<D: IsDao>(&mut self, db: &Database) -> Result<Vec<D>, DbError>{
+        let result = try!(db.insert_all(self));
+        Ok(result.iter().map(|dao| D::from_dao(dao)).collect())
+    }
+
     /// set a value of a column when inserting/updating records
     pub fn set(&mut self, column: &str, value: &ToValue) {
         self.column(&column);
@@ -623,12 +957,15 @@ impl Insert{
     }
 }
 
+#[derive(Clone)]
 pub struct Update{
     pub table: TableName,
     pub columns: Vec<ColumnName>,
     pub values: Vec<Operand>,
     pub filters: Vec<Filter>,
-    pub return_columns: Vec<ColumnName>
+    pub return_columns: Vec<ColumnName>,
+    /// the other tables used in the filters and values
+    pub joins: Vec<Join>,
 }
 
 impl Update{
@@ -640,9 +977,16 @@ impl Update{
             values: vec![],
             filters: vec![],
             return_columns: vec![],
+            joins: vec![],
         }
     }
 
+    /// update the records based on the matching records of the joined table,
+    /// `update.join("line_item".ON("line_item.order_id".EQ(&"orders.order_id")))`
+    pub fn join(&mut self, join: Join) {
+        self.joins.push(join);
+    }
+
     pub fn add_filter(&mut self, filter: &Filter){
         self.filters.push(filter.clone());
     }
@@ -672,6 +1016,13 @@ impl Update{
         self.column(&column);
         self.value(&Operand::Value(value.to_db_type()));
     }
+    /// build the sql with the dialect, no database connection is needed
+    pub fn build_with(&self, dialect: &Dialect) -> SqlFrag {
+        dialect.build_update(self, &BuildMode::Standard)
+    }
+
+    /// update the records and return only the first updated record,
+    /// use update_all when the filter matches more than 1 record
     pub fn update<D: IsDao>(&mut self, db: &Database) -> Result<D, DbError>{
         let result = db.update(self);
         match result{
@@ -679,11 +1030,24 @@ impl Update{
             Err(e) => Err(e),
         }
     }
+
+    /// update the records and return all of the updated records
+    pub fn update_all<D: IsDao>(&mut self, db: &Database) -> Result<Vec<D>, DbError>{
+        if self.return_columns.is_empty() {
+            self.return_all();
+        }
+        let result = try!(db.update_all(self));
+        Ok(result.iter().map(|dao| D::from_dao(dao)).collect())
+    }
 }
 
+#[derive(Clone)]
 pub struct Delete{
     pub from_table: TableName,
-    pub filters: Vec<Filter>
+    pub filters: Vec<Filter>,
+    /// the other tables used in the filters
+    pub joins: Vec<Join>,
+    pub return_columns: Vec<ColumnName>,
 }
 
 impl Delete{
@@ -691,10 +1055,31 @@ impl Delete{
     pub fn from(table: &ToTableName) -> Self{
         Delete{
             from_table: table.to_table_name(),
-            filters: vec![]
+            filters: vec![],
+            joins: vec![],
+            return_columns: vec![],
         }
     }
 
+    /// build the sql with the dialect, no database connection is needed
+    pub fn build_with(&self, dialect: &Dialect) -> SqlFrag {
+        dialect.build_delete(self, &BuildMode::Standard)
+    }
+
+    /// the columns of the deleted records to return
+    pub fn returns(&mut self, columns: Vec<&str>) {
+        self.return_columns = columns.iter().map(|c| c.to_column_name()).collect();
+    }
+
+    pub fn return_all(&mut self) {
+        self.return_columns = vec![ColumnName::from("*")];
+    }
+
+    /// delete the records that has matching records in the joined table
+    pub fn join(&mut self, join: Join) {
+        self.joins.push(join);
+    }
+
     pub fn add_filter(&mut self, filter: &Filter){
         self.filters.push(filter.clone())
     }
@@ -705,15 +1090,25 @@ impl Delete{
     pub fn execute(&self, db: &Database) -> Result<usize, DbError> {
         db.delete(self)
     }
+
+    /// delete the records and return them, all the columns are returned
+    /// when no return columns is specified
+    pub fn delete<D: IsDao>(&mut self, db: &Database) -> Result<Vec<D>, DbError> {
+        if self.return_columns.is_empty() {
+            self.return_all();
+        }
+        let result = try!(db.delete_with_return(self));
+        Ok(result.iter().map(|dao| D::from_dao(dao)).collect())
+    }
 }
 
 impl IsQuery for Delete{
     fn build(&mut self, db: &Database) -> SqlFrag{
-        db.build_delete(self, &BuildMode::Standard)
+        db.dialect().build_delete(self, &BuildMode::Standard)
     }
 
     fn debug_build(&mut self, db: &Database) -> SqlFrag {
-        db.build_delete(self, &BuildMode::Debug)
+        db.dialect().build_delete(self, &BuildMode::Debug)
     }
 }
 
@@ -0,0 +1,227 @@
+use url::form_urlencoded;
+use uuid::Uuid;
+use chrono::datetime::DateTime;
+use rustc_serialize::json::Json;
+use rustc_serialize::base64::FromBase64;
+use dao::{Type, Value};
+use database::DbError;
+use table::{Table, Column};
+use query::{Select, Filter, Condition, Equality, Connector, Operand};
+use query::{Order, Direction, NullsWhere, ToColumnName};
+
+/// the parameters that are not column filters
+const RESERVED: &'static [&'static str] = &["select", "order", "limit", "offset"];
+
+/// build the select query of the table from the url query string in the style of PostgREST,
+/// ie: `name=ilike.*phone*&price=gte.100&order=created.desc&limit=20&offset=40`.
+///
+/// - `column=op.value` filters the records, the op is one of
+///   `eq`, `neq`, `gt`, `gte`, `lt`, `lte`, `like`, `ilike`, `in` and `is`,
+///   prefixed with `not.` to negate it, ie: `status=not.in.(closed,archived)`, `deleted=is.null`
+/// - `select=name,price` the columns to return, all the columns when omitted
+/// - `order=created.desc.nullslast,name` the ordering of the records
+/// - `limit=20&offset=40` the range of the records
+///
+/// The columns are checked against the columns of the table,
+/// and the values are converted to the data type of the column.
+pub fn from_query_string(table: &Table, query_string: &str) -> Result<Select, DbError> {
+    let query_string = query_string.trim_left_matches('?');
+    let mut select = Select::new();
+    select.from(table);
+    for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+        match &*key {
+            "select" => {
+                for column in value.split(',') {
+                    let column = try!(get_column(table, column.trim()));
+                    select.column(&column.name);
+                }
+            }
+            "order" => {
+                for order in value.split(',') {
+                    let order = try!(parse_order(table, order.trim()));
+                    select.order_by.push(order);
+                }
+            }
+            "limit" => select.set_limit(try!(parse_usize(&key, &value))),
+            "offset" => select.set_offset(try!(parse_usize(&key, &value))),
+            _ => {
+                let column = try!(get_column(table, &key));
+                let filter = try!(parse_filter(&column, &value));
+                select.add_filter(&filter);
+            }
+        }
+    }
+    if select.enumerated_fields.is_empty() {
+        select.column("*");
+    }
+    Ok(select)
+}
+
+fn get_column(table: &Table, name: &str) -> Result<Column, DbError> {
+    if RESERVED.contains(&name) {
+        return Err(DbError::new(&format!("`{}` can not be used as a column", name)));
+    }
+    match table.get_column(name) {
+        Some(column) => Ok(column),
+        None => Err(DbError::new(&format!("table `{}` has no column `{}`", table, name))),
+    }
+}
+
+fn parse_usize(key: &str, value: &str) -> Result<usize, DbError> {
+    match value.parse::<usize>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(DbError::new(&format!("invalid {}: `{}`", key, value))),
+    }
+}
+
+/// `column`, `column.asc`, `column.desc.nullsfirst`
+fn parse_order(table: &Table, order: &str) -> Result<Order, DbError> {
+    let mut parts = order.split('.');
+    let name = parts.next().unwrap_or("");
+    let column = try!(get_column(table, name));
+    let mut direction = None;
+    let mut nulls_where = None;
+    for part in parts {
+        match part {
+            "asc" if direction.is_none() => direction = Some(Direction::ASC),
+            "desc" if direction.is_none() => direction = Some(Direction::DESC),
+            "nullsfirst" if nulls_where.is_none() => nulls_where = Some(NullsWhere::FIRST),
+            "nullslast" if nulls_where.is_none() => nulls_where = Some(NullsWhere::LAST),
+            _ => return Err(DbError::new(&format!("invalid order: `{}`", order))),
+        }
+    }
+    Ok(Order {
+        operand: Operand::ColumnName(column.name.to_column_name()),
+        direction: direction,
+        nulls_where: nulls_where,
+    })
+}
+
+/// `op.value` or `not.op.value`
+fn parse_filter(column: &Column, value: &str) -> Result<Filter, DbError> {
+    let (negated, value) = if value.starts_with("not.") {
+        (true, &value[4..])
+    } else {
+        (false, value)
+    };
+    let (op, operand) = match value.find('.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => return Err(DbError::new(&format!("missing operator in `{}={}`", column.name, value))),
+    };
+    let (equality, right) = match (op, negated) {
+        ("eq", false) => (Equality::EQ, try!(to_operand(column, operand))),
+        ("eq", true) | ("neq", false) => (Equality::NEQ, try!(to_operand(column, operand))),
+        ("neq", true) => (Equality::EQ, try!(to_operand(column, operand))),
+        ("gt", false) => (Equality::GT, try!(to_operand(column, operand))),
+        ("gt", true) => (Equality::LTE, try!(to_operand(column, operand))),
+        ("gte", false) => (Equality::GTE, try!(to_operand(column, operand))),
+        ("gte", true) => (Equality::LT, try!(to_operand(column, operand))),
+        ("lt", false) => (Equality::LT, try!(to_operand(column, operand))),
+        ("lt", true) => (Equality::GTE, try!(to_operand(column, operand))),
+        ("lte", false) => (Equality::LTE, try!(to_operand(column, operand))),
+        ("lte", true) => (Equality::GT, try!(to_operand(column, operand))),
+        ("like", false) => (Equality::LIKE, try!(to_pattern(column, operand))),
+        ("like", true) => (Equality::NOT_LIKE, try!(to_pattern(column, operand))),
+        ("ilike", false) => (Equality::ILIKE, try!(to_pattern(column, operand))),
+        ("ilike", true) => (Equality::NOT_ILIKE, try!(to_pattern(column, operand))),
+        ("in", false) => (Equality::IN, try!(to_list(column, operand))),
+        ("in", true) => (Equality::NOT_IN, try!(to_list(column, operand))),
+        ("is", _) => {
+            match (operand, negated) {
+                ("null", false) => (Equality::IS_NULL, Operand::None),
+                ("null", true) => (Equality::IS_NOT_NULL, Operand::None),
+                ("true", _) | ("false", _) => {
+                    let equality = if negated { Equality::IS_DISTINCT_FROM } else { Equality::IS_NOT_DISTINCT_FROM };
+                    (equality, try!(to_operand(column, operand)))
+                }
+                _ => return Err(DbError::new(&format!("invalid value for is: `{}`", operand))),
+            }
+        }
+        _ => return Err(DbError::new(&format!("unknown operator `{}` in `{}`", op, column.name))),
+    };
+    Ok(Filter {
+        connector: Connector::And,
+        condition: Condition {
+            left: Operand::ColumnName(column.name.to_column_name()),
+            equality: equality,
+            right: right,
+        },
+        sub_filters: vec![],
+    })
+}
+
+fn to_operand(column: &Column, text: &str) -> Result<Operand, DbError> {
+    let value = try!(to_value(column, text));
+    Ok(Operand::Value(value))
+}
+
+/// the `*` in the pattern is the wildcard `%` of sql, since `%` has to be escaped in the url
+fn to_pattern(column: &Column, text: &str) -> Result<Operand, DbError> {
+    match column.data_type {
+        Type::String => Ok(Operand::Value(Value::String(text.replace("*", "%")))),
+        _ => Err(DbError::new(&format!("column `{}` is not a text, it can not be matched with a pattern", column.name))),
+    }
+}
+
+/// `(a,b,"c,d")`, the items can be double quoted when they contain a comma
+fn to_list(column: &Column, text: &str) -> Result<Operand, DbError> {
+    if !text.starts_with('(') || !text.ends_with(')') || text.len() < 2 {
+        return Err(DbError::new(&format!("the list of `{}` should be enclosed in parenthesis", column.name)));
+    }
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut quoted = false;
+    for c in text[1..text.len() - 1].chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(item.trim().to_owned());
+                item = String::new();
+            }
+            _ => item.push(c),
+        }
+    }
+    items.push(item.trim().to_owned());
+    let mut operands = vec![];
+    for item in items {
+        operands.push(try!(to_operand(column, &item)));
+    }
+    Ok(Operand::Vec(operands))
+}
+
+/// convert the text into the value of the data type of the column
+fn to_value(column: &Column, text: &str) -> Result<Value, DbError> {
+    let value = match column.data_type {
+        Type::Bool => {
+            match text {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            }
+        }
+        Type::I8 => text.parse().ok().map(Value::I8),
+        Type::I16 => text.parse().ok().map(Value::I16),
+        Type::I32 => text.parse().ok().map(Value::I32),
+        Type::I64 => text.parse().ok().map(Value::I64),
+        Type::U8 => text.parse().ok().map(Value::U8),
+        Type::U16 => text.parse().ok().map(Value::U16),
+        Type::U32 => text.parse().ok().map(Value::U32),
+        Type::U64 => text.parse().ok().map(Value::U64),
+        Type::F32 => text.parse().ok().map(Value::F32),
+        Type::F64 => text.parse().ok().map(Value::F64),
+        Type::String => Some(Value::String(text.to_owned())),
+        Type::VecU8 => text.from_base64().ok().map(Value::VecU8),
+        Type::Json => Json::from_str(text).ok().map(Value::Json),
+        Type::Uuid => Uuid::parse_str(text).ok().map(Value::Uuid),
+        Type::DateTime => DateTime::parse_from_rfc3339(text).ok().map(Value::DateTime),
+    };
+    match value {
+        Some(value) => Ok(value),
+        None => {
+            Err(DbError::new(&format!("invalid value `{}` for column `{}` of type {:?}",
+                                      text,
+                                      column.name,
+                                      column.data_type)))
+        }
+    }
+}
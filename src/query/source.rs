@@ -3,6 +3,7 @@ use query::Select;
 use query::Function;
 use query::table_name::ToTableName;
 use table::Table;
+use dao::Value;
 
 
 /// This fields can be used in the FROM field
@@ -12,6 +13,8 @@ pub enum QuerySource {
     TableName(TableName),
     Query(Select),
     Function(Function),
+    /// raw sql with `?` placeholders for the values
+    Raw(String, Vec<Value>),
 }
 
 /// QuerySource fields can be renamed
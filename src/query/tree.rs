@@ -0,0 +1,127 @@
+use query::{Select, DeclaredQuery, TableName, ColumnName, Operand, Field};
+use query::{HasEquality, HasDirection};
+use query::join::ToJoin;
+use query::function::{CAST, CONCAT};
+use table::{Table, Column};
+use dao::Value;
+
+/// the name of the recursive query the rows of the tree are selected from
+pub const TREE: &'static str = "tree";
+/// the number of levels from the starting node, the starting node is at depth 0
+pub const TREE_DEPTH: &'static str = "tree_depth";
+/// the keys of the nodes from the starting node separated with `/`, ie: `1/4/9`
+pub const TREE_PATH: &'static str = "tree_path";
+
+/// the direction the tree is traversed from the starting node
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Traversal {
+    /// the children of the node, their children and so on
+    DESCENDANTS,
+    /// the parent of the node, its parent and so on up to the root
+    ANCESTORS,
+}
+
+/// the column referring to the parent row, and the column of the parent it refers to,
+/// the table should be in the tables together with the other tables it refers to
+pub fn self_reference<'a>(table: &'a Table, tables: &'a [Table]) -> Option<(&'a Column, &'a str)> {
+    table.referred_tables(tables)
+        .into_iter()
+        .find(|&(_, referred)| referred == table)
+        .and_then(|(column, _)| column.foreign.as_ref().map(|f| (column, &f.column[..])))
+}
+
+/// select the nodes of the tree starting from the node with the `id` key,
+/// together with their depth and path from the starting node.
+/// The nodes are linked by the `parent` column referring to the `key` column of the same table,
+/// the traversal stops at `max_depth` levels so that cycles in the data will not recurse endlessly.
+/// The nodes are ordered by their depth then their path, the path is a text so `1/10` is before `1/2`
+/// within the same depth.
+///
+/// ```sql
+/// WITH RECURSIVE tree AS (
+///      SELECT category.category_id, category.parent_id, 0 AS tree_depth, CAST(category.category_id AS TEXT) AS tree_path
+///        FROM category
+///       WHERE category.category_id = $1
+///   UNION ALL
+///      SELECT category.category_id, category.parent_id, tree.tree_depth + 1, CONCAT(tree.tree_path, '/', category.category_id)
+///        FROM category
+///        JOIN tree ON category.parent_id = tree.category_id
+///       WHERE tree.tree_depth < $2)
+///    SELECT category_id, parent_id, tree_depth, tree_path
+///      FROM tree
+///  ORDER BY tree_depth, tree_path
+/// ```
+pub fn tree_query(table_name: &TableName,
+                  key: &str,
+                  parent: &str,
+                  id: Value,
+                  traversal: Traversal,
+                  max_depth: usize)
+                  -> Select {
+    let qualified = |table: &str, column: &str| {
+        Operand::ColumnName(ColumnName {
+            column: column.to_owned(),
+            table: Some(table.to_owned()),
+            schema: None,
+        })
+    };
+    let node_fields = || -> Vec<Field> {
+        table_name.columns
+            .iter()
+            .map(|c| {
+                Field {
+                    operand: qualified(&table_name.name, &c.column),
+                    name: None,
+                }
+            })
+            .collect()
+    };
+    let raw = |sql: &str| Operand::Raw(sql.to_owned(), vec![]);
+
+    let mut anchor = Select::new();
+    anchor.enumerated_fields = node_fields();
+    anchor.enumerated_fields.push(Field {
+        operand: raw("0"),
+        name: Some(TREE_DEPTH.to_owned()),
+    });
+    anchor.enumerated_fields.push(Field {
+        operand: CAST(&qualified(&table_name.name, key), "TEXT"),
+        name: Some(TREE_PATH.to_owned()),
+    });
+    anchor.from(table_name);
+    anchor.add_filter(&qualified(&table_name.name, key).EQ(&id));
+
+    let link = match traversal {
+        Traversal::DESCENDANTS => qualified(&table_name.name, parent).EQ(&qualified(TREE, key)),
+        Traversal::ANCESTORS => qualified(&table_name.name, key).EQ(&qualified(TREE, parent)),
+    };
+    let mut recursive = Select::new();
+    recursive.enumerated_fields = node_fields();
+    recursive.enumerated_fields.push(Field {
+        operand: raw(&format!("{}.{} + 1", TREE, TREE_DEPTH)),
+        name: None,
+    });
+    let (path, separator, node_key) = (qualified(TREE, TREE_PATH), raw("'/'"), qualified(&table_name.name, key));
+    recursive.enumerated_fields.push(Field {
+        operand: CONCAT(&[&path, &separator, &node_key]),
+        name: None,
+    });
+    recursive.from(table_name);
+    recursive.join(TREE.ON(link));
+    recursive.add_filter(&qualified(TREE, TREE_DEPTH).LT(&(max_depth as i32)));
+
+    let mut query = Select::new();
+    query.with(DeclaredQuery::recursive(TREE, anchor, recursive));
+    for c in &table_name.columns {
+        query.column(&c.column);
+    }
+    query.column(TREE_DEPTH);
+    query.column(TREE_PATH);
+    query.from(&TREE);
+    query.order_by.push(TREE_DEPTH.ASC());
+    query.order_by.push(TREE_PATH.ASC());
+    query.enable_query_stat = false;
+    query
+}
@@ -1,6 +1,77 @@
 use dao::Value;
-use database::{SqlOption, BuildMode};
+use database::{SqlOption, BuildMode, IdentifierQuoting};
+use database::DbError;
 use std::fmt;
+use chrono::UTC;
+use rustc_serialize::hex::ToHex;
+
+/// quote the string, escaping the quotes by doubling them,
+/// and the backslashes in databases that treats them as escape characters
+fn quote_string(str: &str, sql_options: &[SqlOption]) -> String {
+    let escaped = if sql_options.contains(&SqlOption::UsesBackslashEscape) {
+        str.replace("\\", "\\\\")
+    } else {
+        str.to_owned()
+    };
+    format!("'{}'", escaped.replace("'", "''"))
+}
+
+/// NaN and the infinities are not numeric literals, they are written as strings
+/// which postgresql casts to floats
+fn float_literal(float: String, sql_options: &[SqlOption]) -> String {
+    match &*float {
+        "NaN" => quote_string("NaN", sql_options),
+        "inf" => quote_string("Infinity", sql_options),
+        "-inf" => quote_string("-Infinity", sql_options),
+        _ => float,
+    }
+}
+
+/// write the value as a literal of the database, used in the debug build
+/// so that the sql can be run as is, the same as the parameterized sql
+pub fn literal(value: &Value, sql_options: &[SqlOption]) -> String {
+    match *value {
+        Value::Bool(x) => {
+            let (t, f) = if sql_options.contains(&SqlOption::UsesIntegerBoolean) {
+                ("1", "0")
+            } else {
+                ("TRUE", "FALSE")
+            };
+            let literal = if x { t } else { f };
+            literal.to_owned()
+        }
+        Value::I8(x) => format!("{}", x),
+        Value::I16(x) => format!("{}", x),
+        Value::I32(x) => format!("{}", x),
+        Value::I64(x) => format!("{}", x),
+        Value::U8(x) => format!("{}", x),
+        Value::U16(x) => format!("{}", x),
+        Value::U32(x) => format!("{}", x),
+        Value::U64(x) => format!("{}", x),
+        Value::F32(x) => float_literal(format!("{:?}", x), sql_options),
+        Value::F64(x) => float_literal(format!("{:?}", x), sql_options),
+        Value::String(ref x) => quote_string(x, sql_options),
+        Value::VecU8(ref x) => {
+            if sql_options.contains(&SqlOption::UsesByteaHex) {
+                format!("'\\x{}'::bytea", x.to_hex())
+            } else if sql_options.contains(&SqlOption::UsesHexLiteral) {
+                format!("0x{}", x.to_hex())
+            } else {
+                format!("X'{}'", x.to_hex())
+            }
+        }
+        Value::Uuid(ref x) => quote_string(&format!("{}", x), sql_options),
+        Value::DateTime(ref x) => {
+            if sql_options.contains(&SqlOption::SupportsTimestampTz) {
+                format!("TIMESTAMP WITH TIME ZONE '{}'",
+                        x.format("%Y-%m-%d %H:%M:%S%.f%:z"))
+            } else {
+                format!("'{}'", x.with_timezone(&UTC).format("%Y-%m-%d %H:%M:%S%.f"))
+            }
+        }
+        Value::Json(ref x) => quote_string(&x.to_string(), sql_options),
+    }
+}
 
 /// sql fragment
 /// use this for writing SQL statements
@@ -9,6 +80,58 @@ pub struct SqlFrag {
     pub params: Vec<Value>,
     pub sql_options: Vec<SqlOption>,
     pub build_mode: BuildMode,
+    pub identifier_quoting: IdentifierQuoting,
+    /// the part of the query that can not be built in this database,
+    /// the sql is not executed when there is an error
+    pub error: Option<String>,
+}
+
+/// the commonly reserved words in postgresql, sqlite and mysql,
+/// identifiers with these names are quoted
+const RESERVED_WORDS: &'static [&'static str] = &["all", "alter", "and", "any", "as", "asc", "between", "both",
+                                                  "by", "case", "cast", "check", "collate", "column",
+                                                  "constraint", "create", "cross", "current_date",
+                                                  "current_time", "current_timestamp", "current_user",
+                                                  "default", "delete", "desc", "distinct", "drop", "else",
+                                                  "end", "except", "exists", "false", "fetch", "for",
+                                                  "foreign", "from", "full", "grant", "group", "having",
+                                                  "in", "index", "inner", "insert", "intersect", "into",
+                                                  "is", "join", "key", "lateral", "leading", "left",
+                                                  "like", "limit", "lock", "natural", "not", "null",
+                                                  "offset", "on", "or", "order", "outer", "partition",
+                                                  "primary", "range", "references", "right", "rows",
+                                                  "select", "session_user", "set", "some", "table",
+                                                  "then", "to", "trailing", "true", "union", "unique",
+                                                  "update", "user", "using", "values", "when", "where",
+                                                  "window", "with"];
+
+/// whether the identifier has to be quoted, when it is a reserved word, is not in lowercase
+/// or has characters other than letters, digits and underscores
+fn needs_quote(name: &str) -> bool {
+    let starts_with_letter = match name.chars().next() {
+        Some(c) => c.is_ascii_lowercase() || c == '_',
+        None => true,
+    };
+    !starts_with_letter ||
+    !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') ||
+    RESERVED_WORDS.contains(&name)
+}
+
+/// quote the table name, column name or alias with double quotes, or backticks in mysql,
+/// the quotes in the name are escaped by doubling them. `*` is never quoted
+pub fn quote_identifier(name: &str, sql_options: &[SqlOption], quoting: &IdentifierQuoting) -> String {
+    if name == "*" || (*quoting == IdentifierQuoting::WhenNeeded && !needs_quote(name)) {
+        return name.to_owned();
+    }
+    if sql_options.contains(&SqlOption::UsesBracketQuote) {
+        return format!("[{}]", name.replace("]", "]]"));
+    }
+    let quote = if sql_options.contains(&SqlOption::UsesBacktickQuote) {
+        "`"
+    } else {
+        "\""
+    };
+    format!("{}{}{}", quote, name.replace(quote, &format!("{}{}", quote, quote)), quote)
 }
 
 impl fmt::Display for SqlFrag {
@@ -36,9 +159,62 @@ impl SqlFrag {
             params: vec![],
             sql_options: sql_options,
             build_mode: build_mode.clone(),
+            identifier_quoting: IdentifierQuoting::WhenNeeded,
+            error: None,
+        }
+    }
+
+    /// mark the sql as invalid, only the first error is kept
+    /// since the rest of the errors may just be caused by it
+    pub fn set_error(&mut self, description: &str) {
+        if self.error.is_none() {
+            self.error = Some(description.to_owned());
         }
     }
 
+    /// the sql fragment, or the error when a part of the query can not be built
+    pub fn checked(self) -> Result<Self, DbError> {
+        match self.error {
+            Some(ref error) => Err(DbError::new(error)),
+            None => Ok(self),
+        }
+    }
+
+    /// write the table name, column name or alias quoted for the database
+    pub fn identifier(&mut self, name: &str) -> &mut Self {
+        let quoted = quote_identifier(name, &self.sql_options, &self.identifier_quoting);
+        self.append(&quoted)
+    }
+
+    /// write a name qualified with its table or schema, ie: `schema.table`,
+    /// quoting each of the parts
+    pub fn qualified_identifier(&mut self, name: &str) -> &mut Self {
+        let mut do_dot = false;
+        for part in name.split('.') {
+            if do_dot {
+                self.append(".");
+            } else {
+                do_dot = true;
+            }
+            self.identifier(part);
+        }
+        self
+    }
+
+    /// write the identifiers separated with commas
+    pub fn identifiers(&mut self, names: &[String]) -> &mut Self {
+        let mut do_comma = false;
+        for name in names {
+            if do_comma {
+                self.commasp();
+            } else {
+                do_comma = true;
+            }
+            self.qualified_identifier(name);
+        }
+        self
+    }
+
     #[inline]
     pub fn append(&mut self, str: &str) -> &mut Self {
         self.sql.push_str(str);
@@ -139,12 +315,58 @@ impl SqlFrag {
                     self.append(&numbered_param);
                 } else if self.sql_options.contains(&SqlOption::UsesQuestionMark) {
                     self.append("?");
+                } else if self.sql_options.contains(&SqlOption::UsesAtParam) {
+                    let named_param = format!("@p{}", self.params.len());
+                    self.append(&named_param);
                 }
             }
             BuildMode::Debug => {
-                // use fmt::Display
-                self.append(&format!("{}",&param));
+                let literal = literal(&param, &self.sql_options);
+                self.append(&literal);
             }
         }
     }
+
+    /// write a raw sql, the `?` placeholders are replaced with the parameters
+    /// in the same numbering as the rest of the query, use `??` for a literal `?`.
+    /// the `?` inside the quoted strings and identifiers are not placeholders
+    pub fn raw(&mut self, sql: &str, params: &[Value]) {
+        let mut params = params.iter();
+        let mut quote = None;
+        let mut chars = sql.chars().peekable();
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(q) => {
+                    // a doubled quote closes then opens the quote again
+                    if c == q {
+                        quote = None;
+                    }
+                    self.sql.push(c);
+                }
+                None => {
+                    if c == '\'' || c == '"' {
+                        quote = Some(c);
+                        self.sql.push(c);
+                    } else if c == '?' {
+                        if chars.peek() == Some(&'?') {
+                            chars.next();
+                            self.sql.push('?');
+                        } else {
+                            match params.next() {
+                                Some(param) => self.parameter(param.clone()),
+                                None => {
+                                    return self.set_error(&format!("There are more placeholders than parameters in: {}", sql));
+                                }
+                            }
+                        }
+                    } else {
+                        self.sql.push(c);
+                    }
+                }
+            }
+        }
+        if params.next().is_some() {
+            self.set_error(&format!("There are more parameters than placeholders in: {}", sql));
+        }
+    }
 }
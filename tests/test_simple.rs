@@ -1,4 +1,7 @@
 extern crate rustorm;
+extern crate rustc_serialize;
+#[macro_use]
+extern crate rustorm_derive;
 
 #[cfg(test)] #[macro_use] extern crate pretty_assertions;
 
@@ -6,10 +9,39 @@ extern crate rustorm;
 use rustorm::query::Query;
 use rustorm::query::Filter;
 use rustorm::query::Select;
+use rustorm::query::Insert;
+use rustorm::query::Update;
+use rustorm::query::Delete;
+use rustorm::query::join::{Join, ToJoin};
+use rustorm::query::operand::raw;
+use rustorm::dao::{Dao, Value, DaoResult};
+use rustorm::dao::{IsDao, FromValue, ToValue};
+use rustorm::query::Equality;
 use rustorm::query::Equality::EQ;
+use rustorm::query::Direction;
+use rustorm::query::HasDirection;
+use rustorm::query::{TableName, ColumnName, Traversal, Operand, Field};
+use rustorm::query::tree::tree_query;
+use rustorm::query::parser::parse;
+use rustorm::query::rest::from_query_string;
+use rustorm::table::{Table, Column};
+use rustorm::dao::Type;
+use rustc_serialize::json::Json;
+use rustorm::query::fold::{Fold, walk_select};
+use rustorm::query::HasEquality;
+use rustorm::query::field::Rename;
+use rustorm::query::function::{call, STRING_AGG, DATE_TRUNC, TS_RANK, TS_HEADLINE};
 use rustorm::database::BuildMode;
+use rustorm::database::IdentifierQuoting;
+use rustorm::database::DbError;
+use rustorm::database::SqlOption;
+use rustorm::dialect::{Dialect, PostgresDialect, MysqlDialect, SqliteDialect, SqlServerDialect};
 use rustorm::platform::pool::Platform;
 use rustorm::platform::Postgres;
+#[cfg(feature = "sqlite")]
+use rustorm::query::IsTable;
+#[cfg(feature = "sqlite")]
+use rustorm::entity::EntityManager;
 
 #[cfg(test)]
 #[test]
@@ -21,7 +53,7 @@ fn test_pg(){
     query.from(&"users".to_string());
     let filter = Filter::new("username", EQ, &"Hello".to_string());
     query.add_filter(&filter);   
-    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    let sql = pg.dialect().build_select(&query, &BuildMode::Debug); 
     println!("{}", sql);
     let expected = r#"
    SELECT username, email
@@ -39,7 +71,7 @@ fn test_sqlite(){
     query.from(&"users".to_string());
     let filter = Filter::new("username", EQ, &"Hello".to_string());
     query.add_filter(&filter);   
-    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    let sql = pg.dialect().build_select(&query, &BuildMode::Debug); 
     println!("{}", sql);
     let expected = r#"
    SELECT 
@@ -58,7 +90,7 @@ fn test_mysql(){
     query.from(&"users".to_string());
     let filter = Filter::new("username", EQ, &"Hello".to_string());
     query.add_filter(&filter);   
-    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    let sql = pg.dialect().build_select(&query, &BuildMode::Debug); 
     println!("{}", sql);
     let expected = r#"
    SELECT 
@@ -68,3 +100,1273 @@ fn test_mysql(){
     assert_eq!(sql.sql.trim(), expected.trim());
 }
 
+
+#[test]
+fn test_pg_between(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.columns(vec!["username", "email"]);
+    query.from(&"users".to_string());
+    query.add_filter(&"age".BETWEEN(&18, &30));
+    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    let expected = r#"
+   SELECT username, email
+     FROM users
+    WHERE age BETWEEN 18 AND 30
+    "#;
+    assert_eq!(sql.sql.trim(), expected.trim());
+}
+
+#[test]
+fn test_predicates_golden(){
+    let mut orders = Select::new();
+    orders.column("orders.order_id");
+    orders.from(&"orders".to_string());
+    orders.add_filter(&"orders.customer_id".EQ(&"customer.customer_id"));
+    let regions = Operand::Vec(vec![Operand::Value(Value::String("north".to_owned())),
+                                    Operand::Value(Value::String("east".to_owned()))]);
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"customer".to_string());
+    query.add_filter(&orders.NOT_EXISTS());
+    query.add_filter(&"region".ANY(EQ, &regions));
+    query.add_filter(&"email".IS_DISTINCT_FROM(&"backup_email"));
+
+    let sql = PostgresDialect::new().build_select(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.sql.contains("WHERE NOT EXISTS ("));
+    assert!(sql.sql.contains("region = ANY (ARRAY['north', 'east'])"));
+    assert!(sql.sql.contains("email IS DISTINCT FROM backup_email"));
+
+    let sql = MysqlDialect::new().build_select(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.sql.contains("( region = 'north' OR region = 'east' )"));
+    assert!(sql.sql.contains("NOT (email <=> backup_email)"));
+
+    let sql = SqliteDialect::new().build_select(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.sql.contains("email IS NOT backup_email"));
+    assert!(sql.error.is_none());
+
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"customer".to_string());
+    query.add_filter(&"phone".REGEX(&r"^\+63".to_string()));
+    let sql = PostgresDialect::new().build_select(&query, &BuildMode::Debug);
+    assert!(sql.sql.contains(r"WHERE phone ~ '^\+63'"));
+    let sql = MysqlDialect::new().build_select(&query, &BuildMode::Debug);
+    assert!(sql.sql.contains(r"WHERE phone REGEXP '^\\+63'"));
+}
+
+#[test]
+fn test_unsupported_predicates_are_errors(){
+    let lite = SqliteDialect::new();
+    let my = MysqlDialect::new();
+    let build = |dialect: &Dialect, filter: Filter| {
+        let mut query = Select::new();
+        query.column("name");
+        query.from(&"customer".to_string());
+        query.add_filter(&filter);
+        dialect.build_select(&query, &BuildMode::Standard).error
+    };
+    // sqlite has no regexp function unless one is registered in the connection
+    assert!(build(&lite, "phone".REGEX(&"^0".to_string())).is_some());
+    assert!(build(&my, "phone".SIMILAR_TO(&"0%".to_string())).is_some());
+    assert!(build(&my, Filter::new("age", Equality::BETWEEN, &18)).is_some());
+    let mut orders = Select::new();
+    orders.column("total");
+    orders.from(&"orders".to_string());
+    assert!(build(&lite, "credit".ALL(Equality::GT, &orders)).is_some());
+    assert!(build(&lite, "credit".ANY(EQ, &orders)).is_none());
+    assert!(build(&my, "credit".ALL(Equality::GT, &orders)).is_none());
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_ilike_emulated(){
+    let lite = Platform::sqlite();
+    let mut query = Select::new();
+    query.columns(vec!["username", "email"]);
+    query.from(&"users".to_string());
+    query.add_filter(&"username".ILIKE(&"iVan%".to_string()));
+    let sql = lite.dialect().build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    let expected = r#"
+   SELECT username, email
+     FROM users
+    WHERE LOWER(username) LIKE LOWER('iVan%')
+    "#;
+    assert_eq!(sql.sql.trim(), expected.trim());
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_string_agg(){
+    let lite = Platform::sqlite();
+    let mut query = Select::new();
+    query.enumerated_fields.push(STRING_AGG(&"username", ",").into());
+    query.from(&"users".to_string());
+    let sql = lite.dialect().build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    let expected = r#"
+   SELECT GROUP_CONCAT(username, ',')
+     FROM users
+    "#;
+    assert_eq!(sql.sql.trim(), expected.trim());
+}
+
+#[test]
+fn test_function_errors_and_separator_escape(){
+    let my = MysqlDialect::new();
+    let mut query = Select::new();
+    query.enumerated_fields.push(STRING_AGG(&"username", "\\'").into());
+    query.from(&"users".to_string());
+    let sql = my.build_select(&query, &BuildMode::Standard);
+    assert!(sql.error.is_none());
+    // the backslash is escaped too, it can not escape the closing quote
+    assert!(sql.sql.contains(r"GROUP_CONCAT(username SEPARATOR '\\''')"));
+
+    let mut query = Select::new();
+    query.enumerated_fields.push(DATE_TRUNC("fortnight", &"created").into());
+    query.from(&"users".to_string());
+    assert!(query.build_with(&my).error.is_some());
+    assert!(query.build_with(&SqliteDialect::new()).error.is_some());
+    assert!(query.build_with(&SqlServerDialect::new()).error.is_some());
+    // a function with the wrong parameters is an error, not a panic
+    let mut query = Select::new();
+    query.enumerated_fields.push(call("CAST", &[]).into());
+    query.from(&"users".to_string());
+    assert!(query.build_with(&PostgresDialect::new()).error.is_some());
+}
+
+#[test]
+fn test_pg_subquery_params(){
+    let pg = PostgresDialect::new();
+    let mut orders = Select::new();
+    orders.column("orders.customer_id");
+    orders.from(&"orders".to_string());
+    orders.add_filter(&"orders.total".GT(&100));
+    orders.add_filter(&"orders.customer_id".EQ(&"c.customer_id"));
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"customer".AS("c"));
+    query.add_filter(&"active".EQ(&true));
+    query.add_filter(&orders.EXISTS());
+    query.add_filter(&"region".EQ(&"north".to_string()));
+    let sql = pg.build_select(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert_eq!(sql.params.len(), 3);
+    assert!(sql.sql.contains("EXISTS ("));
+    assert!(sql.sql.contains("orders.total > $2"));
+    assert!(sql.sql.contains("orders.customer_id = c.customer_id"));
+    assert!(sql.sql.contains("region = $3"));
+}
+
+#[test]
+fn test_pg_upsert(){
+    let pg = PostgresDialect::new();
+    let mut query = Insert::into(&"product".to_string());
+    query.columns(vec!["product_id", "name", "price"]);
+    query.values(vec![&1, &"Laptop".to_string(), &999]);
+    query.on_conflict(vec!["product_id"]).do_update(vec!["name", "price"]);
+    let sql = pg.build_insert(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    let expected = r#"
+   INSERT INTO product( product_id, name, price ) 
+   VALUES (1, 'Laptop', 999) 
+       ON CONFLICT ( product_id ) DO UPDATE 
+      SET name = EXCLUDED.name, price = EXCLUDED.price
+    "#;
+    assert_eq!(sql.sql.trim(), expected.trim());
+}
+
+#[test]
+fn test_upsert_errors(){
+    let mut query = Insert::into(&"product".to_string());
+    query.columns(vec!["product_id", "name"]);
+    query.values(vec![&1, &"Laptop".to_string()]);
+    query.on_conflict(vec![]).do_update(vec!["name"]);
+    assert!(query.build_with(&PostgresDialect::new()).error.is_some());
+    assert!(query.build_with(&MysqlDialect::new()).error.is_none());
+    query.on_conflict(vec![]).do_nothing();
+    assert!(query.build_with(&MysqlDialect::new()).error.is_some());
+    query.on_conflict(vec!["product_id"]).do_nothing();
+    assert!(query.build_with(&SqlServerDialect::new()).error.is_some());
+    // ON CONFLICT is in sqlite 3.24 and later
+    assert!(query.build_with(&SqliteDialect::with_version("3.22.0")).error.is_some());
+    let sql = query.build_with(&SqliteDialect::with_version("3.24.0"));
+    assert!(sql.error.is_none());
+    assert!(sql.sql.contains("ON CONFLICT ( product_id ) DO NOTHING"));
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(IsDao)]
+#[derive(IsTable)]
+#[derive(Debug, PartialEq)]
+struct Tag {
+    name: String,
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_upsert_existing_record(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    db.execute_sql("CREATE TABLE tag (name TEXT PRIMARY KEY)", &[]).unwrap();
+    let em = EntityManager::new(db);
+    let tag = Tag { name: "rust".to_owned() };
+    let inserted: Tag = em.upsert(&tag, vec!["name"]).unwrap();
+    // DO NOTHING on the conflict, the existing record is selected
+    let existing: Tag = em.upsert(&tag, vec!["name"]).unwrap();
+    assert_eq!(inserted, tag);
+    assert_eq!(existing, tag);
+    assert_eq!(db.execute_sql("DELETE FROM tag", &[]).unwrap(), 1);
+}
+
+#[test]
+#[cfg(feature = "mysql")]
+fn test_mysql_upsert(){
+    let my = Platform::mysql();
+    let mut query = Insert::into(&"product".to_string());
+    query.columns(vec!["product_id", "name"]);
+    query.values(vec![&1, &"Laptop".to_string()]);
+    query.on_conflict(vec!["product_id"]).do_update(vec!["name"]);
+    let sql = my.dialect().build_insert(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("ON DUPLICATE KEY UPDATE name = VALUES(name)"));
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_insert_rows_batches(){
+    let lite = Platform::sqlite();
+    let mut query = Insert::into(&"users".to_string());
+    query.columns(vec!["username", "email"]);
+    let rows = (0..1000)
+        .map(|i| vec![Value::String(format!("user{}", i)), Value::String(format!("user{}@mail.com", i))])
+        .collect();
+    query.rows(rows);
+    let batches = query.batches(lite.max_parameters());
+    assert_eq!(batches.len(), 3);
+    let sql = lite.dialect().build_insert(&batches[2], &BuildMode::Standard);
+    println!("{}", sql);
+    assert_eq!(sql.params.len(), 4);
+    assert!(sql.sql.contains("VALUES ($1 , $2 ), ($3 , $4 )"));
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(IsDao)]
+#[derive(IsTable)]
+struct Memo {
+    title: String,
+    body: Option<String>,
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_insert_all_missing_values(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    db.execute_sql("CREATE TABLE memo (title TEXT, body TEXT)", &[]).unwrap();
+    let memos = vec![Memo { title: "draft".to_owned(), body: None },
+                     Memo { title: "done".to_owned(), body: Some("sent".to_owned()) }];
+    let _: Vec<Memo> = EntityManager::new(db).insert_all(&memos).unwrap();
+    let rows = db.execute_sql_with_return("SELECT title FROM memo WHERE body IS NULL", &[]).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["title"], Value::String("draft".to_owned()));
+}
+
+#[test]
+fn test_from_value_widened(){
+    assert_eq!(i32::from_type(Value::I64(5)), 5);
+    assert_eq!(u8::from_type(Value::I64(255)), 255);
+    assert_eq!(u64::from_type(Value::I64(7)), 7);
+    assert_eq!(bool::from_type(Value::I64(1)), true);
+    assert_eq!(f32::from_type(Value::F64(2.5)), 2.5);
+    assert_eq!(String::from_type(Value::I64(1984)), "1984");
+}
+
+#[test]
+#[should_panic]
+fn test_from_value_out_of_range(){
+    i8::from_type(Value::I64(300));
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(IsDao)]
+#[derive(IsTable)]
+#[derive(Debug, PartialEq)]
+struct Member {
+    zip: String,
+    age: i32,
+    active: bool,
+    score: f32,
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_typed_values_from_dao(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    db.execute_sql("CREATE TABLE member (zip INTEGER, age INTEGER, active BOOLEAN, score REAL)", &[]).unwrap();
+    db.execute_sql("INSERT INTO member (zip, age, active, score) VALUES (1984, 30, 1, 4.5)", &[]).unwrap();
+    let rows = db.execute_sql_with_return("SELECT * FROM member", &[]).unwrap();
+    assert_eq!(rows[0]["zip"], Value::I64(1984));
+    let member = Member::from_dao(&rows[0]);
+    assert_eq!(member, Member { zip: "1984".to_owned(), age: 30, active: true, score: 4.5 });
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_insert_all_in_transaction(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    db.execute_sql("CREATE TABLE users (username TEXT UNIQUE, email TEXT)", &[]).unwrap();
+    let mut query = Insert::into(&"users".to_string());
+    query.columns(vec!["username", "email"]);
+    // the last row of the third batch is a duplicate
+    let mut rows: Vec<Vec<Value>> = (0..1000)
+        .map(|i| vec![Value::String(format!("user{}", i)), Value::String(format!("user{}@mail.com", i))])
+        .collect();
+    rows.push(vec![Value::String("user0".to_owned()), Value::String("again@mail.com".to_owned())]);
+    query.rows(rows);
+    db.begin().unwrap();
+    db.execute_sql("INSERT INTO users (username, email) VALUES ('lee', 'lee@mail.com')", &[]).unwrap();
+    assert!(db.insert_all(&query).is_err());
+    // only the batches are undone, the transaction of the caller goes on
+    assert!(db.in_transaction());
+    db.commit().unwrap();
+    assert!(!db.in_transaction());
+    let users = db.execute_sql_with_return("SELECT username FROM users", &[]).unwrap();
+    assert_eq!(users.len(), 1);
+}
+
+fn update_order_total() -> Update {
+    let mut query = Update::table(&"orders".to_string());
+    query.column(&"total");
+    query.value(&"line_item.amount");
+    query.join("line_item".ON("line_item.order_id".EQ(&"orders.order_id")));
+    query.add_filter(&"orders.status".EQ(&"open".to_string()));
+    query
+}
+
+#[test]
+fn test_pg_update_from(){
+    let pg = PostgresDialect::new();
+    let sql = pg.build_update(&update_order_total(), &BuildMode::Standard); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("SET total = line_item.amount"));
+    assert!(sql.sql.contains("FROM line_item"));
+    assert!(sql.sql.contains("line_item.order_id = orders.order_id"));
+    assert!(sql.sql.contains("orders.status = $1"));
+    // only the inner joins can be written in the FROM of the update
+    let mut query = update_order_total();
+    query.joins = vec![];
+    query.join("line_item".ON("line_item.order_id".EQ(&"orders.order_id")).LEFT());
+    assert!(query.build_with(&pg).error.is_some());
+}
+
+#[test]
+fn test_sqlite_update_join_rewrite(){
+    let lite = SqliteDialect::new();
+    let mut query = update_order_total();
+    query.set("note", &"recounted".to_string());
+    let sql = lite.build_update(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("SET total = ("));
+    assert!(sql.sql.contains("WHERE line_item.order_id = orders.order_id ), note = $1"));
+    assert!(sql.sql.contains("WHERE rowid IN ("));
+    assert!(sql.sql.contains("SELECT orders.rowid"));
+    assert!(!sql.sql.contains("JOIN"));
+    // the filter is written once
+    assert_eq!(sql.params, vec![Value::String("recounted".to_owned()), Value::String("open".to_owned())]);
+}
+
+#[test]
+fn test_sqlite_delete_returning(){
+    let mut query = Delete::from(&"users".to_string());
+    query.add_filter(&"active".EQ(&false));
+    query.return_all();
+    let sql = query.build_with(&SqliteDialect::with_version("3.35.5"));
+    println!("{}", sql);
+    assert!(sql.sql.contains("WHERE active = $1"));
+    assert!(sql.sql.contains("RETURNING *"));
+    // the older or unknown versions select the records instead
+    let sql = query.build_with(&SqliteDialect::with_version("3.31.1"));
+    assert!(!sql.sql.contains("RETURNING"));
+    let sql = query.build_with(&SqliteDialect::new());
+    assert!(!sql.sql.contains("RETURNING"));
+}
+
+#[test]
+fn test_pg_for_update_skip_locked(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.column("job_id");
+    query.from(&"job".to_string());
+    query.add_filter(&"status".EQ(&"queued".to_string()));
+    query.set_limit(10);
+    query.for_update().of(&"job").skip_locked();
+    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    let expected = r#"
+   SELECT job_id
+     FROM job
+    WHERE status = 'queued'
+    LIMIT 10
+      FOR UPDATE OF job SKIP LOCKED
+    "#;
+    assert_eq!(sql.sql.trim(), expected.trim());
+    // not silently dropped where row locking is not supported
+    let sql = query.build_with(&SqliteDialect::new());
+    assert!(sql.error.is_some());
+}
+
+#[test]
+fn test_mysql_version_features(){
+    let mut query = Select::new();
+    query.column("job_id");
+    query.from(&"job".to_string());
+    query.for_update().skip_locked();
+    assert!(query.build_with(&MysqlDialect::with_version("5.7.40-log")).error.is_some());
+    let sql = query.build_with(&MysqlDialect::with_version("8.0.32"));
+    assert!(sql.error.is_none());
+    assert!(sql.sql.contains("FOR UPDATE SKIP LOCKED"));
+
+    let category = TableName {
+        schema: None,
+        name: "category".to_owned(),
+        columns: vec![ColumnName::from("category_id"), ColumnName::from("parent_id")],
+    };
+    let query = tree_query(&category, "category_id", "parent_id", Value::I32(1), Traversal::DESCENDANTS, 10);
+    assert!(query.build_with(&MysqlDialect::with_version("5.7.40-log")).error.is_some());
+    assert!(query.build_with(&MysqlDialect::with_version("8.0.32")).error.is_none());
+
+    // LATERAL is in mysql 8.0.14 and later
+    let mut query = Select::new();
+    query.from(&"users".to_string());
+    query.join(Join::cross(&"recent_orders").LATERAL());
+    assert!(query.build_with(&MysqlDialect::with_version("8.0.13")).error.is_some());
+    assert!(query.build_with(&MysqlDialect::with_version("8.0.14")).error.is_none());
+}
+
+#[test]
+fn test_pg_raw_operand_params(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"users".to_string());
+    query.add_filter(&"active".EQ(&true));
+    query.add_filter(&raw("age(now(), birthday) > ?::interval", vec![Value::String("18 years".to_owned())]).EQ(&true));
+    query.add_filter(&"region".EQ(&"north".to_string()));
+    let sql = pg.build_select(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert_eq!(sql.params.len(), 4);
+    assert!(sql.sql.contains("age(now(), birthday) > $2 ::interval = $3"));
+    assert!(sql.sql.contains("region = $4"));
+
+    // the question mark in a string literal is not a placeholder
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"users".to_string());
+    query.add_filter(&raw("note <> 'what?' AND score > ?", vec![Value::I32(3)]).EQ(&true));
+    let sql = pg.build_select(&query, &BuildMode::Standard);
+    assert!(sql.sql.contains("note <> 'what?' AND score > $1 "));
+    assert_eq!(sql.params, vec![Value::I32(3), Value::Bool(true)]);
+    assert_eq!(sql.error, None);
+
+    let mut query = Select::new();
+    query.from(&"users".to_string());
+    query.add_filter(&raw("score > ? AND rank < ?", vec![Value::I32(3)]).EQ(&true));
+    assert!(pg.build_select(&query, &BuildMode::Standard).error.is_some());
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_statement_cache_kept_by_connection(){
+    // a single connection, so every checkout is given the same one
+    let pool = rustorm::platform::pool::ManagedPool::init("sqlite:///:memory:", 1).unwrap();
+    {
+        let db = pool.connect().unwrap();
+        db.execute_sql("CREATE TABLE cached (id TEXT)", &[]).unwrap();
+        db.execute_sql_with_return("SELECT * FROM cached", &[]).unwrap();
+    }
+    let db = pool.connect().unwrap();
+    let before = db.statement_cache_stats().unwrap();
+    db.execute_sql_with_return("SELECT * FROM cached", &[]).unwrap();
+    let after = db.statement_cache_stats().unwrap();
+    assert_eq!(after.hits, before.hits + 1);
+
+    db.execute_sql("ALTER TABLE cached ADD COLUMN name TEXT", &[]).unwrap();
+    db.invalidate_statement_cache();
+    assert_eq!(db.statement_cache_stats().unwrap().size, 0);
+    db.execute_sql("INSERT INTO cached (id, name) VALUES ($1, $2)",
+                   &[Value::String("1".to_owned()), Value::String("one".to_owned())]).unwrap();
+    let rows = db.execute_sql_with_return("SELECT * FROM cached", &[]).unwrap();
+    assert_eq!(rows[0]["name"], Value::String("one".to_owned()));
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_keyset_expanded(){
+    let lite = Platform::sqlite();
+    let mut query = Select::new();
+    query.columns(vec!["id", "name"]);
+    query.from(&"users".to_string());
+    query.keyset(vec!["created", "id"], Direction::ASC, 20);
+    query.after(vec![Value::String("2016-01-01".to_owned()), Value::I32(5)]).unwrap();
+    let sql = lite.dialect().build_select(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert_eq!(sql.params.len(), 3);
+    assert!(sql.sql.contains("WHERE ( created > $1"));
+    assert!(sql.sql.contains("OR ( created = $2"));
+    assert!(sql.sql.contains("id > $3"));
+    assert!(sql.sql.contains("LIMIT 20"));
+}
+
+#[test]
+fn test_pg_keyset_row_values(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.columns(vec!["id", "name"]);
+    query.from(&"users".to_string());
+    query.keyset(vec!["created", "id"], Direction::DESC, 20);
+    query.after(vec![Value::String("2016-01-01".to_owned()), Value::I32(5)]).unwrap();
+    let sql = pg.build_select(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("WHERE (created, id) < ($1 , $2 )"));
+    assert_eq!(sql.params, vec![Value::String("2016-01-01".to_owned()), Value::I32(5)]);
+}
+
+#[test]
+fn test_keyset_mismatch_errors(){
+    let mut query = Select::new();
+    query.from(&"users".to_string());
+    assert!(query.after(vec![Value::I32(5)]).is_err());
+    query.keyset(vec!["created", "id"], Direction::ASC, 20);
+    assert!(query.after(vec![Value::I32(5)]).is_err());
+    query.keyset.as_mut().unwrap().after = vec![Value::I32(5)];
+    assert!(query.build_with(&PostgresDialect::new()).error.is_some());
+}
+
+#[test]
+fn test_pg_distinct_on(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.columns(vec!["customer_id", "total"]);
+    query.from(&"orders".to_string());
+    query.distinct_on_columns(&vec!["customer_id".to_owned()]);
+    query.order_by.push("customer_id".ASC());
+    query.order_by.push("total".DESC());
+    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("SELECT DISTINCT ON (customer_id) customer_id, total"));
+}
+
+#[test]
+fn test_sqlite_distinct_on_emulated(){
+    let lite = SqliteDialect::with_version("3.25.0");
+    let mut query = Select::new();
+    query.columns(vec!["customer_id", "total"]);
+    query.from(&"orders".to_string());
+    query.distinct_on_columns(&vec!["customer_id".to_owned()]);
+    query.order_by.push("customer_id".ASC());
+    query.order_by.push("total".DESC().NULLS_FIRST());
+    let sql = lite.build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    assert!(!sql.sql.contains("DISTINCT ON"));
+    // NULLS FIRST is in sqlite 3.30 and later
+    assert!(sql.sql.contains("ROW_NUMBER() OVER (PARTITION BY customer_id ORDER BY customer_id ASC, \
+                              CASE WHEN total IS NULL THEN 0 ELSE 1 END, total DESC) AS distinct_on_row"));
+    assert!(sql.sql.contains("AS distinct_on"));
+    assert!(sql.sql.contains("distinct_on_row = 1"));
+    assert!(!sql.sql.contains("NULLS FIRST"));
+    assert_eq!(sql.error, None);
+    let sql = SqliteDialect::with_version("3.30.1").build_select(&query, &BuildMode::Debug);
+    assert!(sql.sql.contains("ORDER BY customer_id ASC, total DESC NULLS FIRST) AS distinct_on_row"));
+
+    // ROW_NUMBER() is not available before sqlite 3.25
+    let sql = SqliteDialect::with_version("3.22.0").build_select(&query, &BuildMode::Debug);
+    assert!(sql.error.is_some());
+    let sql = MysqlDialect::with_version("5.7.40-log").build_select(&query, &BuildMode::Debug);
+    assert!(sql.error.is_some());
+
+    let mut query = Select::new();
+    query.from(&"orders".to_string());
+    query.distinct_on_columns(&vec!["customer_id".to_owned()]);
+    let sql = MysqlDialect::with_version("8.0.32").build_select(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.sql.contains("SELECT *, ROW_NUMBER() OVER (PARTITION BY customer_id) AS distinct_on_row"));
+    assert!(!sql.sql.contains("SELECT  FROM"));
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_distinct_on_query_stats(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    // ROW_NUMBER() is not available before sqlite 3.25
+    if !db.dialect().sql_options().contains(&SqlOption::SupportsWindowFunctions) {
+        return;
+    }
+    db.execute_sql("CREATE TABLE orders (customer_id TEXT, total TEXT)", &[]).unwrap();
+    db.execute_sql("INSERT INTO orders (customer_id, total) VALUES ('a', '10'), ('a', '20'), ('b', '5')", &[]).unwrap();
+    let mut query = Select::new();
+    query.columns(vec!["customer_id", "total"]);
+    query.from(&"orders".to_string());
+    query.distinct_on_columns(&vec!["customer_id".to_owned()]);
+    query.order_by.push("customer_id".ASC());
+    query.order_by.push("total".DESC());
+    query.enable_query_stat = true;
+    let result = db.execute_with_return(&query).unwrap();
+    assert_eq!(result.dao.len(), 2);
+    assert_eq!(result.total, Some(2));
+}
+
+#[test]
+fn test_pg_self_join_alias(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.columns(vec!["e.name", "m.name"]);
+    query.from(&"employee".AS("e"));
+    query.join("employee".AS("m").ON("m.employee_id".EQ(&"e.manager_id")).LEFT());
+    query.join(Join::cross(&"region"));
+    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("FROM employee AS e"));
+    assert!(sql.sql.contains("LEFT JOIN employee AS m"));
+    assert!(sql.sql.contains("ON m.employee_id = e.manager_id"));
+    assert!(sql.sql.contains("CROSS JOIN region"));
+}
+
+#[test]
+fn test_lateral_join_errors(){
+    let mut query = Select::new();
+    query.from(&"users".to_string());
+    query.join(Join::cross(&"recent_orders").LATERAL());
+    let sql = query.build_with(&PostgresDialect::new());
+    assert!(sql.error.is_none());
+    assert!(sql.sql.contains("CROSS JOIN LATERAL recent_orders"));
+    assert!(query.build_with(&SqliteDialect::new()).error.is_some());
+    assert!(query.build_with(&SqlServerDialect::new()).error.is_some());
+}
+
+#[test]
+fn test_sqlite_tree_descendants(){
+    let lite = SqliteDialect::new();
+    let category = TableName {
+        schema: None,
+        name: "category".to_owned(),
+        columns: vec![ColumnName::from("category_id"), ColumnName::from("parent_id")],
+    };
+    let query = tree_query(&category, "category_id", "parent_id", Value::I32(1), Traversal::DESCENDANTS, 10);
+    let sql = lite.build_select(&query, &BuildMode::Standard); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("WITH RECURSIVE tree AS ("));
+    assert!(sql.sql.contains("0 AS tree_depth, CAST(category.category_id AS TEXT) AS tree_path"));
+    assert!(sql.sql.contains("WHERE category.category_id = $1"));
+    assert!(sql.sql.contains("UNION ALL"));
+    assert!(sql.sql.contains("tree.tree_depth + 1, (tree.tree_path || '/' || category.category_id)"));
+    assert!(sql.sql.contains("JOIN tree"));
+    assert!(sql.sql.contains("ON category.parent_id = tree.category_id"));
+    assert!(sql.sql.contains("WHERE tree.tree_depth < $2"));
+    assert!(sql.sql.contains("FROM tree"));
+    assert!(sql.sql.contains("ORDER BY tree_depth ASC, tree_path ASC"));
+    assert_eq!(sql.params, vec![Value::I32(1), Value::I32(10)]);
+}
+
+#[test]
+fn test_pg_quote_identifiers_when_needed(){
+    let pg = PostgresDialect::new();
+    let mut query = Select::new();
+    query.columns(vec!["user", "Total", "email"]);
+    query.from(&"order".to_string());
+    let filter = Filter::new("user", EQ, &"Hello".to_string());
+    query.add_filter(&filter);
+    let sql = pg.build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    let expected = r#"
+   SELECT "user", "Total", email
+     FROM "order"
+    WHERE "user" = 'Hello'
+    "#;
+    assert_eq!(sql.sql.trim(), expected.trim());
+}
+
+#[test]
+#[cfg(feature = "mysql")]
+fn test_mysql_quote_identifiers_always(){
+    let mut my = Platform::mysql();
+    my.set_identifier_quoting(IdentifierQuoting::Always);
+    let mut query = Select::new();
+    query.columns(vec!["users.username", "users.email"]);
+    query.from(&"users".to_string());
+    query.join("orders".ON("orders.user_id".EQ(&"users.user_id")));
+    let sql = my.dialect().build_select(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    assert!(sql.sql.contains("SELECT `users`.`username`, `users`.`email`"));
+    assert!(sql.sql.contains("FROM `users`"));
+    assert!(sql.sql.contains("JOIN `orders`"));
+    assert!(sql.sql.contains("ON `orders`.`user_id` = `users`.`user_id`"));
+}
+
+#[test]
+fn test_pg_debug_literals(){
+    let pg = PostgresDialect::new();
+    let mut query = Insert::into(&"note".to_string());
+    query.columns(vec!["title", "pinned", "attachment"]);
+    query.values(vec![&"it's".to_string(), &true, &Value::VecU8(vec![1, 171])]);
+    let sql = pg.build_insert(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    assert!(sql.sql.contains(r"VALUES ('it''s', TRUE, '\x01ab'::bytea)"));
+
+    // the numbers are written as they are, not as text
+    let category = TableName {
+        schema: None,
+        name: "category".to_owned(),
+        columns: vec![ColumnName::from("category_id"), ColumnName::from("parent_id")],
+    };
+    let query = tree_query(&category, "category_id", "parent_id", Value::I32(1), Traversal::DESCENDANTS, 10);
+    let sql = pg.build_select(&query, &BuildMode::Debug);
+    assert!(sql.sql.contains("WHERE category.category_id = 1"));
+    assert!(sql.sql.contains("WHERE tree.tree_depth < 10"));
+}
+
+#[test]
+#[cfg(feature = "mysql")]
+fn test_mysql_debug_literals(){
+    let my = Platform::mysql();
+    let mut query = Insert::into(&"note".to_string());
+    query.columns(vec!["title", "pinned", "attachment"]);
+    query.values(vec![&r"C:\it's".to_string(), &false, &Value::VecU8(vec![1, 171])]);
+    let sql = my.dialect().build_insert(&query, &BuildMode::Debug); 
+    println!("{}", sql);
+    assert!(sql.sql.contains(r"VALUES ('C:\\it''s', FALSE, X'01ab')"));
+}
+
+#[test]
+fn test_build_with_dialect_offline(){
+    let mut query = Select::new();
+    query.columns(vec!["username", "email"]);
+    query.from(&"users".to_string());
+    query.add_filter(&Filter::new("username", EQ, &"Hello".to_string()));
+    let pg = query.build_with(&PostgresDialect::new());
+    assert_eq!(pg.sql.trim(), "SELECT username, email\n     FROM users\n    WHERE username = $1");
+    assert_eq!(pg.params, vec![Value::String("Hello".to_owned())]);
+    let my = query.build_with(&MysqlDialect::new());
+    assert!(my.sql.contains("WHERE username = ?"));
+}
+
+#[test]
+fn test_sqlserver_select_golden(){
+    let ms = SqlServerDialect::new();
+    let mut query = Select::new();
+    query.columns(vec!["username", "email"]);
+    query.from(&"users".to_string());
+    query.add_filter(&Filter::new("username", EQ, &"Hello".to_string()));
+    query.set_limit(10);
+    let sql = query.build_with(&ms);
+    println!("{}", sql);
+    assert_eq!(sql.sql.trim(), "SELECT TOP (10) username, email\n     FROM users\n    WHERE username = @p1");
+    assert_eq!(sql.params, vec![Value::String("Hello".to_owned())]);
+
+    query.set_offset(20);
+    let sql = query.build_with(&ms);
+    println!("{}", sql);
+    assert!(sql.sql.contains("SELECT username, email"));
+    assert!(sql.sql.ends_with("\n ORDER BY (SELECT NULL)\n   OFFSET 20 ROWS\n    FETCH NEXT 10 ROWS ONLY"));
+
+    let mut query = Select::new();
+    query.column("order_id");
+    query.from(&"order".to_string());
+    query.order_by.push("order_id".DESC());
+    query.set_offset(5);
+    let sql = query.build_with(&ms);
+    println!("{}", sql);
+    assert!(sql.sql.contains("FROM [order]"));
+    assert!(sql.sql.ends_with("ORDER BY order_id DESC\n   OFFSET 5 ROWS"));
+}
+
+#[test]
+fn test_sqlserver_output_golden(){
+    let ms = SqlServerDialect::new();
+    let mut insert = Insert::into(&"product".to_string());
+    insert.columns(vec!["product_id", "name"]);
+    insert.values(vec![&1, &"Laptop".to_string()]);
+    insert.return_all();
+    let sql = insert.build_with(&ms);
+    println!("{}", sql);
+    assert_eq!(sql.sql.trim(), "INSERT INTO product( product_id, name ) \n   OUTPUT INSERTED.*\n   VALUES (@p1, @p2)");
+
+    let mut update = Update::table(&"product".to_string());
+    update.set("name", &"Desktop".to_string());
+    update.add_filter(&"product_id".EQ(&1));
+    update.return_columns = vec![ColumnName::from("name")];
+    let sql = update.build_with(&ms);
+    println!("{}", sql);
+    assert!(sql.sql.contains("SET name = @p1\n   OUTPUT INSERTED.name\n    WHERE product_id = @p2"));
+
+    let mut delete = Delete::from(&"product".to_string());
+    delete.add_filter(&"product_id".EQ(&1));
+    delete.return_all();
+    let sql = delete.build_with(&ms);
+    println!("{}", sql);
+    assert!(sql.sql.contains("DELETE FROM product\n   OUTPUT DELETED.*\n    WHERE product_id = @p1"));
+    assert!(!sql.sql.contains("RETURNING"));
+}
+
+#[test]
+fn test_sqlserver_is_distinct_from(){
+    let ms = SqlServerDialect::new();
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"customer".to_string());
+    query.add_filter(&"email".IS_DISTINCT_FROM(&"backup_email"));
+    query.add_filter(&"phone".IS_NOT_DISTINCT_FROM(&"backup_phone"));
+    let sql = query.build_with(&ms);
+    println!("{}", sql);
+    assert!(sql.sql.contains("WHERE NOT EXISTS (SELECT email INTERSECT SELECT backup_email)"));
+    assert!(sql.sql.contains("EXISTS (SELECT phone INTERSECT SELECT backup_phone)"));
+    assert!(!sql.sql.contains("NOT EXISTS (SELECT phone"));
+    assert!(!sql.sql.contains("IS NOT"));
+}
+
+#[test]
+fn test_parse_select_round_trip(){
+    let pg = PostgresDialect::new();
+    let sql = "SELECT p.name, COUNT(*) AS orders
+                 FROM product AS p
+            LEFT JOIN line_item li ON li.product_id = p.product_id
+                WHERE p.active = true AND (p.price > 100 OR p.name LIKE 'Lap%')
+             GROUP BY p.name
+             ORDER BY p.name DESC
+                LIMIT 10 OFFSET 20";
+    let query = parse(sql).unwrap();
+    let sql = pg.build_query(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.sql.contains("LEFT JOIN line_item AS li"));
+    assert!(sql.sql.contains("( p.price > 100 OR p.name LIKE 'Lap%'"));
+    assert!(sql.sql.contains("LIMIT 10"));
+    let again = pg.build_query(&parse(&sql.sql).unwrap(), &BuildMode::Debug);
+    assert_eq!(again.sql, sql.sql);
+}
+
+#[test]
+fn test_parse_modify_and_reject(){
+    let pg = PostgresDialect::new();
+    let mut query = parse("DELETE FROM note WHERE archived = TRUE RETURNING note_id").unwrap();
+    if let Query::Delete(ref mut delete) = query {
+        delete.add_filter(&"tenant_id".EQ(&7));
+    }
+    let sql = pg.build_query(&query, &BuildMode::Standard);
+    println!("{}", sql);
+    assert!(sql.sql.contains("WHERE archived = $1"));
+    assert!(sql.sql.contains("AND tenant_id = $2"));
+    assert_eq!(sql.params, vec![Value::Bool(true), Value::I32(7)]);
+
+    assert!(parse("SELECT price * 2 FROM product").is_err());
+    assert!(parse("SELECT name FROM product WHERE price > ?").is_err());
+    assert!(parse("SELECT name FROM a UNION SELECT name FROM b").is_err());
+    assert!(parse("SELECT name FROM product WHERE price > 1e5").is_err());
+
+    let query = parse("UPDATE note SET body = NULL WHERE note_id = 3").unwrap();
+    let sql = pg.build_query(&query, &BuildMode::Standard);
+    assert!(sql.sql.contains("SET body = NULL"));
+    assert_eq!(sql.params, vec![Value::I32(3)]);
+    let query = parse("INSERT INTO note (title, body) VALUES ('draft', NULL)").unwrap();
+    let sql = pg.build_query(&query, &BuildMode::Debug);
+    assert!(sql.sql.contains("VALUES ('draft', NULL)"));
+}
+
+/// query the shadow tables of the tenant
+struct TenantShadow(i32);
+
+impl Fold for TenantShadow {
+    fn fold_table_name(&mut self, table_name: TableName) -> TableName {
+        TableName { name: format!("{}_shadow", table_name.name), ..table_name }
+    }
+
+    fn fold_select(&mut self, select: Select) -> Select {
+        let mut select = walk_select(self, select);
+        select.add_filter(&"tenant_id".EQ(&self.0));
+        select
+    }
+}
+
+#[test]
+fn test_fold_tenant_shadow_tables(){
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"product".to_string());
+    query.add_filter(&"price".GT(&100));
+    let query = TenantShadow(7).fold_query(Query::Select(query));
+    let sql = PostgresDialect::new().build_query(&query, &BuildMode::Standard);
+    println!("{}", sql);
+    assert!(sql.sql.contains("FROM product_shadow"));
+    assert!(sql.sql.contains("AND tenant_id = $2"));
+    assert_eq!(sql.params, vec![Value::I32(100), Value::I32(7)]);
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_rewriter_forbids_delete_without_where(){
+    let mut lite = Platform::sqlite();
+    lite.add_rewriter(Box::new(|query: Query| {
+        let unfiltered = match query {
+            Query::Delete(ref delete) => delete.filters.is_empty(),
+            _ => false,
+        };
+        if unfiltered {
+            Err(DbError::new("DELETE without WHERE is not allowed"))
+        } else {
+            Ok(query)
+        }
+    }));
+    let result = lite.delete(&Delete::from(&"users"));
+    assert!(result.is_err());
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_pool_rewriter_applies_to_retrieve(){
+    let mut pool = rustorm::platform::pool::ManagedPool::init("sqlite:///:memory:", 1).unwrap();
+    pool.add_rewriter(std::sync::Arc::new(|query: Query| {
+        match query {
+            Query::Select(mut select) => {
+                select.add_filter(&"tenant".EQ(&"acme".to_string()));
+                Ok(Query::Select(select))
+            }
+            query => Ok(query),
+        }
+    }));
+    {
+        let db = pool.connect().unwrap();
+        db.execute_sql("CREATE TABLE account (name TEXT, tenant TEXT)", &[]).unwrap();
+        db.execute_sql("INSERT INTO account (name, tenant) VALUES ('a', 'acme'), ('b', 'other')", &[]).unwrap();
+    }
+    // the rewriter of the pool is in every checked out connection
+    let db = pool.connect().unwrap();
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"account".to_string());
+    let result = query.retrieve(db.as_ref()).unwrap();
+    assert_eq!(result.dao.len(), 1);
+    assert_eq!(result.dao[0]["name"], Value::String("a".to_owned()));
+    let one = query.retrieve_one(db.as_ref()).unwrap().unwrap();
+    assert_eq!(one["name"], Value::String("a".to_owned()));
+}
+
+#[test]
+fn test_evaluate_filter_three_valued(){
+    let mut dao = Dao::new();
+    dao.insert("name".to_owned(), Value::String("Lee".to_owned()));
+    dao.insert("age".to_owned(), Value::I64(30));
+    // email is NULL
+    assert_eq!("email".EQ(&"lee@mail.com".to_string()).evaluate(&dao).unwrap(), None);
+    assert_eq!("email".NEQ(&"lee@mail.com".to_string()).evaluate(&dao).unwrap(), None);
+    assert_eq!("email".IS_NULL().evaluate(&dao).unwrap(), Some(true));
+    assert_eq!("age".GT(&18).or("email".IS_NOT_NULL()).evaluate(&dao).unwrap(), Some(true));
+    assert_eq!("age".LT(&18).or("email".LIKE(&"%@mail.com".to_string())).evaluate(&dao).unwrap(), None);
+    assert_eq!("age".LT(&18).and("email".LIKE(&"%@mail.com".to_string())).evaluate(&dao).unwrap(), Some(false));
+    assert_eq!("name".ILIKE(&"l_E".to_string()).evaluate(&dao).unwrap(), Some(true));
+    assert_eq!("age".NOT_IN(&Operand::Vec(vec![Operand::Value(Value::I32(20)), Operand::Value(Value::F64(30.0))])).evaluate(&dao).unwrap(), Some(false));
+    assert!(!"email".NOT_IN(&Operand::Vec(vec![Operand::Value(Value::I32(20))])).matches(&dao).unwrap());
+    // the . is literal in SIMILAR TO, only % and _ are wildcards
+    assert_eq!("name".SIMILAR_TO(&"L.e".to_string()).evaluate(&dao).unwrap(), Some(false));
+    assert_eq!("name".SIMILAR_TO(&"L(e|o)_".to_string()).evaluate(&dao).unwrap(), Some(true));
+}
+
+#[test]
+fn test_evaluate_select_in_memory(){
+    let people: Vec<Dao> = vec![("Ann", Some(41)), ("Bob", None), ("Cid", Some(17)), ("Dee", Some(25))]
+        .into_iter()
+        .map(|(name, age)| {
+            let mut dao = Dao::new();
+            dao.insert("name".to_owned(), Value::String(name.to_owned()));
+            if let Some(age) = age {
+                dao.insert("age".to_owned(), Value::I32(age));
+            }
+            dao
+        })
+        .collect();
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"person".to_string());
+    query.add_filter(&"age".GTE(&18).or("age".IS_NULL()));
+    query.order_by.push("age".DESC());
+    query.set_limit(2);
+    let rows = query.evaluate(&people).unwrap();
+    let names: Vec<&Value> = rows.iter().map(|row| &row["name"]).collect();
+    assert_eq!(names, vec![&Value::String("Bob".to_owned()), &Value::String("Ann".to_owned())]);
+    assert!(rows.iter().all(|row| !row.contains_key("age")));
+}
+
+fn product_table() -> Table {
+    let column = |name: &str, data_type: Type| Column {
+        table: Some("product".to_owned()),
+        name: name.to_owned(),
+        data_type: data_type,
+        db_data_type: String::new(),
+        is_primary: false,
+        is_unique: false,
+        default: None,
+        comment: None,
+        not_null: false,
+        foreign: None,
+        is_inherited: false,
+    };
+    let mut table = Table::with_name("product");
+    table.columns = vec![column("name", Type::String),
+                         column("price", Type::I32),
+                         column("created", Type::DateTime)];
+    table
+}
+
+#[test]
+fn test_rest_query_string(){
+    let pg = PostgresDialect::new();
+    let table = product_table();
+    let query = from_query_string(&table,
+                                  "?name=ilike.*phone*&price=gte.100&price=not.in.(1,2)&order=created.desc.nullslast&limit=20&offset=40")
+        .unwrap();
+    let sql = query.build_with(&pg);
+    println!("{}", sql);
+    assert!(sql.sql.contains("FROM product"));
+    assert!(sql.sql.contains("name ILIKE $1"));
+    assert!(sql.sql.contains("price >= $2"));
+    assert!(sql.sql.contains("price NOT IN ($3"));
+    assert!(sql.sql.contains("ORDER BY created DESC NULLS LAST"));
+    assert!(sql.sql.contains("LIMIT 20"));
+    assert!(sql.sql.contains("OFFSET 40"));
+    assert_eq!(sql.params,
+               vec![Value::String("%phone%".to_owned()), Value::I32(100), Value::I32(1), Value::I32(2)]);
+}
+
+#[test]
+fn test_rest_query_string_rejects(){
+    let table = product_table();
+    assert!(from_query_string(&table, "color=eq.red").is_err());
+    assert!(from_query_string(&table, "price=eq.cheap").is_err());
+    assert!(from_query_string(&table, "price=like.*9").is_err());
+    assert!(from_query_string(&table, "name=matches.x").is_err());
+    assert!(from_query_string(&table, "order=price.sideways").is_err());
+    assert!(from_query_string(&table, "limit=-1").is_err());
+}
+
+#[test]
+fn test_select_json_round_trip(){
+    let pg = PostgresDialect::new();
+    let query = parse("SELECT p.name, COUNT(*) AS orders
+                         FROM product AS p
+                    LEFT JOIN line_item li ON li.product_id = p.product_id
+                        WHERE p.price > 100 AND (p.active = true OR p.name LIKE 'Lap%')
+                     GROUP BY p.name
+                     ORDER BY p.name DESC NULLS LAST
+                        LIMIT 10 OFFSET 20").unwrap();
+    let mut query = match query {
+        Query::Select(select) => select,
+        _ => unreachable!(),
+    };
+    query.add_filter(&"p.weight".LT(&2.5f32));
+    query.add_filter(&"p.stock".GTE(&Value::U64(18446744073709551615)));
+    query.add_filter(&"p.image".NEQ(&Value::VecU8(vec![0, 159, 255])));
+    let json = query.to_json().to_string();
+    println!("{}", json);
+    assert!(json.contains("\"version\":1"));
+    assert!(json.contains("{\"type\":\"F32\",\"value\":2.5}"));
+    let loaded = Select::from_json(&Json::from_str(&json).unwrap()).unwrap();
+    let sql = query.build_with(&pg);
+    let again = loaded.build_with(&pg);
+    assert_eq!(again.sql, sql.sql);
+    assert_eq!(again.params, sql.params);
+    assert!(again.params.contains(&Value::F32(2.5)));
+    assert_eq!(loaded.to_json().to_string(), json);
+}
+
+#[test]
+fn test_select_json_validation(){
+    let load = |json: &str| Select::from_json(&Json::from_str(json).unwrap());
+    assert!(load(r#"{"version":1,"select":{"limit":5}}"#).is_ok());
+    assert!(load(r#"{"select":{"limit":5}}"#).is_err());
+    assert!(load(r#"{"version":2,"select":{"limit":5}}"#).is_err());
+    assert!(load(r#"{"version":1,"select":{"limt":5}}"#).is_err());
+    let err = load(r#"{"version":1,"select":{"filters":[{"connector":"AND","condition":{
+                       "left":{"column":{"column":"age"}},"equality":"GT",
+                       "right":{"value":{"type":"I8","value":300}}}}]}}"#)
+        .unwrap_err();
+    assert!(format!("{}", err).contains("select.filters[0].condition.right.value.value"));
+    let filter = "age".GT(&18).or("age".IS_NULL());
+    let loaded = Filter::from_json(&filter.to_json()).unwrap();
+    assert_eq!(loaded.to_json(), filter.to_json());
+    // the raw sql and the unknown functions are written into the sql as is
+    let raw = r#"{"version":1,"select":{"from":[{"source":{"raw":{"sql":"users; DROP TABLE users"}}}]}}"#;
+    assert!(load(raw).is_err());
+    assert!(Select::from_trusted_json(&Json::from_str(raw).unwrap()).is_ok());
+    let function = r#"{"version":1,"select":{"enumerated_fields":[{"operand":{"source":{"function":{
+                         "function":"pg_sleep","params":[{"value":{"type":"I32","value":10}}]}}}}]}}"#;
+    assert!(load(function).is_err());
+    let filter = "price".NEQ(&Value::F64(::std::f64::NAN)).and("price".LT(&Value::F64(::std::f64::INFINITY)));
+    let loaded = Filter::from_json(&Json::from_str(&filter.to_json().to_string()).unwrap()).unwrap();
+    assert_eq!(loaded.to_json(), filter.to_json());
+}
+
+#[test]
+fn test_select_json_untrusted_rejections(){
+    let rejected = |query: &Select| Select::from_json(&query.to_json()).is_err();
+    let with_field = |operand: Operand| {
+        let mut query = Select::new();
+        query.enumerated_fields.push(operand.into());
+        query.from(&"users".to_string());
+        query
+    };
+    assert!(!rejected(&with_field(STRING_AGG(&"username", ", "))));
+    // the number of parameters of the functions
+    assert!(rejected(&with_field(call("CAST", &[]))));
+    assert!(rejected(&with_field(call("TS_RANK", &[&"description"]))));
+    // the unit of DATE_TRUNC and the separator of STRING_AGG are written into the sql
+    assert!(rejected(&with_field(DATE_TRUNC("fortnight", &"created"))));
+    assert!(rejected(&with_field(STRING_AGG(&"username", "\\'"))));
+    // the keyset values do not match the keyset columns
+    let mut query = with_field(Operand::ColumnName(ColumnName::from("id")));
+    query.keyset(vec!["created", "id"], Direction::ASC, 20);
+    query.keyset.as_mut().unwrap().after = vec![Value::I32(5)];
+    assert!(rejected(&query));
+    // LATERAL is not supported in every database
+    let mut query = with_field(Operand::ColumnName(ColumnName::from("id")));
+    query.join(Join::cross(&"recent_orders").LATERAL());
+    assert!(rejected(&query));
+    assert!(Select::from_trusted_json(&query.to_json()).is_ok());
+}
+
+#[test]
+fn test_text_search_golden(){
+    let mut query = Select::new();
+    query.column("name");
+    query.enumerated_fields.push(TS_HEADLINE(&"description", "english", "wireless phone").into());
+    query.from(&"product".to_string());
+    query.add_filter(&"description".MATCH("english", &"wireless phone".to_string()));
+    query.order_by.push(TS_RANK(&"description", "english", "wireless phone").DESC());
+
+    let sql = PostgresDialect::new().build_select(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.sql.contains("SELECT name, ts_headline('english', description, plainto_tsquery('english', 'wireless phone'))"));
+    assert!(sql.sql.contains("WHERE to_tsvector('english', description) @@ plainto_tsquery('english', 'wireless phone')"));
+    assert!(sql.sql.contains("ORDER BY ts_rank(to_tsvector('english', description), plainto_tsquery('english', 'wireless phone')) DESC"));
+
+    // the headline is not supported in mysql, nor the full text search in sql server
+    assert!(query.build_with(&MysqlDialect::new()).error.is_some());
+    assert!(query.build_with(&SqlServerDialect::new()).error.is_some());
+    query.enumerated_fields.pop();
+    let sql = MysqlDialect::new().build_select(&query, &BuildMode::Debug);
+    println!("{}", sql);
+    assert!(sql.error.is_none());
+    assert!(sql.sql.contains("WHERE MATCH (description) AGAINST ('wireless phone' IN NATURAL LANGUAGE MODE)"));
+    assert!(sql.sql.contains("ORDER BY MATCH (description) AGAINST ('wireless phone' IN NATURAL LANGUAGE MODE) DESC"));
+    // the rank in sqlite is of the fts5 table the column is qualified with
+    assert!(query.build_with(&SqliteDialect::new()).error.is_some());
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_fts5_search(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    db.execute_sql("CREATE VIRTUAL TABLE product_fts USING fts5(name, description)", &[]).unwrap();
+    let products = [("Phone", "a wireless phone with a long battery"),
+                    ("Headset", "wireless headset for the phone, wireless charging"),
+                    ("Cable", "usb-c cable for charging the phone")];
+    for &(name, description) in &products {
+        db.execute_sql("INSERT INTO product_fts (name, description) VALUES (?, ?)",
+                       &[Value::String(name.to_owned()), Value::String(description.to_owned())])
+            .unwrap();
+    }
+    let mut query = Select::new();
+    query.column("name");
+    query.enumerated_fields.push(Field {
+        operand: TS_HEADLINE(&"product_fts.description", "english", "wireless phone"),
+        name: Some("headline".to_owned()),
+    });
+    query.from(&"product_fts".to_string());
+    query.add_filter(&"product_fts.description".MATCH("english", &"wireless phone".to_string()));
+    query.order_by.push(TS_RANK(&"product_fts.description", "english", "wireless phone").DESC());
+    let rows = query.retrieve(db).unwrap().dao;
+    let names: Vec<&Value> = rows.iter().map(|row| &row["name"]).collect();
+    assert_eq!(names, vec![&Value::String("Headset".to_owned()), &Value::String("Phone".to_owned())]);
+    assert_eq!(rows[0]["headline"],
+               Value::String("<b>wireless</b> headset for the <b>phone</b>, <b>wireless</b> charging".to_owned()));
+
+    // the dash is a word separator, not the fts5 query syntax
+    let mut query = Select::new();
+    query.column("name");
+    query.from(&"product_fts".to_string());
+    query.add_filter(&"description".MATCH("english", &"usb-c cable".to_string()));
+    let rows = query.retrieve(db).unwrap().dao;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["name"], Value::String("Cable".to_owned()));
+}
+
+/// a row of the report, not a table
+#[derive(IsDao)]
+#[derive(Debug, PartialEq)]
+struct CustomerOrders {
+    customer: String,
+    orders: i64,
+    biggest: Option<f64>,
+}
+
+#[cfg(feature = "sqlite")]
+fn customer_orders_query() -> Select {
+    let mut query = Select::new();
+    query.column("customer");
+    query.enumerated_fields.push(Field { operand: rustorm::query::COUNT(&"*"), name: Some("orders".to_owned()) });
+    query.enumerated_fields.push(Field { operand: rustorm::query::function::MAX(&"total"), name: Some("biggest".to_owned()) });
+    query.from(&"orders".to_string());
+    query.group_by.push(Operand::ColumnName(ColumnName::from("customer")));
+    query.order_by.push("customer".ASC());
+    query
+}
+
+#[test]
+fn test_cast_as_report(){
+    let mut ann = Dao::new();
+    ann.insert("customer".to_owned(), Value::String("Ann".to_owned()));
+    ann.insert("orders".to_owned(), Value::I32(2));
+    ann.insert("biggest".to_owned(), Value::F64(12.5));
+    // the biggest total is NULL
+    let mut bob = Dao::new();
+    bob.insert("customer".to_owned(), Value::String("Bob".to_owned()));
+    bob.insert("orders".to_owned(), Value::I64(1));
+    let result = DaoResult {
+        dao: vec![ann, bob],
+        renamed_columns: vec![],
+        total: None,
+        page: None,
+        page_size: None,
+        cursor: None,
+    };
+    let rows: Vec<CustomerOrders> = result.cast_as();
+    assert_eq!(rows,
+               vec![CustomerOrders { customer: "Ann".to_owned(), orders: 2, biggest: Some(12.5) },
+                    CustomerOrders { customer: "Bob".to_owned(), orders: 1, biggest: None }]);
+    assert!(!rows[1].to_dao().contains_key("biggest"));
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_sqlite_collect_as_report(){
+    let db = rustorm::platform::pool::db_with_url("sqlite:///:memory:").unwrap();
+    let db = db.as_ref();
+    db.execute_sql("CREATE TABLE orders (customer TEXT, total REAL)", &[]).unwrap();
+    db.execute_sql("INSERT INTO orders VALUES ('Ann', 12.5), ('Ann', 3), ('Bob', NULL)", &[]).unwrap();
+    let rows: Vec<CustomerOrders> = customer_orders_query().collect_as(db).unwrap();
+    assert_eq!(rows,
+               vec![CustomerOrders { customer: "Ann".to_owned(), orders: 2, biggest: Some(12.5) },
+                    CustomerOrders { customer: "Bob".to_owned(), orders: 1, biggest: None }]);
+    // the COUNT(*) of the query stat is skipped, and the flag is kept for the other uses of the query
+    let mut query = customer_orders_query();
+    query.enable_query_stat = true;
+    let rows: Vec<CustomerOrders> = query.collect_as(db).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(query.enable_query_stat);
+}